@@ -0,0 +1,63 @@
+use criterion::{black_box, Criterion, criterion_group, criterion_main};
+
+use caissa::board::board::ChessBoard;
+use caissa::game::fen;
+use caissa::hash::ZobristHash;
+
+// The start, a busy middlegame (Kiwipete), an open middlegame and a pawn endgame
+const POSITIONS: [(&str, &str); 4] = [
+    ("start", "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"),
+    ("kiwipete", "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1"),
+    ("middlegame", "r1bq1rk1/pp2bppp/2n1pn2/3p4/2PP4/2N1PN2/PP1B1PPP/R2QKB1R w KQ - 0 8"),
+    ("endgame", "8/5k2/3p4/1p1Pp2p/pP2Pp1P/P4P1K/8/8 b - - 99 50")
+];
+
+fn boards() -> Vec<(&'static str, ChessBoard)> {
+    POSITIONS.iter().map(|(name, fen)| (*name, fen::new_board(fen).unwrap())).collect()
+}
+
+fn generate_moves(c: &mut Criterion) {
+    let mut group = c.benchmark_group("generate_moves");
+    for (name, board) in boards() {
+        group.bench_function(name, |b| b.iter(|| black_box(&board).generate_moves(board.state.team_to_play)));
+    }
+    group.finish();
+}
+
+fn attacks(c: &mut Criterion) {
+    let mut group = c.benchmark_group("attacks");
+    for (name, board) in boards() {
+        group.bench_function(name, |b| b.iter(|| black_box(&board).attacks(board.state.team_to_play)));
+    }
+    group.finish();
+}
+
+// Every legal move of the position played and taken back, the way the search walks the tree
+fn play_and_undo(c: &mut Criterion) {
+    let mut group = c.benchmark_group("play_move_undo_move");
+    for (name, mut board) in boards() {
+        let moves = board.generate_moves(board.state.team_to_play);
+        group.bench_function(name, |b| b.iter(|| {
+            for mv in &moves {
+                let state = board.state;
+                if let Some(played) = board.play_move(mv.origin, mv.target, mv.get_promotion(), true) {
+                    board.undo_move(&played);
+                }
+                board.state = state;
+            }
+        }));
+    }
+    group.finish();
+}
+
+fn zobrist_hash(c: &mut Criterion) {
+    let zobrist = ZobristHash::new();
+    let mut group = c.benchmark_group("zobrist_hash");
+    for (name, board) in boards() {
+        group.bench_function(name, |b| b.iter(|| zobrist.hash(black_box(&board))));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, generate_moves, attacks, play_and_undo, zobrist_hash);
+criterion_main!(benches);
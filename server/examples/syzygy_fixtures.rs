@@ -0,0 +1,720 @@
+// Writes the small Syzygy tables the syzygy tests probe:
+//
+//     cargo run --release --example syzygy_fixtures -- tests/syzygy
+//
+// Every ending is solved by retrograde analysis on top of the engine's move generator, then stored in the
+// Syzygy format with runs of equal values as pair symbols, the simplest coding the format allows. The index
+// follows the probing code in src/engine/syzygy.rs: kings first, then the other pieces of each side.
+
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::env;
+use std::fs;
+use std::path::Path;
+
+use caissa::board::{Pieces, Teams};
+use caissa::board::board::ChessBoard;
+use caissa::game::fen;
+
+const WDL_MAGIC: [u8; 4] = [0x71, 0xE8, 0x23, 0x5D];
+const DTZ_MAGIC: [u8; 4] = [0xD7, 0x66, 0x0C, 0xA5];
+const FLAG_WIN_PLIES: u8 = 4;
+const FLAG_LOSS_PLIES: u8 = 8;
+const FLAG_SINGLE_VALUE: u8 = 128;
+
+// Each ending only leads into the ones solved before it
+const TABLES: [&str; 6] = ["KNvK", "KBvK", "KRvK", "KQvK", "KPvK", "KNNvK"];
+
+// Our pieces and their letters by Syzygy piece code minus one
+const PIECES: [usize; 6] = [Pieces::PAWN, Pieces::KNIGHT, Pieces::BISHOP, Pieces::ROOK, Pieces::QUEEN, Pieces::KING];
+const LETTERS: [char; 6] = ['P', 'N', 'B', 'R', 'Q', 'K'];
+
+// 64 byte blocks holding at most MAX_BLOCK_VALUES values, with a sparse index entry every 2^SPAN_LOG values
+const BLOCK_LOG: u8 = 6;
+const SPAN_LOG: u8 = 8;
+const MAX_BLOCK_VALUES: usize = 60000;
+// A pair symbol expands to at most 256 values, its length minus one being stored in a byte
+const MAX_RUN_LOG: u32 = 8;
+const MAX_CODE_LENGTH: usize = 24;
+
+// Moves of a position, packed as the index of the position reached or its outcome when it is in another table
+const ZEROING: u32 = 1 << 31;
+const OUTCOME: u32 = 3 << 29;
+const DRAW: u32 = 1 << 29;
+const LOSS: u32 = 2 << 29;
+const WIN: u32 = 3 << 29;
+const POSITION: u32 = (1 << 29) - 1;
+
+struct IndexTables {
+    binomial: [[u64; 64]; 6],
+    map_b1h1h7: [u64; 64],
+    map_a1d1d4: [u64; 64],
+    map_kk: [[u64; 64]; 10],
+    lead_pawn_idx: [u64; 64],
+    lead_pawns_size: [u64; 4]
+}
+
+fn diagonal_offset(square: usize) -> i32 {
+    (square / 8) as i32 - (square % 8) as i32
+}
+
+fn adjacent(first: usize, second: usize) -> bool {
+    (first / 8).abs_diff(second / 8) <= 1 && (first % 8).abs_diff(second % 8) <= 1
+}
+
+impl IndexTables {
+    fn new() -> IndexTables {
+        let mut tables = IndexTables {
+            binomial: [[0; 64]; 6],
+            map_b1h1h7: [0; 64],
+            map_a1d1d4: [0; 64],
+            map_kk: [[0; 64]; 10],
+            lead_pawn_idx: [0; 64],
+            lead_pawns_size: [0; 4]
+        };
+
+        let mut code = 0;
+        for square in 0..64 {
+            if diagonal_offset(square) < 0 {
+                tables.map_b1h1h7[square] = code;
+                code += 1;
+            }
+        }
+        code = 0;
+        let mut diagonal = Vec::new();
+        for square in (0..28).filter(|square| square % 8 < 4) {
+            if diagonal_offset(square) < 0 {
+                tables.map_a1d1d4[square] = code;
+                code += 1;
+            } else if diagonal_offset(square) == 0 {
+                diagonal.push(square);
+            }
+        }
+        for square in diagonal {
+            tables.map_a1d1d4[square] = code;
+            code += 1;
+        }
+
+        code = 0;
+        let mut both_on_diagonal = Vec::new();
+        for index in 0..10 {
+            for first in 0..28 {
+                if first % 8 > 3 || tables.map_a1d1d4[first] != index as u64 || (index == 0 && first != 1) {
+                    continue;
+                }
+                for second in 0..64 {
+                    if adjacent(first, second) || (diagonal_offset(first) == 0 && diagonal_offset(second) > 0) {
+                        continue;
+                    } else if diagonal_offset(first) == 0 && diagonal_offset(second) == 0 {
+                        both_on_diagonal.push((index, second));
+                    } else {
+                        tables.map_kk[index][second] = code;
+                        code += 1;
+                    }
+                }
+            }
+        }
+        for (index, square) in both_on_diagonal {
+            tables.map_kk[index][square] = code;
+            code += 1;
+        }
+
+        tables.binomial[0][0] = 1;
+        for n in 1..64 {
+            for k in 0..6.min(n + 1) {
+                let left = if k > 0 { tables.binomial[k - 1][n - 1] } else { 0 };
+                let right = if k < n { tables.binomial[k][n - 1] } else { 0 };
+                tables.binomial[k][n] = left + right;
+            }
+        }
+
+        // A single lead pawn is numbered up the board on its file
+        for file in 0..4 {
+            for rank in 1..7 {
+                tables.lead_pawn_idx[rank * 8 + file] = tables.lead_pawns_size[file];
+                tables.lead_pawns_size[file] += 1;
+            }
+        }
+        tables
+    }
+}
+
+// How a table orders and groups its pieces
+struct Layout {
+    name: &'static str,
+    // Syzygy piece codes: the pawn, the kings, then the other white and black pieces
+    pieces: Vec<u8>,
+    has_pawns: bool,
+    has_unique_pieces: bool,
+    group_len: Vec<usize>,
+    // Per file, the factor of every group and the table size last
+    group_idx: Vec<Vec<u64>>
+}
+
+impl Layout {
+    fn new(name: &'static str, index: &IndexTables) -> Layout {
+        let (white, black) = name.split_once('v').unwrap();
+        let code = |letter: char| LETTERS.iter().position(|other| *other == letter).unwrap() as u8 + 1;
+        let mut pieces: Vec<u8> = white.chars().filter(|letter| *letter == 'P').map(code).collect();
+        assert!(pieces.len() <= 1 && !black.contains('P'), "only a single white pawn is supported");
+        pieces.extend([6, 14]);
+        pieces.extend(white.chars().filter(|letter| !"PK".contains(*letter)).map(code));
+        pieces.extend(black.chars().filter(|letter| *letter != 'K').map(|letter| code(letter) + 8));
+
+        let has_pawns = pieces[0] == 1;
+        let has_unique_pieces = [white, black].iter()
+            .any(|side| "QRBNP".chars().any(|letter| side.matches(letter).count() == 1));
+        let mut first_len: i32 = if has_pawns { 0 } else if has_unique_pieces { 3 } else { 2 };
+        let mut group_len = vec![1];
+        for i in 1..pieces.len() {
+            first_len -= 1;
+            if first_len > 0 || pieces[i] == pieces[i - 1] {
+                *group_len.last_mut().unwrap() += 1;
+            } else {
+                group_len.push(1);
+            }
+        }
+
+        let files = if has_pawns { 4 } else { 1 };
+        let group_idx = (0..files).map(|file| {
+            let mut idx = if has_pawns {
+                index.lead_pawns_size[file]
+            } else if has_unique_pieces {
+                31332
+            } else {
+                462
+            };
+            let mut factors = vec![1];
+            let mut free_squares = 64 - group_len[0];
+            for length in &group_len[1..] {
+                factors.push(idx);
+                idx *= index.binomial[*length][free_squares];
+                free_squares -= length;
+            }
+            factors.push(idx);
+            factors
+        }).collect();
+        Layout { name, pieces, has_pawns, has_unique_pieces, group_len, group_idx }
+    }
+
+    fn files(&self) -> usize {
+        self.group_idx.len()
+    }
+
+    fn size(&self, file: usize) -> u64 {
+        *self.group_idx[file].last().unwrap()
+    }
+
+    // File and index of the pieces on these squares, in table order
+    fn encode(&self, index: &IndexTables, squares: &mut [usize]) -> (usize, u64) {
+        let file = if self.has_pawns { (squares[0] % 8).min(7 - squares[0] % 8) } else { 0 };
+        if squares[0] % 8 > 3 {
+            squares.iter_mut().for_each(|square| *square ^= 7);
+        }
+        let mut idx;
+        if self.has_pawns {
+            idx = index.lead_pawn_idx[squares[0]];
+        } else {
+            if squares[0] / 8 > 3 {
+                squares.iter_mut().for_each(|square| *square ^= 56);
+            }
+            for i in 0..self.group_len[0] {
+                let offset = diagonal_offset(squares[i]);
+                if offset == 0 {
+                    continue;
+                }
+                if offset > 0 {
+                    squares[i..].iter_mut().for_each(|square| *square = ((*square >> 3) | (*square << 3)) & 63);
+                }
+                break;
+            }
+            if self.has_unique_pieces {
+                let (s0, s1, s2) = (squares[0], squares[1], squares[2]);
+                let adjust1 = (s1 > s0) as usize;
+                let adjust2 = (s2 > s0) as usize + (s2 > s1) as usize;
+                idx = if diagonal_offset(s0) != 0 {
+                    (index.map_a1d1d4[s0] * 63 + (s1 - adjust1) as u64) * 62 + (s2 - adjust2) as u64
+                } else if diagonal_offset(s1) != 0 {
+                    (6 * 63 + (s0 / 8) as u64 * 28 + index.map_b1h1h7[s1]) * 62 + (s2 - adjust2) as u64
+                } else if diagonal_offset(s2) != 0 {
+                    6 * 63 * 62 + 4 * 28 * 62 + (s0 / 8) as u64 * 7 * 28 + (s1 / 8 - adjust1) as u64 * 28 + index.map_b1h1h7[s2]
+                } else {
+                    6 * 63 * 62 + 4 * 28 * 62 + 4 * 7 * 28 + (s0 / 8) as u64 * 7 * 6 + (s1 / 8 - adjust1) as u64 * 6
+                        + (s2 / 8 - adjust2) as u64
+                };
+            } else {
+                idx = index.map_kk[index.map_a1d1d4[squares[0]] as usize][squares[1]];
+            }
+        }
+
+        let mut group_start = self.group_len[0];
+        for (group, length) in self.group_len.iter().enumerate().skip(1) {
+            squares[group_start..group_start + length].sort();
+            let mut n = 0;
+            for i in 0..*length {
+                let square = squares[group_start + i];
+                let adjust = squares[..group_start].iter().filter(|previous| square > **previous).count();
+                n += index.binomial[i + 1][square - adjust];
+            }
+            idx += n * self.group_idx[file][group];
+            group_start += length;
+        }
+        (file, idx)
+    }
+
+    // Squares of the board's pieces in table order
+    fn squares(&self, board: &ChessBoard) -> Vec<usize> {
+        let mut taken = 0u64;
+        self.pieces.iter().map(|code| {
+            let bits = board.bits.get_pieces((code >> 3) as usize, PIECES[(code & 7) as usize - 1]).0 & !taken;
+            taken |= bits & bits.wrapping_neg();
+            bits.trailing_zeros() as usize
+        }).collect()
+    }
+}
+
+// Table name of one side's material, e.g. KNN
+fn material_name(board: &ChessBoard, team: usize) -> String {
+    let mut name = String::new();
+    for code in [6, 5, 4, 3, 2, 1] {
+        for _ in 0..board.bits.get_pieces(team, PIECES[code - 1]).count_ones() {
+            name.push(LETTERS[code - 1]);
+        }
+    }
+    name
+}
+
+fn new_board(pieces: &[u8], squares: &[usize], team: usize) -> ChessBoard {
+    let mut letters = [None; 64];
+    for (code, square) in pieces.iter().zip(squares) {
+        let letter = LETTERS[(code & 7) as usize - 1];
+        letters[*square] = Some(if code >> 3 == 1 { letter.to_ascii_lowercase() } else { letter });
+    }
+    let mut placement = String::new();
+    for rank in (0..8).rev() {
+        let mut empty = 0;
+        for letter in &letters[rank * 8..rank * 8 + 8] {
+            match letter {
+                Some(letter) => {
+                    if empty > 0 {
+                        placement.push_str(&empty.to_string());
+                        empty = 0;
+                    }
+                    placement.push(*letter);
+                }
+                None => empty += 1
+            }
+        }
+        if empty > 0 {
+            placement.push_str(&empty.to_string());
+        }
+        if rank > 0 {
+            placement.push('/');
+        }
+    }
+    let team = if team == Teams::WHITE { 'w' } else { 'b' };
+    fen::new_board(&format!("{} {} - - 0 1", placement, team)).unwrap()
+}
+
+// Outcome of every position of a table for the side to move, with the plies to the next zeroing move
+struct Solved {
+    layout: Layout,
+    // First position of every side to move and file
+    base: Vec<usize>,
+    legal: Vec<bool>,
+    wdl: Vec<i8>,
+    dtz: Vec<u16>
+}
+
+impl Solved {
+    fn position(&self, index: &IndexTables, board: &ChessBoard) -> usize {
+        let (file, idx) = self.layout.encode(index, &mut self.layout.squares(board));
+        self.base[board.state.team_to_play * self.layout.files() + file] + idx as usize
+    }
+}
+
+fn solve(layout: Layout, index: &IndexTables, solved: &HashMap<&str, Solved>) -> Solved {
+    let files = layout.files();
+    let mut base = Vec::new();
+    let mut total = 0;
+    for _ in 0..2 {
+        for file in 0..files {
+            base.push(total);
+            total += layout.size(file) as usize;
+        }
+    }
+
+    // One placement standing for every index, the first one met
+    let count = layout.pieces.len();
+    let mut placements: Vec<Option<Vec<usize>>> = vec![None; total / 2];
+    let mut squares = vec![0; count];
+    let mut next = 0;
+    loop {
+        let valid = (0..count).all(|i| {
+            let pawn = layout.pieces[i] & 7 == 1;
+            squares[..i].iter().all(|other| *other != squares[i]) && !(pawn && (squares[i] < 8 || squares[i] >= 56))
+        }) && !adjacent(squares[layout.has_pawns as usize], squares[layout.has_pawns as usize + 1]);
+        if valid {
+            let (file, idx) = layout.encode(index, &mut squares.clone());
+            placements[base[file] + idx as usize].get_or_insert_with(|| squares.clone());
+        }
+        // Next placement, counting in base 64
+        while next < count && squares[next] == 63 {
+            squares[next] = 0;
+            next += 1;
+        }
+        if next == count {
+            break;
+        }
+        squares[next] += 1;
+        next = 0;
+    }
+
+    let mut legal = vec![false; total];
+    let mut in_check = vec![false; total];
+    let mut first_move = vec![0u32; total + 1];
+    let mut moves = Vec::new();
+    for position in 0..total {
+        first_move[position] = moves.len() as u32;
+        let Some(squares) = &placements[position % (total / 2)] else { continue };
+        let team = position / (total / 2);
+        let board = new_board(&layout.pieces, squares, team);
+        if board.is_in_check(1 - team) {
+            continue;
+        }
+        legal[position] = true;
+        in_check[position] = board.is_in_check(team);
+        for mv in board.generate_moves(team) {
+            let pawn = board.bits.get_pieces(team, Pieces::PAWN).0 & (1 << mv.origin) != 0;
+            let zeroing = if mv.is_capture() || mv.is_en_passant() || pawn { ZEROING } else { 0 };
+            let mut child = board.clone();
+            child.play_move(mv.origin, mv.target, mv.get_promotion(), true).unwrap();
+            let white = material_name(&child, Teams::WHITE);
+            let black = material_name(&child, Teams::BLACK);
+            let name = format!("{}v{}", white, black);
+            let packed = if name == layout.name {
+                let (file, idx) = layout.encode(index, &mut layout.squares(&child));
+                (base[child.state.team_to_play * files + file] + idx as usize) as u32
+            } else if white == "K" && black == "K" {
+                DRAW
+            } else {
+                let table = solved.get(name.as_str()).unwrap_or_else(|| panic!("{} is not solved yet", name));
+                match table.wdl[table.position(index, &child)] {
+                    2 => WIN,
+                    -2 => LOSS,
+                    _ => DRAW
+                }
+            };
+            moves.push(packed | zeroing);
+        }
+    }
+    first_move[total] = moves.len() as u32;
+    let moves_of = |position: usize| &moves[first_move[position] as usize..first_move[position + 1] as usize];
+
+    // Outcome of the position a move leads to, for the side to move there
+    let outcome = |wdl: &[i8], mv: u32| match mv & OUTCOME {
+        0 => wdl[(mv & POSITION) as usize],
+        DRAW => 0,
+        LOSS => -2,
+        _ => 2
+    };
+
+    let mut wdl = vec![0i8; total];
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for position in 0..total {
+            if !legal[position] || wdl[position] != 0 {
+                continue;
+            }
+            let moves = moves_of(position);
+            let value = if moves.is_empty() {
+                if in_check[position] { -2 } else { 0 }
+            } else if moves.iter().any(|mv| outcome(&wdl, *mv) == -2) {
+                2
+            } else if moves.iter().all(|mv| outcome(&wdl, *mv) == 2) {
+                -2
+            } else {
+                0
+            };
+            if value != 0 {
+                wdl[position] = value;
+                changed = true;
+            }
+        }
+    }
+
+    // Winning positions are one ply further than the closest losing one they reach, losing ones one ply
+    // further than the farthest winning one, a zeroing move counting as one ply and a mate as none
+    let mut dtz = vec![u16::MAX; total];
+    for position in 0..total {
+        if legal[position] && wdl[position] == -2 && moves_of(position).is_empty() {
+            dtz[position] = 0;
+        }
+    }
+    let mut plies = 0;
+    while (0..total).any(|position| legal[position] && wdl[position] != 0 && dtz[position] == u16::MAX) {
+        plies += 1;
+        assert!(plies <= 100, "{} has wins beyond the fifty-move rule", layout.name);
+        for position in 0..total {
+            if !legal[position] || dtz[position] != u16::MAX {
+                continue;
+            }
+            let moves = moves_of(position);
+            if wdl[position] == 2 {
+                let reached = moves.iter().any(|mv| {
+                    outcome(&wdl, *mv) == -2
+                        && (if mv & ZEROING != 0 { plies == 1 } else { dtz[(mv & POSITION) as usize] == plies - 1 })
+                });
+                if reached {
+                    dtz[position] = plies;
+                }
+            } else if wdl[position] == -2 {
+                let distance = moves.iter().map(|mv| if mv & ZEROING != 0 {
+                    1
+                } else {
+                    dtz[(mv & POSITION) as usize].saturating_add(1)
+                }).max().unwrap();
+                if distance <= plies {
+                    dtz[position] = distance;
+                }
+            }
+        }
+    }
+    Solved { layout, base, legal, wdl, dtz }
+}
+
+enum Symbol {
+    Value(u16),
+    Pair(usize, usize)
+}
+
+// The bytes read by set_sizes, and the sparse index, block lengths and blocks of one side and file
+struct Item {
+    sizes: Vec<u8>,
+    sparse_index: Vec<u8>,
+    block_lengths: Vec<u8>,
+    data: Vec<u8>
+}
+
+// Huffman code lengths of symbols seen this many times
+fn code_lengths(counts: &[u64]) -> Vec<usize> {
+    let mut counts = counts.to_vec();
+    loop {
+        let mut lengths = vec![0; counts.len()];
+        let mut heap: BinaryHeap<Reverse<(u64, Vec<usize>)>> =
+            counts.iter().enumerate().map(|(symbol, count)| Reverse((*count, vec![symbol]))).collect();
+        while heap.len() > 1 {
+            let Reverse((first, mut symbols)) = heap.pop().unwrap();
+            let Reverse((second, others)) = heap.pop().unwrap();
+            symbols.extend(others);
+            for symbol in &symbols {
+                lengths[*symbol] += 1;
+            }
+            heap.push(Reverse((first + second, symbols)));
+        }
+        if lengths.iter().all(|length| *length <= MAX_CODE_LENGTH) {
+            return lengths;
+        }
+        // Flatten the counts until the longest code fits
+        counts.iter_mut().for_each(|count| *count = *count / 2 + 1);
+    }
+}
+
+fn compress(values: &[u16], flags: u8) -> Item {
+    if values.iter().all(|value| *value == values[0]) {
+        return Item {
+            sizes: vec![flags | FLAG_SINGLE_VALUE, values[0] as u8],
+            sparse_index: Vec::new(),
+            block_lengths: Vec::new(),
+            data: Vec::new()
+        };
+    }
+
+    // Runs of a value are cut into pieces of 2^k values, each its own symbol
+    let mut symbols = Vec::new();
+    let mut runs: HashMap<(u16, u32), usize> = HashMap::new();
+    fn run(symbols: &mut Vec<Symbol>, runs: &mut HashMap<(u16, u32), usize>, value: u16, log: u32) -> usize {
+        if let Some(symbol) = runs.get(&(value, log)) {
+            return *symbol;
+        }
+        let symbol = if log == 0 {
+            Symbol::Value(value)
+        } else {
+            let half = run(symbols, runs, value, log - 1);
+            Symbol::Pair(half, half)
+        };
+        symbols.push(symbol);
+        runs.insert((value, log), symbols.len() - 1);
+        symbols.len() - 1
+    }
+    let mut stream = Vec::new();
+    let mut start = 0;
+    while start < values.len() {
+        let mut length = values[start..].iter().take_while(|value| **value == values[start]).count();
+        start += length;
+        while length > 0 {
+            let log = length.ilog2().min(MAX_RUN_LOG);
+            stream.push((run(&mut symbols, &mut runs, values[start - length], log), 1usize << log));
+            length -= 1 << log;
+        }
+    }
+
+    // Canonical codes, symbols being numbered from the longest code to the shortest
+    let mut counts = vec![1u64; symbols.len()];
+    for (symbol, _) in &stream {
+        counts[*symbol] += 1;
+    }
+    let lengths = code_lengths(&counts);
+    let mut order: Vec<usize> = (0..symbols.len()).collect();
+    order.sort_by_key(|symbol| (Reverse(lengths[*symbol]), *symbol));
+    let mut renumbered = vec![0; symbols.len()];
+    for (new, symbol) in order.iter().enumerate() {
+        renumbered[*symbol] = new;
+    }
+    let min_length = *lengths.iter().min().unwrap();
+    let max_length = *lengths.iter().max().unwrap();
+    let per_length: Vec<usize> = (min_length..=max_length)
+        .map(|length| lengths.iter().filter(|other| **other == length).count())
+        .collect();
+    let lowest_sym: Vec<usize> = (0..per_length.len()).map(|i| per_length[i + 1..].iter().sum()).collect();
+    let mut base = vec![0u64; per_length.len()];
+    for i in (0..per_length.len() - 1).rev() {
+        base[i] = (base[i + 1] + per_length[i + 1] as u64) / 2;
+    }
+    let code = |symbol: usize| {
+        let i = lengths[symbol] - min_length;
+        (base[i] + (renumbered[symbol] - lowest_sym[i]) as u64, lengths[symbol])
+    };
+
+    // Blocks are filled with whole symbols and padded with zeros
+    let block_size = 1 << BLOCK_LOG;
+    let mut data = Vec::new();
+    let mut block_starts = vec![0usize];
+    let mut block_values = Vec::new();
+    let mut block = vec![0u8; block_size];
+    let (mut bits, mut in_block, mut position) = (0, 0, 0);
+    for (symbol, length) in stream {
+        let (value, code_length) = code(symbol);
+        if bits + code_length > block_size * 8 || in_block + length > MAX_BLOCK_VALUES {
+            data.append(&mut block);
+            block = vec![0u8; block_size];
+            block_values.push(in_block);
+            block_starts.push(position);
+            bits = 0;
+            in_block = 0;
+        }
+        for bit in (0..code_length).rev() {
+            if value >> bit & 1 != 0 {
+                block[bits / 8] |= 0x80 >> (bits % 8);
+            }
+            bits += 1;
+        }
+        in_block += length;
+        position += length;
+    }
+    data.append(&mut block);
+    block_values.push(in_block);
+
+    let span = 1usize << SPAN_LOG;
+    let mut sparse_index = Vec::new();
+    for entry in 0..values.len().div_ceil(span) {
+        let middle = entry * span + span / 2;
+        let block = block_starts.partition_point(|start| *start <= middle.min(values.len() - 1)) - 1;
+        let offset = u16::try_from(middle - block_starts[block]).expect("sparse index offset out of range");
+        sparse_index.extend((block as u32).to_le_bytes());
+        sparse_index.extend(offset.to_le_bytes());
+    }
+    let block_lengths = block_values.iter().flat_map(|count| (*count as u16 - 1).to_le_bytes()).collect();
+
+    let mut sizes = vec![flags, BLOCK_LOG, SPAN_LOG, 0];
+    sizes.extend((block_values.len() as u32).to_le_bytes());
+    sizes.extend([max_length as u8, min_length as u8]);
+    sizes.extend(lowest_sym.iter().flat_map(|lowest| (*lowest as u16).to_le_bytes()));
+    sizes.extend((symbols.len() as u16).to_le_bytes());
+    for symbol in &order {
+        let (left, right) = match symbols[*symbol] {
+            Symbol::Value(value) => (value as usize, 0xFFF),
+            Symbol::Pair(left, right) => (renumbered[left], renumbered[right])
+        };
+        sizes.extend([left as u8, (left >> 8) as u8 | ((right & 0xF) << 4) as u8, (right >> 4) as u8]);
+    }
+    if symbols.len() & 1 != 0 {
+        sizes.push(0);
+    }
+    Item { sizes, sparse_index, block_lengths, data }
+}
+
+// Positions nobody probes take the value before them, which keeps the runs long
+fn fill(values: Vec<Option<u16>>) -> Vec<u16> {
+    let mut last = values.iter().flatten().next().copied().unwrap_or(0);
+    values.into_iter().map(|value| {
+        last = value.unwrap_or(last);
+        last
+    }).collect()
+}
+
+fn write_table(path: &Path, magic: [u8; 4], layout: &Layout, items: &[Vec<Item>]) {
+    let sides = items[0].len();
+    let mut bytes = magic.to_vec();
+    bytes.push((sides == 2) as u8 | (layout.has_pawns as u8) << 1);
+    for _ in items {
+        // The lead pawns, or the first group, are indexed first on both sides
+        bytes.push(0);
+        bytes.extend(layout.pieces.iter().map(|code| code | code << 4));
+    }
+    bytes.resize(bytes.len() + (bytes.len() & 1), 0);
+    for item in items.iter().flatten() {
+        bytes.extend(&item.sizes);
+    }
+    if magic == DTZ_MAGIC {
+        bytes.resize(bytes.len() + (bytes.len() & 1), 0);
+    }
+    for item in items.iter().flatten() {
+        bytes.extend(&item.sparse_index);
+    }
+    for item in items.iter().flatten() {
+        bytes.extend(&item.block_lengths);
+    }
+    for item in items.iter().flatten() {
+        bytes.resize((bytes.len() + 0x3F) & !0x3F, 0);
+        bytes.extend(&item.data);
+    }
+    // The decoder reads a few bytes past the end of a block
+    bytes.resize(bytes.len() + 64, 0);
+    fs::write(path, bytes).unwrap();
+}
+
+fn main() {
+    let directory = env::args().nth(1).expect("usage: syzygy_fixtures <directory>");
+    let index = IndexTables::new();
+    let mut solved = HashMap::new();
+    for name in TABLES {
+        let table = solve(Layout::new(name, &index), &index, &solved);
+        let layout = &table.layout;
+        let files = layout.files();
+        let values = |team: usize, file: usize, value: &dyn Fn(usize) -> Option<u16>| {
+            let start = table.base[team * files + file];
+            fill((start..start + layout.size(file) as usize).map(value).collect())
+        };
+
+        // Both sides to move in the WDL table, the winning side only in the DTZ table
+        let wdl: Vec<Vec<Item>> = (0..files).map(|file| (0..2).map(|team| {
+            let values = values(team, file, &|position| table.legal[position].then(|| (table.wdl[position] + 2) as u16));
+            compress(&values, 0)
+        }).collect()).collect();
+        let dtz: Vec<Vec<Item>> = (0..files).map(|file| {
+            let values = values(Teams::WHITE, file, &|position| {
+                (table.legal[position] && table.wdl[position] != 0).then(|| table.dtz[position].max(1) - 1)
+            });
+            vec![compress(&values, FLAG_WIN_PLIES | FLAG_LOSS_PLIES)]
+        }).collect();
+        write_table(&Path::new(&directory).join(format!("{}.rtbw", name)), WDL_MAGIC, layout, &wdl);
+        write_table(&Path::new(&directory).join(format!("{}.rtbz", name)), DTZ_MAGIC, layout, &dtz);
+
+        let wins = (0..table.wdl.len()).filter(|position| table.legal[*position] && table.wdl[*position] == 2).count();
+        let longest = (0..table.dtz.len()).filter(|position| table.wdl[*position] != 0).map(|position| table.dtz[position]).max();
+        println!("{}: {} winning positions, longest DTZ {:?}", name, wins, longest);
+        solved.insert(name, table);
+    }
+}
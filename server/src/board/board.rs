@@ -1,19 +1,27 @@
-use crate::board::{BitBoard, BitPosition, CompletedMove, GamePiece, get_opposite_team, MailBox, Piece, Pieces, PossibleMove, PossibleMoves, Team, Teams};
+use crate::board::{BitBoard, BitPosition, CompletedMove, GamePiece, get_opposite_team, MailBox, Piece, Pieces, Team, Teams};
 use crate::board::state::{CastlingSides, ChessState};
+use crate::board::variant::Variant;
 use crate::engine::get_piece_value;
-use crate::game::{Move, Vector};
-use crate::math::{individually_mask_piece_moves, iterate_bits, map_bits};
-use crate::math::kings::{calculate_king_castling_moves, mask_king_moves};
+use crate::engine::nnue::NnueAccumulator;
+use crate::engine::pst::PieceSquareScore;
+use crate::game::san::castling_move;
+use crate::game::Vector;
+use crate::math::{individually_mask_piece_moves, iterate_bits};
+use crate::math::kings::{CASTLED_KING_FILES, CASTLED_ROOK_FILES, calculate_king_castling_moves, mask_king_moves};
 use crate::math::knights::mask_all_knight_moves;
-use crate::math::pawns::mask_all_pawn_moves;
+use crate::math::pawns::{mask_all_pawn_capture_moves, mask_first_rank_double_step};
 use crate::math::sliding::{properly_mask_all_bishop_moves, properly_mask_all_queen_moves, properly_mask_all_rook_moves};
-use crate::print;
 
 #[derive(Debug, Clone, Hash)]
 pub struct ChessBoard {
     pub bits: BitPosition,
     pub mailbox: MailBox,
     pub state: ChessState,
+    pub psqt: PieceSquareScore,
+    // Only present while an NNUE evaluator is searching this board
+    pub nnue: Option<NnueAccumulator>,
+    // Atomic: the pieces each capture blew up, latest last, so undo_move can put them back
+    exploded: Vec<Vec<(u8, GamePiece)>>,
     experimental: ExperimentalData
 }
 
@@ -24,18 +32,52 @@ struct ExperimentalData {
 
 impl ChessBoard {
     pub fn new(bits: BitPosition, mailbox: MailBox, state: ChessState) -> ChessBoard {
+        let mut psqt = PieceSquareScore::default();
+        for team in [Teams::WHITE, Teams::BLACK] {
+            for piece in Pieces::iter() {
+                iterate_bits(bits.get_pieces(team, piece).0, |bit| {
+                    psqt.add(team, piece, bit.trailing_zeros() as usize);
+                });
+            }
+        }
         ChessBoard {
             bits,
             mailbox,
             state,
+            psqt,
+            nnue: None,
+            exploded: Vec::new(),
             experimental: ExperimentalData::new()
         }
     }
 
+    // Every piece placement goes through here and take_piece, so incremental data stays in sync
+    fn put_piece(&mut self, team: Team, piece: Piece, square: u8) {
+        let mailbox_index = Vector::from_bit_position_index(square as usize).mail_box_index();
+        self.mailbox.set_piece_at(mailbox_index, Some(GamePiece::from(piece, team)));
+        self.bits.move_or(team, piece, BitBoard(1 << square));
+        self.psqt.add(team, piece, square as usize);
+        if let Some(nnue) = &mut self.nnue {
+            nnue.add(team, piece, square as usize);
+        }
+    }
+
+    fn take_piece(&mut self, team: Team, piece: Piece, square: u8) {
+        let mailbox_index = Vector::from_bit_position_index(square as usize).mail_box_index();
+        self.mailbox.set_piece_at(mailbox_index, None);
+        self.bits.move_and(team, piece, !BitBoard(1 << square));
+        self.psqt.remove(team, piece, square as usize);
+        if let Some(nnue) = &mut self.nnue {
+            nnue.remove(team, piece, square as usize);
+        }
+    }
+
+    // A pawn reaching the last rank needs the piece it becomes, which is ignored for any other move
     pub fn play_move(
         &mut self,
         from: u8,
         to: u8,
+        promotion: Option<Piece>,
         update_state: bool
     ) -> Option<CompletedMove> {
         let from_y = from / 8;
@@ -45,12 +87,22 @@ impl ChessBoard {
             return None;
         }
         let piece = piece.unwrap();
+        let last_rank = if piece.get_team() == Teams::WHITE { 7 } else { 0 };
+        let promotion = if piece.get_piece() == Pieces::PAWN && to / 8 == last_rank {
+            Some(promotion.filter(|piece| Pieces::PROMOTIONS.contains(piece))?)
+        } else {
+            None
+        };
         let mut mv = CompletedMove::clean(from,to);
+        let mut king_to = to;
+        let mut castled_rook = None;
         if update_state {
+            self.state.en_passant_square = None;
             match piece {
                 GamePiece::Pawn(_team) => {
                     if (to as i8 - from as i8).abs() == 16 {
-                        self.state.en_passant_square = Some(to as usize);
+                        // The square the pawn skipped over
+                        self.state.en_passant_square = Some(((from + to) / 2) as usize);
                     } else if from % 8 != to % 8 {
                         let x = to % 8;
                         let y = to / 8;
@@ -63,21 +115,20 @@ impl ChessBoard {
                     }
                 }
                 GamePiece::King(team) => {
-                    if (to as i8 - from as i8).abs() == 2 {
-                        let rook_from = if to == 2 { 0 } else { 7 };
-                        let rook_to = if to == 2 { 3 } else { 5 };
-                        let offset = team * 56;
+                    if let Some(side) = self.castling_side(team, from, to) {
+                        let offset = team as u8 * 56;
+                        let rook_from = offset + self.state.castling_files[team][side];
                         mv.set_castling();
-                        self.move_piece((rook_from + offset) as u8, (rook_to + offset) as u8);
-                    } else {
-                        self.state.castling_rights.disallow_all(team);
+                        // The rook leaves first: in Chess960 the king may land on its square, or not move at all
+                        self.take_piece(team, Pieces::ROOK, rook_from);
+                        king_to = offset + CASTLED_KING_FILES[side];
+                        castled_rook = Some((team, offset + CASTLED_ROOK_FILES[side]));
                     }
+                    self.state.castling_rights.disallow_all(team);
                 }
                 GamePiece::Rook(team) => {
-                    match from % 8 {
-                        0 => self.state.castling_rights.disallow(team, CastlingSides::QUEENSIDE),
-                        7 => self.state.castling_rights.disallow(team, CastlingSides::KINGSIDE),
-                        _ => {}
+                    if let Some(side) = self.castling_rook_side(team, from) {
+                        self.state.castling_rights.disallow(team, side);
                     }
                 }
                 _ => {}
@@ -89,14 +140,88 @@ impl ChessBoard {
             let target = self.mailbox.get_piece_at(target_mailbox_index);
             if let Some(target) = target {
                 mv.set_capture(target.get_piece());
+                // Taking a rook on its starting square removes the matching castling right
+                let opponent = target.get_team();
+                if target.get_piece() == Pieces::ROOK {
+                    if let Some(side) = self.castling_rook_side(opponent, to) {
+                        self.state.castling_rights.disallow(opponent, side);
+                    }
+                }
             }
             self.state.team_to_play = get_opposite_team(self.state.team_to_play);
+            self.state.ply += 1;
             // self.experimental.set_last_move(mv.clone());
         }
-        self.move_piece(from, to);
+        self.move_piece(from, king_to);
+        if let Some((team, rook_to)) = castled_rook {
+            self.put_piece(team, Pieces::ROOK, rook_to);
+        }
+        if let Some(promotion) = promotion {
+            let team = piece.get_team();
+            self.take_piece(team, Pieces::PAWN, to);
+            self.put_piece(team, promotion, to);
+            mv.set_promotion(promotion);
+        }
+        if update_state {
+            let team = piece.get_team();
+            if self.state.variant == Variant::Atomic && (mv.is_capture() || mv.is_en_passant()) {
+                self.explode(to);
+            }
+            if self.state.variant == Variant::ThreeCheck && self.is_in_check(get_opposite_team(team)) {
+                self.state.checks[team] += 1;
+            }
+        }
         Some(mv)
     }
 
+    // Atomic: the capture blows up the capturing piece and every piece but pawns around the target square
+    fn explode(&mut self, square: u8) {
+        let mut removed = Vec::new();
+        iterate_bits(mask_king_moves(1 << square, None).0 | 1 << square, |bit| {
+            let index = Vector::from_bit_position_index(bit.trailing_zeros() as usize).mail_box_index();
+            if let Some(piece) = *self.mailbox.get_piece_at(index) {
+                if piece.get_piece() != Pieces::PAWN || bit == 1 << square {
+                    removed.push((bit.trailing_zeros() as u8, piece));
+                }
+            }
+        });
+        for (square, piece) in &removed {
+            let team = piece.get_team();
+            match piece.get_piece() {
+                Pieces::KING => self.state.castling_rights.disallow_all(team),
+                Pieces::ROOK => {
+                    if let Some(side) = self.castling_rook_side(team, *square) {
+                        self.state.castling_rights.disallow(team, side);
+                    }
+                }
+                _ => {}
+            }
+            self.take_piece(team, piece.get_piece(), *square);
+        }
+        self.exploded.push(removed);
+    }
+
+    // Castling is the king moving onto its own castling rook, or in classical chess two squares sideways
+    fn castling_side(&self, team: Team, from: u8, to: u8) -> Option<usize> {
+        let rank = team as u8 * 7;
+        if from / 8 != rank || to / 8 != rank {
+            return None;
+        }
+        let side = if to > from { CastlingSides::KINGSIDE } else { CastlingSides::QUEENSIDE };
+        let target = self.mailbox.get_piece_at(Vector::from_bit_position_index(to as usize).mail_box_index());
+        let takes_own_rook = *target == Some(GamePiece::Rook(team)) && self.castling_rook_side(team, to) == Some(side);
+        let classical = !self.state.chess960 && from.abs_diff(to) == 2;
+        (takes_own_rook || classical).then_some(side)
+    }
+
+    // Side whose castling rook starts on the square, if any
+    fn castling_rook_side(&self, team: Team, square: u8) -> Option<usize> {
+        if square / 8 != team as u8 * 7 {
+            return None;
+        }
+        [CastlingSides::KINGSIDE, CastlingSides::QUEENSIDE].into_iter().find(|side| self.state.castling_files[team][*side] == square % 8)
+    }
+
     pub fn move_piece(
         &mut self,
         from: u8,
@@ -109,22 +234,17 @@ impl ChessBoard {
         let to_x = to % 8;
         let to_mailbox_index = (to_y * 16 + to_x) as usize;
         let piece = self.mailbox.get_piece_at(from_mailbox_index);
-        if piece.is_none() {
+        if piece.is_none() || from == to {
             return;
         }
-        let target = self.mailbox.get_piece_at(to_mailbox_index);
-        if let Some(target) = target {
-            let target_team = target.get_team();
-            let target_piece = target.get_piece();
-            self.bits.move_and(target_team, target_piece, !BitBoard(1 << to as usize));
-        }
         let piece = piece.unwrap();
-        self.mailbox.set_piece_at(from_mailbox_index, None);
-        self.mailbox.set_piece_at(to_mailbox_index, Some(piece));
+        if let Some(target) = *self.mailbox.get_piece_at(to_mailbox_index) {
+            self.take_piece(target.get_team(), target.get_piece(), to);
+        }
         let team = piece.get_team();
         let piece = piece.get_piece();
-        self.bits.move_and(team, piece, !BitBoard(1 << from as usize));
-        self.bits.move_or(team, piece, BitBoard(1 << to as usize));
+        self.take_piece(team, piece, from);
+        self.put_piece(team, piece, to);
     }
 
     pub fn is_in_check(
@@ -132,34 +252,63 @@ impl ChessBoard {
         team: Team
     ) -> bool {
         let king = self.bits.get_pieces(team, Pieces::KING);
+        // Atomic kings side by side cannot check each other: taking one would blow up the other
+        if self.state.variant == Variant::Atomic && mask_king_moves(king.0, None).0 & self.bits.get_pieces(get_opposite_team(team), Pieces::KING).0 != 0 {
+            return false;
+        }
         let opponent_threats = self.attacks(get_opposite_team(team));
         (opponent_threats.0 & king.0) != 0
     }
 
+    // Whether the team may stand in this position after its move: its king not in check, and in Atomic not
+    // blown up, which blowing up the opponent's king first outweighs
+    fn is_legal_after_move(&self, team: Team) -> bool {
+        if self.state.variant == Variant::Atomic {
+            let king = |team: Team| self.bits.get_pieces(team, Pieces::KING).0;
+            return king(team) != 0 && (king(get_opposite_team(team)) == 0 || !self.is_in_check(team));
+        }
+        !self.is_in_check(team)
+    }
+
     pub fn remove_piece(
         &mut self,
         location: Vector
     ) {
-        let mailbox_index = location.mail_box_index();
-        if let Some(piece) = self.mailbox.get_piece_at(mailbox_index) {
-            let team = piece.get_team();
-            let piece = piece.get_piece();
-            self.bits.move_and(team, piece, !BitBoard(1 << location.bit_position_index()));
-            self.mailbox.set_piece_at(mailbox_index, None);
+        if let Some(piece) = *self.mailbox.get_piece_at(location.mail_box_index()) {
+            self.take_piece(piece.get_team(), piece.get_piece(), location.bit_position_index() as u8);
+        }
+    }
+
+    // A copy to try moves on. Legality checks never evaluate, so the accumulator is left behind rather
+    // than copied for every node searched.
+    fn copy_without_accumulator(&self) -> ChessBoard {
+        ChessBoard {
+            bits: self.bits,
+            mailbox: self.mailbox,
+            state: self.state,
+            psqt: self.psqt,
+            nnue: None,
+            exploded: self.exploded.clone(),
+            experimental: self.experimental.clone()
         }
     }
 
     pub fn generate_moves(&self, team: Team) -> Vec<CompletedMove> {
-        let hypothetical_board = &mut self.clone();
+        // A game the variant already decided has no moves left
+        if self.state.variant.winner(self).is_some() {
+            return vec![];
+        }
+        let hypothetical_board = &mut self.copy_without_accumulator();
         let opponent_threats = None;
         let mut moves = vec![];
         let opponent_pieces = self.bits.get_team_pieces(get_opposite_team(team));
         let team_pieces = self.bits.get_team_pieces(team);
         let empty_squares = !(team_pieces | opponent_pieces);
         let occupied_squares = !empty_squares;
+        let last_rank = if team == Teams::WHITE { 7 } else { 0 };
 
         for piece in Pieces::iter() {
-            let mut bitboard = self.bits.get_pieces(team, piece).0.clone();
+            let mut bitboard = self.bits.get_pieces(team, piece).0;
             if bitboard == 0 { continue; }
             while bitboard != 0 {
                 let piece_bit = 1 << bitboard.trailing_zeros();
@@ -168,18 +317,32 @@ impl ChessBoard {
                 let mut attacks =
                     individually_mask_piece_moves(piece_bit, piece, team, &empty_squares, &occupied_squares, &opponent_pieces, &self.state.en_passant_square, opponent_threats).0
                         & !team_pieces.0;
+                if piece == Pieces::PAWN && self.state.variant.first_rank_double_steps() {
+                    attacks |= mask_first_rank_double_step(piece_bit, &empty_squares, team).0;
+                }
                 if attacks == 0 { continue; }
                 iterate_bits(attacks, |target| {
-                    let hypothetical_move = hypothetical_board.play_move(piece_bit.trailing_zeros() as u8, target.trailing_zeros() as u8, true);
-                    if let Some(hypothetical_move) = hypothetical_move {
-                        if !hypothetical_board.is_in_check(team) {
-                            moves.push(hypothetical_move.clone());
+                    let target = target.trailing_zeros() as u8;
+                    // A pawn reaching the last rank makes a move for each piece it may become, any other move ignores the piece
+                    let choices = if piece == Pieces::PAWN && target / 8 == last_rank { Pieces::PROMOTIONS.len() } else { 1 };
+                    for promotion in &Pieces::PROMOTIONS[..choices] {
+                        let state = hypothetical_board.state;
+                        let hypothetical_move = hypothetical_board.play_move(piece_bit.trailing_zeros() as u8, target, Some(*promotion), true);
+                        if let Some(hypothetical_move) = hypothetical_move {
+                            if hypothetical_board.is_legal_after_move(team) {
+                                moves.push(hypothetical_move.clone());
+                            }
+                            hypothetical_board.undo_move(&hypothetical_move);
                         }
-                        hypothetical_board.undo_move(&hypothetical_move);
+                        hypothetical_board.state = state;
                     }
                 });
             }
         }
+        // Castling has its own rules for the squares the king crosses, so it is checked apart
+        if team == self.state.team_to_play {
+            moves.extend([CastlingSides::KINGSIDE, CastlingSides::QUEENSIDE].into_iter().filter_map(|side| castling_move(self, side)));
+        }
         moves
     }
 
@@ -195,7 +358,7 @@ impl ChessBoard {
         let empty_squares = !(team_pieces | opponent_pieces);
         let occupied_squares = !empty_squares;
 
-        let bitboard = individually_mask_piece_moves(location.bit_position_index() as u64, piece.get_piece(), team, &empty_squares, &occupied_squares, &opponent_pieces, &self.state.en_passant_square, None);
+        let _bitboard = individually_mask_piece_moves(location.bit_position_index() as u64, piece.get_piece(), team, &empty_squares, &occupied_squares, &opponent_pieces, &self.state.en_passant_square, None);
         vec![]
     }
 
@@ -215,14 +378,15 @@ impl ChessBoard {
         let rook_moves = properly_mask_all_rook_moves(&self.bits.get_pieces(team, Pieces::ROOK), &occupied_squares);
         moves.0 |= rook_moves.0;
 
-        let pawn_moves = mask_all_pawn_moves(&self.bits.get_pieces(team, Pieces::PAWN), &empty_squares, &opponent_pieces, &self.state.en_passant_square, team);
+        // Pawns attack the squares they capture on, taken or not, and never the ones they march to
+        let pawn_moves = mask_all_pawn_capture_moves(&self.bits.get_pieces(team, Pieces::PAWN), &BitBoard(u64::MAX), &None, team);
         moves.0 |= pawn_moves.0;
 
         let queen_moves = properly_mask_all_queen_moves(&self.bits.get_pieces(team, Pieces::QUEEN), &occupied_squares);
         moves.0 |= queen_moves.0;
 
         let king_moves = mask_king_moves(self.bits.get_pieces(team, Pieces::KING).0, None);
-        let king_castling_moves = calculate_king_castling_moves(&self.state.castling_rights, team, &occupied_squares);
+        let king_castling_moves = calculate_king_castling_moves(&self.state.castling_rights, &self.state.castling_files, team, self.bits.get_pieces(team, Pieces::KING).0, &occupied_squares);
         moves.0 |= king_moves.0;
         moves.0 |= king_castling_moves.0;
 
@@ -230,12 +394,12 @@ impl ChessBoard {
     }
 
     // Difference of material
-    pub fn evaluate(&self, reference: Team) -> i32 {
+    pub fn material(&self, reference: Team) -> i32 {
         let mut score = 0;
+        let opponent = get_opposite_team(reference);
         for piece in Pieces::iter() {
-            let team = reference;
-            let team_pieces = self.bits.get_pieces(team, piece);
-            let opponent_pieces = self.bits.get_pieces(get_opposite_team(team), piece);
+            let team_pieces = self.bits.get_pieces(reference, piece);
+            let opponent_pieces = self.bits.get_pieces(opponent, piece);
             score += (team_pieces.count_ones() as i32 - opponent_pieces.count_ones() as i32) * get_piece_value(piece);
         }
         score
@@ -245,11 +409,17 @@ impl ChessBoard {
         &mut self,
         completed_move: &CompletedMove
     ) {
-        let origin_x = completed_move.origin % 8;
-        let origin_y = completed_move.origin / 8;
+        if completed_move.is_castling() {
+            self.undo_castling(completed_move);
+            return;
+        }
+        if self.state.variant == Variant::Atomic && (completed_move.is_capture() || completed_move.is_en_passant()) {
+            for (square, piece) in self.exploded.pop().unwrap_or_default() {
+                self.put_piece(piece.get_team(), piece.get_piece(), square);
+            }
+        }
         let target_x = completed_move.target % 8;
         let target_y = completed_move.target / 8;
-        let from_mailbox_index = (origin_y * 16 + origin_x) as usize;
         let to_mailbox_index = (target_y * 16 + target_x) as usize;
         let piece = self.mailbox.get_piece_at(to_mailbox_index);
         if piece.is_none() {
@@ -258,36 +428,33 @@ impl ChessBoard {
         let piece = piece.unwrap();
         let team = piece.get_team();
         let piece = piece.get_piece();
-        self.mailbox.set_piece_at(from_mailbox_index, Some(GamePiece::from(piece, team)));
-        self.mailbox.set_piece_at(to_mailbox_index, None);
-        self.bits.move_and(team, piece, !BitBoard(1 << completed_move.target as usize));
-        self.bits.move_or(team, piece, BitBoard(1 << completed_move.origin as usize));
+        self.take_piece(team, piece, completed_move.target);
+        // A promoted piece goes back as the pawn it was
+        self.put_piece(team, if completed_move.is_promotion() { Pieces::PAWN } else { piece }, completed_move.origin);
         if completed_move.is_en_passant() {
-            let target_y = if team == Teams::WHITE { 3 } else { 4 };
-            let target_mailbox_index = (target_y * 16 + target_x) as usize;
-            let target_piece = GamePiece::Pawn(get_opposite_team(team));
-            self.mailbox.set_piece_at(target_mailbox_index, Some(target_piece));
-            self.bits.move_or(get_opposite_team(team), Pieces::PAWN, BitBoard(1 << (target_y * 8 + target_x)));
-        }
-        if completed_move.is_castling() {
-            let rook_from = if completed_move.target == 2 { 0 } else { 7 };
-            let rook_to = if completed_move.target == 2 { 3 } else { 5 };
-            let offset = team * 56;
-            self.move_piece((rook_to + offset) as u8, (rook_from + offset) as u8);
+            // The pawn taken en passant stood beside the capturing one
+            self.put_piece(get_opposite_team(team), Pieces::PAWN, completed_move.origin / 8 * 8 + target_x);
         }
         if completed_move.is_capture() {
-            let capture: Piece = completed_move.get_capture() as usize;
-            let target_y = completed_move.target / 8;
-            let target_x = completed_move.target % 8;
-            let target_mailbox_index = (target_y * 16 + target_x) as usize;
-            self.mailbox.set_piece_at(target_mailbox_index, Some(GamePiece::from(capture, get_opposite_team(team))));
-            self.bits.move_or(get_opposite_team(team), capture, BitBoard(1 << completed_move.target as usize));
+            self.put_piece(get_opposite_team(team), completed_move.get_capture(), completed_move.target);
         }
         self.state.team_to_play = get_opposite_team(self.state.team_to_play);
     }
+
+    // The king and rook go back to where the castling files say they started
+    fn undo_castling(&mut self, completed_move: &CompletedMove) {
+        let team = if completed_move.origin / 8 == 0 { Teams::WHITE } else { Teams::BLACK };
+        let side = if completed_move.target > completed_move.origin { CastlingSides::KINGSIDE } else { CastlingSides::QUEENSIDE };
+        let offset = team as u8 * 56;
+        self.take_piece(team, Pieces::ROOK, offset + CASTLED_ROOK_FILES[side]);
+        self.move_piece(offset + CASTLED_KING_FILES[side], completed_move.origin);
+        self.put_piece(team, Pieces::ROOK, offset + self.state.castling_files[team][side]);
+        self.state.team_to_play = get_opposite_team(self.state.team_to_play);
+    }
 }
 
 
+#[allow(dead_code)]
 impl ExperimentalData {
     fn new() -> Self {
         Self {
@@ -1,9 +1,11 @@
 use std::ops::{BitAnd, BitOr, BitOrAssign, Div, Not, Rem};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use crate::game::Vector;
 
+#[allow(clippy::module_inception)]
 pub mod board;
 pub mod state;
+pub mod variant;
 
 pub type Team = usize;
 pub struct Teams;
@@ -29,6 +31,8 @@ impl Pieces{
     pub const ROOK: usize = 3;
     pub const QUEEN: usize = 4;
     pub const KING: usize = 5;
+    // What a pawn on the last rank may become, the likeliest first
+    pub const PROMOTIONS: [Piece; 4] = [Self::QUEEN, Self::KNIGHT, Self::ROOK, Self::BISHOP];
 
     pub fn iter() -> impl Iterator<Item = Piece> {
         0..6
@@ -107,6 +111,12 @@ pub struct MailBox {
     board: [Option<GamePiece>; 128]
 }
 
+impl Default for MailBox {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl MailBox {
     pub fn new() -> Self {
         let board = [None; 128];
@@ -136,6 +146,12 @@ pub fn mailbox_shift_down(index: usize, n: usize, team: Team) -> usize {
     if team == Teams::WHITE { index - n * 16 } else { index + n * 16 }
 }
 
+impl Default for BitPosition {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl BitPosition {
     pub fn new() -> Self {
         let bb_sides = [BitBoard(0), BitBoard(0)];
@@ -185,7 +201,7 @@ impl BitAnd for BitBoard {
 }
 
 // Implementation for &BitBoard & BitBoard
-impl<'a> BitAnd<BitBoard> for &'a BitBoard {
+impl BitAnd<BitBoard> for &BitBoard {
     type Output = BitBoard;
 
     fn bitand(self, rhs: BitBoard) -> Self::Output {
@@ -203,7 +219,7 @@ impl<'a> BitAnd<&'a BitBoard> for BitBoard {
 }
 
 // Implementation for &BitBoard & &BitBoard
-impl<'a, 'b> BitAnd<&'b BitBoard> for &'a BitBoard {
+impl<'b> BitAnd<&'b BitBoard> for &BitBoard {
     type Output = BitBoard;
 
     fn bitand(self, rhs: &'b BitBoard) -> Self::Output {
@@ -271,11 +287,12 @@ impl BitBoard {
     }
 }
 
-#[derive(Serialize, Debug, Hash, Clone)]
+#[derive(Serialize, Deserialize, Debug, Hash, Clone)]
 pub struct CompletedMove {
     pub origin: u8,
     pub target: u8,
     capture: u8,
+    promotion: u8,
     pub bits: u8,
 }
 
@@ -288,19 +305,20 @@ impl CompletedMove {
     pub const STALEMATE: u8 = 0b0010_0000;
 
     pub fn clean(origin: u8, target: u8) -> Self {
-        Self { origin, target, bits: 0u8, capture: 0u8 }
+        Self { origin, target, bits: 0u8, capture: 0u8, promotion: 0u8 }
     }
 
     pub fn new(origin: u8, target: u8, bits: u8, capture: u8) -> Self {
-        Self { origin, target, bits, capture }
+        Self { origin, target, bits, capture, promotion: 0u8 }
     }
 
     pub fn set_capture(&mut self, capture: Piece) {
         self.capture = (capture+1) as u8;
     }
 
-    pub fn set_promotion(&mut self) {
+    pub fn set_promotion(&mut self, piece: Piece) {
         self.bits |= Self::PROMOTION;
+        self.promotion = (piece+1) as u8;
     }
 
     pub fn set_castling(&mut self) {
@@ -355,6 +373,11 @@ impl CompletedMove {
         (self.capture-1) as Piece
     }
 
+    // Piece the pawn became, None for any other move
+    pub fn get_promotion(&self) -> Option<Piece> {
+        self.is_promotion().then(|| (self.promotion-1) as Piece)
+    }
+
     pub fn is_valid(&self) -> bool {
         self.origin < 64 && self.target < 64
     }
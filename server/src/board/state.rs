@@ -1,10 +1,24 @@
 use crate::board::{Team, Teams};
+use crate::board::variant::Variant;
 
 #[derive(Debug, Clone, Hash, PartialEq, Eq, Copy)]
 pub struct ChessState {
     pub castling_rights: CastlingRights,
     pub en_passant_square: Option<usize>,
-    pub team_to_play: Team
+    pub team_to_play: Team,
+    // Half-moves played since the start of the game, derived from the FEN move number
+    pub ply: u16,
+    // Files the castling rooks start on, by team then side. Chess960 puts them anywhere on the back rank.
+    pub castling_files: [[u8; 2]; 2],
+    // Chess960 rules for notation: castling is written as the king taking its own rook
+    pub chess960: bool,
+    pub variant: Variant,
+    // Checks given by each team, counted in Three-check only
+    pub checks: [u8; 2]
+}
+
+impl ChessState {
+    pub const STANDARD_CASTLING_FILES: [[u8; 2]; 2] = [[7, 0], [7, 0]];
 }
 
 pub struct CastlingSides;
@@ -45,7 +59,7 @@ impl CastlingRights {
 
     pub fn disallow_all(&mut self, team: Team) {
         let bits = match team {
-            Teams::WHITE => Self::WHITE_KINGSIDE | Self::WHITE_KINGSIDE,
+            Teams::WHITE => Self::WHITE_KINGSIDE | Self::WHITE_QUEENSIDE,
             Teams::BLACK => Self::BLACK_KINGSIDE | Self::BLACK_QUEENSIDE,
             _ => panic!("Invalid team")
         };
@@ -0,0 +1,85 @@
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::board::{get_opposite_team, Pieces, Team, Teams};
+use crate::board::board::ChessBoard;
+
+// The centre squares d4, e4, d5 and e5
+pub const HILL: u64 = 0x0000_0018_1800_0000;
+pub const THREE_CHECKS: u8 = 3;
+
+// Rules a game is played under. The board asks it how moves play out and the game asks it whether someone
+// won outside of checkmate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Variant {
+    #[default]
+    Standard,
+    // Bringing the king to the centre wins
+    KingOfTheHill,
+    // Giving a third check wins
+    ThreeCheck,
+    // Captures blow up every piece but pawns around the target square, capturer included
+    Atomic,
+    // White plays 36 pawns and no king, and wins by mating, black wins by taking every one of them
+    Horde
+}
+
+impl Variant {
+    pub const ALL: [Variant; 5] = [Variant::Standard, Variant::KingOfTheHill, Variant::ThreeCheck, Variant::Atomic, Variant::Horde];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Variant::Standard => "standard",
+            Variant::KingOfTheHill => "king_of_the_hill",
+            Variant::ThreeCheck => "three_check",
+            Variant::Atomic => "atomic",
+            Variant::Horde => "horde"
+        }
+    }
+
+    // Name in the PGN Variant tag
+    pub fn pgn_name(&self) -> &'static str {
+        match self {
+            Variant::Standard => "Standard",
+            Variant::KingOfTheHill => "King of the Hill",
+            Variant::ThreeCheck => "Three-check",
+            Variant::Atomic => "Atomic",
+            Variant::Horde => "Horde"
+        }
+    }
+
+    pub fn start_fen(&self) -> &'static str {
+        match self {
+            Variant::Horde => "rnbqkbnr/pppppppp/8/1PP2PP1/PPPPPPPP/PPPPPPPP/PPPPPPPP/PPPPPPPP w kq - 0 1",
+            _ => "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"
+        }
+    }
+
+    // Whether pawns on their first rank may step two squares, as the horde's back pawns do
+    pub fn first_rank_double_steps(&self) -> bool {
+        *self == Variant::Horde
+    }
+
+    // The team that won by the variant's own rules, with the move just played. Checkmate and the draws are
+    // left to the game.
+    pub fn winner(&self, board: &ChessBoard) -> Option<Team> {
+        let kings = |team: Team| board.bits.get_pieces(team, Pieces::KING).0;
+        match self {
+            Variant::Standard => None,
+            Variant::KingOfTheHill => [Teams::WHITE, Teams::BLACK].into_iter().find(|team| kings(*team) & HILL != 0),
+            Variant::ThreeCheck => [Teams::WHITE, Teams::BLACK].into_iter().find(|team| board.state.checks[*team] >= THREE_CHECKS),
+            Variant::Atomic => [Teams::WHITE, Teams::BLACK].into_iter().find(|team| kings(get_opposite_team(*team)) == 0),
+            Variant::Horde => (board.bits.get_team_pieces(Teams::WHITE).0 == 0).then_some(Teams::BLACK)
+        }
+    }
+}
+
+impl FromStr for Variant {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Variant::ALL.into_iter().find(|variant| variant.name() == value).ok_or(format!("Unknown variant {}", value))
+    }
+}
@@ -0,0 +1,176 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Write};
+
+use crate::board::{CompletedMove, Pieces, Team, Teams};
+use crate::board::board::ChessBoard;
+use crate::board::variant::Variant;
+use crate::book::polyglot::{polyglot_key, PolyglotBook, PolyglotEntry};
+use crate::game::fen;
+use crate::game::pgn::{GameResult, PgnGame};
+use crate::game::san::parse_san;
+
+const MAGIC: &[u8; 4] = b"CBOK";
+const STARTING_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+// Outcomes of the games in which a move was played from a position
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MoveStats {
+    pub games: u32,
+    pub white_wins: u32,
+    pub draws: u32,
+    pub black_wins: u32
+}
+
+impl MoveStats {
+    fn record(&mut self, result: GameResult) {
+        self.games += 1;
+        match result {
+            GameResult::WhiteWins => self.white_wins += 1,
+            GameResult::BlackWins => self.black_wins += 1,
+            GameResult::Draw => self.draws += 1,
+            GameResult::Unknown => {}
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct BookPosition {
+    pub team_to_play: Team,
+    // Keyed by the Polyglot move encoding
+    pub moves: HashMap<u16, MoveStats>
+}
+
+// Moves played from every position reached in the opening, keyed by Polyglot key
+#[derive(Debug, Clone, Default)]
+pub struct OpeningTree {
+    positions: HashMap<u64, BookPosition>,
+    games: u32
+}
+
+impl OpeningTree {
+    pub fn positions(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn games(&self) -> u32 {
+        self.games
+    }
+
+    pub fn position(&self, board: &ChessBoard) -> Option<&BookPosition> {
+        self.positions.get(&polyglot_key(board))
+    }
+
+    // Replays the game up to max_ply half-moves. Games are cut short at the first move the board cannot play,
+    // which also covers games from custom positions we fail to set up.
+    pub fn add_game(&mut self, game: &PgnGame, max_ply: usize) -> usize {
+        // Books are for standard chess, whose rules the variants' moves would be replayed under
+        let variant = game.tag("Variant");
+        if Variant::ALL.iter().any(|rules| *rules != Variant::Standard && Some(rules.pgn_name()) == variant) {
+            return 0;
+        }
+        let start = game.tag("FEN").unwrap_or(STARTING_FEN);
+        let Some(mut board) = fen::new_board(start) else { return 0 };
+        self.games += 1;
+
+        let mut played = 0;
+        for san in game.moves.iter().take(max_ply) {
+            let Some(mv) = parse_san(&board, san) else { break };
+            let key = polyglot_key(&board);
+            let raw_move = polyglot_move(&board, &mv);
+            let position = self.positions.entry(key).or_insert_with(|| BookPosition {
+                team_to_play: board.state.team_to_play,
+                moves: HashMap::new()
+            });
+            position.moves.entry(raw_move).or_default().record(game.result);
+            if board.play_move(mv.origin, mv.target, mv.get_promotion(), true).is_none() {
+                break;
+            }
+            played += 1;
+        }
+        played
+    }
+
+    // Weights follow the Polyglot convention of two points per win and one per draw for the side to move.
+    // Moves played fewer than min_games times are left out.
+    pub fn to_polyglot(&self, min_games: u32) -> PolyglotBook {
+        let mut entries = Vec::new();
+        for (key, position) in &self.positions {
+            for (raw_move, stats) in &position.moves {
+                if stats.games < min_games {
+                    continue;
+                }
+                let wins = if position.team_to_play == Teams::WHITE { stats.white_wins } else { stats.black_wins };
+                entries.push((*key, *raw_move, wins as u64 * 2 + stats.draws as u64));
+            }
+        }
+        let highest = entries.iter().map(|(_, _, score)| *score).max().unwrap_or(0);
+        let scale = highest / u16::MAX as u64 + 1;
+        PolyglotBook::new(entries.into_iter().map(|(key, raw_move, score)| PolyglotEntry {
+            key,
+            raw_move,
+            weight: (score / scale) as u16,
+            learn: 0
+        }).collect())
+    }
+
+    // Little endian: magic "CBOK", u32 game count, u32 entry count, then per entry
+    // u64 key, u8 side to move, u16 move, and u32 games, white wins, draws and black wins.
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let mut file = BufWriter::new(File::create(path)?);
+        let count: usize = self.positions.values().map(|position| position.moves.len()).sum();
+        file.write_all(MAGIC)?;
+        file.write_all(&self.games.to_le_bytes())?;
+        file.write_all(&(count as u32).to_le_bytes())?;
+        for (key, position) in &self.positions {
+            for (raw_move, stats) in &position.moves {
+                file.write_all(&key.to_le_bytes())?;
+                file.write_all(&[position.team_to_play as u8])?;
+                file.write_all(&raw_move.to_le_bytes())?;
+                for value in [stats.games, stats.white_wins, stats.draws, stats.black_wins] {
+                    file.write_all(&value.to_le_bytes())?;
+                }
+            }
+        }
+        file.flush()
+    }
+
+    pub fn load(path: &str) -> io::Result<OpeningTree> {
+        let mut bytes = Vec::new();
+        File::open(path)?.read_to_end(&mut bytes)?;
+        let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+        if bytes.len() < 12 || &bytes[..4] != MAGIC {
+            return Err(invalid("not a caissa book file"));
+        }
+        let games = u32::from_le_bytes(bytes[4..8].try_into().unwrap());
+        let count = u32::from_le_bytes(bytes[8..12].try_into().unwrap()) as usize;
+        let entry_size = 8 + 1 + 2 + 4 * 4;
+        if bytes.len() != 12 + count * entry_size {
+            return Err(invalid("book file has an unexpected size"));
+        }
+        let mut tree = OpeningTree { positions: HashMap::new(), games };
+        for chunk in bytes[12..].chunks_exact(entry_size) {
+            let key = u64::from_le_bytes(chunk[0..8].try_into().unwrap());
+            let team_to_play = chunk[8] as Team;
+            let raw_move = u16::from_le_bytes(chunk[9..11].try_into().unwrap());
+            let value = |index: usize| u32::from_le_bytes(chunk[11 + index * 4..15 + index * 4].try_into().unwrap());
+            let stats = MoveStats { games: value(0), white_wins: value(1), draws: value(2), black_wins: value(3) };
+            let position = tree.positions.entry(key).or_insert_with(|| BookPosition { team_to_play, moves: HashMap::new() });
+            position.moves.insert(raw_move, stats);
+        }
+        Ok(tree)
+    }
+}
+
+// Polyglot encoding of a move, castling written as the king taking its own rook
+fn polyglot_move(board: &ChessBoard, mv: &CompletedMove) -> u16 {
+    let (origin, target) = (mv.origin, mv.target);
+    let team = board.state.team_to_play;
+    let king = board.bits.get_pieces(team, Pieces::KING).0 & (1 << origin) != 0;
+    let target = match (king, target as i8 - origin as i8) {
+        (true, 2) => origin + 3,
+        (true, -2) => origin - 4,
+        _ => target
+    };
+    PolyglotEntry::encode_move(origin, target, mv.get_promotion())
+}
@@ -0,0 +1,43 @@
+use std::str::FromStr;
+
+use serde::Deserialize;
+
+pub mod builder;
+pub mod polyglot;
+mod random;
+
+// How a move is chosen among the book entries of a position
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BookSelection {
+    // Random, proportional to the entry weights
+    #[default]
+    Weighted,
+    BestWeight
+}
+
+impl FromStr for BookSelection {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "weighted" => Ok(BookSelection::Weighted),
+            "best_weight" => Ok(BookSelection::BestWeight),
+            _ => Err(format!("Unknown book selection {}", value))
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BookSettings {
+    pub enabled: bool,
+    // The book is only consulted while fewer half-moves than this have been played
+    pub max_ply: u16,
+    pub selection: BookSelection
+}
+
+impl Default for BookSettings {
+    fn default() -> Self {
+        BookSettings { enabled: true, max_ply: 20, selection: BookSelection::default() }
+    }
+}
@@ -0,0 +1,216 @@
+use std::fs::File;
+use std::io::{self, Read, Write};
+
+use rand::Rng;
+
+use crate::board::{CompletedMove, Piece, Pieces, Teams};
+use crate::board::board::ChessBoard;
+use crate::board::state::CastlingSides;
+use crate::book::BookSelection;
+use crate::book::random::POLYGLOT_RANDOM;
+use crate::game::play::legal_moves;
+use crate::math::iterate_bits;
+
+const CASTLING_OFFSET: usize = 768;
+const EN_PASSANT_OFFSET: usize = 772;
+const TURN_OFFSET: usize = 780;
+const ENTRY_SIZE: usize = 16;
+
+// Polyglot orders pieces as pawn, knight, bishop, rook, queen, king, indexed here by our piece constants
+const POLYGLOT_PIECE: [usize; 6] = [0, 2, 1, 3, 4, 5];
+
+// Key of a position as defined by the Polyglot format, unrelated to the engine's own Zobrist keys
+pub fn polyglot_key(board: &ChessBoard) -> u64 {
+    let mut key = 0;
+    for team in [Teams::WHITE, Teams::BLACK] {
+        for piece in Pieces::iter() {
+            let kind = POLYGLOT_PIECE[piece] * 2 + (team == Teams::WHITE) as usize;
+            iterate_bits(board.bits.get_pieces(team, piece).0, |bit| {
+                key ^= POLYGLOT_RANDOM[kind * 64 + bit.trailing_zeros() as usize];
+            });
+        }
+    }
+
+    let rights = &board.state.castling_rights;
+    let sides = [
+        (Teams::WHITE, CastlingSides::KINGSIDE),
+        (Teams::WHITE, CastlingSides::QUEENSIDE),
+        (Teams::BLACK, CastlingSides::KINGSIDE),
+        (Teams::BLACK, CastlingSides::QUEENSIDE)
+    ];
+    for (index, (team, side)) in sides.into_iter().enumerate() {
+        if rights.is_allowed(team, side) {
+            key ^= POLYGLOT_RANDOM[CASTLING_OFFSET + index];
+        }
+    }
+
+    // The en passant file only counts when a pawn of the side to move can actually take
+    if let Some(square) = board.state.en_passant_square {
+        let team = board.state.team_to_play;
+        let pawns = board.bits.get_pieces(team, Pieces::PAWN).0;
+        let file = square % 8;
+        let capturer_rank = if team == Teams::WHITE { 4 } else { 3 };
+        let neighbours = [file.checked_sub(1), Some(file + 1).filter(|file| *file < 8)];
+        if neighbours.into_iter().flatten().any(|neighbour| pawns & (1 << (capturer_rank * 8 + neighbour)) != 0) {
+            key ^= POLYGLOT_RANDOM[EN_PASSANT_OFFSET + file];
+        }
+    }
+
+    if board.state.team_to_play == Teams::WHITE {
+        key ^= POLYGLOT_RANDOM[TURN_OFFSET];
+    }
+    key
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PolyglotEntry {
+    pub key: u64,
+    // to file, to rank, from file, from rank and promotion piece, three bits each from the lowest
+    pub raw_move: u16,
+    pub weight: u16,
+    pub learn: u32
+}
+
+impl PolyglotEntry {
+    pub fn origin(&self) -> u8 {
+        ((self.raw_move >> 6) & 63) as u8
+    }
+
+    pub fn target(&self) -> u8 {
+        (self.raw_move & 63) as u8
+    }
+
+    // Knight, bishop, rook and queen are promotion pieces 1 to 4, 0 being no promotion
+    pub fn promotion(&self) -> Option<Piece> {
+        match (self.raw_move >> 12) & 7 {
+            1 => Some(Pieces::KNIGHT),
+            2 => Some(Pieces::BISHOP),
+            3 => Some(Pieces::ROOK),
+            4 => Some(Pieces::QUEEN),
+            _ => None
+        }
+    }
+
+    pub fn encode_move(origin: u8, target: u8, promotion: Option<Piece>) -> u16 {
+        let promotion = match promotion {
+            Some(Pieces::KNIGHT) => 1,
+            Some(Pieces::BISHOP) => 2,
+            Some(Pieces::ROOK) => 3,
+            Some(Pieces::QUEEN) => 4,
+            _ => 0
+        };
+        (promotion << 12) | ((origin as u16) << 6) | target as u16
+    }
+}
+
+// Entries sorted by key, as laid out in a .bin file
+#[derive(Debug, Clone, Default)]
+pub struct PolyglotBook {
+    entries: Vec<PolyglotEntry>
+}
+
+impl PolyglotBook {
+    pub fn new(mut entries: Vec<PolyglotEntry>) -> Self {
+        entries.sort_by(|a, b| a.key.cmp(&b.key).then(b.weight.cmp(&a.weight)));
+        PolyglotBook { entries }
+    }
+
+    pub fn load(path: &str) -> io::Result<PolyglotBook> {
+        let mut bytes = Vec::new();
+        File::open(path)?.read_to_end(&mut bytes)?;
+        Self::from_bytes(&bytes)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<PolyglotBook> {
+        if !bytes.len().is_multiple_of(ENTRY_SIZE) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "polyglot book has a truncated entry"));
+        }
+        let entries = bytes.chunks_exact(ENTRY_SIZE).map(|chunk| PolyglotEntry {
+            key: u64::from_be_bytes(chunk[0..8].try_into().unwrap()),
+            raw_move: u16::from_be_bytes(chunk[8..10].try_into().unwrap()),
+            weight: u16::from_be_bytes(chunk[10..12].try_into().unwrap()),
+            learn: u32::from_be_bytes(chunk[12..16].try_into().unwrap())
+        }).collect();
+        Ok(Self::new(entries))
+    }
+
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let mut file = io::BufWriter::new(File::create(path)?);
+        for entry in &self.entries {
+            file.write_all(&entry.key.to_be_bytes())?;
+            file.write_all(&entry.raw_move.to_be_bytes())?;
+            file.write_all(&entry.weight.to_be_bytes())?;
+            file.write_all(&entry.learn.to_be_bytes())?;
+        }
+        file.flush()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn entries(&self, key: u64) -> &[PolyglotEntry] {
+        let start = self.entries.partition_point(|entry| entry.key < key);
+        let end = self.entries.partition_point(|entry| entry.key <= key);
+        &self.entries[start..end]
+    }
+
+    // Book moves playable in this position with their weights, entries the board rejects are skipped
+    pub fn moves(&self, board: &ChessBoard) -> Vec<(CompletedMove, u16)> {
+        let legal = legal_moves(board);
+        self.entries(polyglot_key(board))
+            .iter()
+            .filter(|entry| entry.weight > 0)
+            .filter_map(|entry| {
+                let (origin, target) = resolve_castling(board, entry.origin(), entry.target());
+                legal.iter()
+                    .find(|mv| mv.origin == origin && mv.target == target && mv.get_promotion() == entry.promotion())
+                    .map(|mv| (mv.clone(), entry.weight))
+            })
+            .collect()
+    }
+
+    pub fn pick(&self, board: &ChessBoard, selection: BookSelection) -> Option<CompletedMove> {
+        let moves = self.moves(board);
+        match selection {
+            BookSelection::BestWeight => moves.into_iter().max_by_key(|(_, weight)| *weight).map(|(mv, _)| mv),
+            BookSelection::Weighted => {
+                let total: u32 = moves.iter().map(|(_, weight)| *weight as u32).sum();
+                if total == 0 {
+                    return None;
+                }
+                let mut roll = rand::thread_rng().gen_range(0..total);
+                for (mv, weight) in moves {
+                    if roll < weight as u32 {
+                        return Some(mv);
+                    }
+                    roll -= weight as u32;
+                }
+                None
+            }
+        }
+    }
+}
+
+// Polyglot writes castling as the king taking its own rook (e1h1), the board expects the king's destination
+// outside Chess960
+fn resolve_castling(board: &ChessBoard, origin: u8, target: u8) -> (u8, u8) {
+    if board.state.chess960 {
+        return (origin, target);
+    }
+    let team = board.state.team_to_play;
+    let home = if team == Teams::WHITE { 4 } else { 60 };
+    let king = board.bits.get_pieces(team, Pieces::KING).0;
+    if origin != home || king & (1 << origin) == 0 {
+        return (origin, target);
+    }
+    match target as i8 - origin as i8 {
+        3 => (origin, origin + 2),
+        -4 => (origin, origin - 2),
+        _ => (origin, target)
+    }
+}
@@ -0,0 +1,135 @@
+use lazy_static::lazy_static;
+
+use crate::board::{BitBoard, Team, Teams};
+use crate::math::kings::mask_king_moves;
+use crate::math::pawns::mask_pawn_capture_moves;
+
+// White king, black king, side to move, pawn file (a-d) and pawn rank (2-7)
+const POSITIONS: usize = 64 * 64 * 2 * 4 * 6;
+
+const INVALID: u8 = 0;
+const UNKNOWN: u8 = 1;
+const DRAW: u8 = 2;
+const WIN: u8 = 4;
+
+lazy_static! {
+    // One bit per position, set when white wins
+    static ref KPK: Vec<u64> = generate();
+}
+
+fn index(team_to_play: Team, black_king: usize, white_king: usize, pawn: usize) -> usize {
+    white_king | black_king << 6 | team_to_play << 12 | (pawn % 8) << 13 | (6 - pawn / 8) << 15
+}
+
+fn distance(a: usize, b: usize) -> usize {
+    (a % 8).abs_diff(b % 8).max((a / 8).abs_diff(b / 8))
+}
+
+fn king_attacks(square: usize) -> u64 {
+    mask_king_moves(1 << square, None).0
+}
+
+fn pawn_attacks(square: usize) -> u64 {
+    mask_pawn_capture_moves(1 << square, &BitBoard(!0), &None, Teams::WHITE).0
+}
+
+// Whether white wins king and pawn against king. The pawn must be white and on files a to d,
+// callers mirror the position beforehand.
+pub fn probe_kpk(white_king: usize, pawn: usize, black_king: usize, team_to_play: Team) -> bool {
+    let index = index(team_to_play, black_king, white_king, pawn);
+    KPK[index / 64] & (1 << (index % 64)) != 0
+}
+
+// Positions that are decided immediately are classified first, the rest is resolved by repeatedly
+// looking at the successors until nothing changes: a position is won for the side to move when one
+// move reaches a position won for it, and lost when every move does for the opponent.
+fn generate() -> Vec<u64> {
+    let mut results = vec![INVALID; POSITIONS];
+    for (index, result) in results.iter_mut().enumerate() {
+        *result = classify_initial(index);
+    }
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for index in 0..POSITIONS {
+            if results[index] == UNKNOWN {
+                let result = classify(&results, index);
+                if result != UNKNOWN {
+                    results[index] = result;
+                    changed = true;
+                }
+            }
+        }
+    }
+
+    let mut bits = vec![0; POSITIONS / 64];
+    for (index, result) in results.iter().enumerate() {
+        if *result == WIN {
+            bits[index / 64] |= 1 << (index % 64);
+        }
+    }
+    bits
+}
+
+fn decode(index: usize) -> (Team, usize, usize, usize) {
+    let white_king = index & 0x3F;
+    let black_king = (index >> 6) & 0x3F;
+    let team_to_play = (index >> 12) & 1;
+    let pawn = (6 - (index >> 15)) * 8 + ((index >> 13) & 3);
+    (team_to_play, black_king, white_king, pawn)
+}
+
+fn classify_initial(index: usize) -> u8 {
+    let (team_to_play, black_king, white_king, pawn) = decode(index);
+    let promotion = pawn + 8;
+    if distance(white_king, black_king) <= 1
+        || white_king == pawn
+        || black_king == pawn
+        || (team_to_play == Teams::WHITE && pawn_attacks(pawn) & (1 << black_king) != 0) {
+        return INVALID;
+    }
+    // The pawn promotes without being taken
+    if team_to_play == Teams::WHITE
+        && pawn / 8 == 6
+        && white_king != promotion
+        && (distance(black_king, promotion) > 1 || distance(white_king, promotion) == 1) {
+        return WIN;
+    }
+    // Stalemate, or the pawn can be taken
+    if team_to_play == Teams::BLACK {
+        let escapes = king_attacks(black_king) & !(king_attacks(white_king) | pawn_attacks(pawn));
+        let takes = king_attacks(black_king) & !king_attacks(white_king) & (1 << pawn);
+        if escapes == 0 || takes != 0 {
+            return DRAW;
+        }
+    }
+    UNKNOWN
+}
+
+fn classify(results: &[u8], index: usize) -> u8 {
+    let (team_to_play, black_king, white_king, pawn) = decode(index);
+    let mut reachable = INVALID;
+    if team_to_play == Teams::WHITE {
+        let mut moves = king_attacks(white_king);
+        while moves != 0 {
+            let square = moves.trailing_zeros() as usize;
+            moves &= moves - 1;
+            reachable |= results[self::index(Teams::BLACK, black_king, square, pawn)];
+        }
+        if pawn / 8 < 6 {
+            reachable |= results[self::index(Teams::BLACK, black_king, white_king, pawn + 8)];
+        }
+        if pawn / 8 == 1 && pawn + 8 != white_king && pawn + 8 != black_king {
+            reachable |= results[self::index(Teams::BLACK, black_king, white_king, pawn + 16)];
+        }
+        if reachable & WIN != 0 { WIN } else if reachable & UNKNOWN != 0 { UNKNOWN } else { DRAW }
+    } else {
+        let mut moves = king_attacks(black_king);
+        while moves != 0 {
+            let square = moves.trailing_zeros() as usize;
+            moves &= moves - 1;
+            reachable |= results[self::index(Teams::WHITE, square, white_king, pawn)];
+        }
+        if reachable & DRAW != 0 { DRAW } else if reachable & UNKNOWN != 0 { UNKNOWN } else { WIN }
+    }
+}
@@ -0,0 +1,134 @@
+use crate::board::{get_opposite_team, Pieces, Team, Teams};
+use crate::board::board::ChessBoard;
+use crate::board::variant::Variant;
+use crate::engine::bitbase::probe_kpk;
+use crate::engine::PIECE_VALUES;
+
+// Added to endgames known to be won, so the search heads for them over any ordinary advantage
+pub const KNOWN_WIN: i32 = 10_000;
+// The winning side to move is a move closer to its goal. Without it, a promotion left to the quiescence
+// search, where the opponent gets no reply, looks better than promoting now.
+const WINNING_TEMPO: i32 = 20;
+
+const DARK_SQUARES: u64 = 0xAA55AA55AA55AA55;
+
+// Evaluation of the endgames against a lone king that the general terms misjudge, from the reference
+// team's point of view. None when the material has no dedicated rule or the variant changes what wins.
+pub fn evaluate_endgame(board: &ChessBoard, reference: Team) -> Option<i32> {
+    if board.state.variant != Variant::Standard {
+        return None;
+    }
+    let counts = |team: Team| -> [u32; 6] { std::array::from_fn(|piece| board.bits.get_pieces(team, piece).count_ones()) };
+    let white = counts(Teams::WHITE);
+    let black = counts(Teams::BLACK);
+    let bare = |counts: &[u32; 6]| counts[..Pieces::KING].iter().all(|count| *count == 0);
+    let (strong, counts) = match (bare(&white), bare(&black)) {
+        (true, true) => return Some(0),
+        (false, true) => (Teams::WHITE, white),
+        (true, false) => (Teams::BLACK, black),
+        (false, false) => return None
+    };
+    let [pawns, bishops, knights, rooks, queens, _] = counts;
+
+    let score = if pawns == 1 && bishops + knights + rooks + queens == 0 {
+        evaluate_kpk(board, strong)?
+    } else if pawns == 0 && rooks + queens == 0 && (bishops + knights == 1 || (knights == 2 && bishops == 0)) {
+        // A single minor piece, or two knights, cannot force mate
+        0
+    } else if pawns == 0 && rooks + queens == 0 && bishops == 1 && knights == 1 {
+        evaluate_kbnk(board, strong)
+    } else if pawns > 0 && rooks + queens + knights == 0 && bishops == 1 && is_wrong_rook_pawn(board, strong) {
+        0
+    } else if rooks + queens > 0 || has_bishop_pair(board, strong) {
+        evaluate_kxk(board, strong)
+    } else {
+        return None;
+    };
+    let score = if score >= KNOWN_WIN && board.state.team_to_play == strong { score + WINNING_TEMPO } else { score };
+    Some(if reference == strong { score } else { -score })
+}
+
+fn distance(a: usize, b: usize) -> i32 {
+    (a % 8).abs_diff(b % 8).max((a / 8).abs_diff(b / 8)) as i32
+}
+
+fn edge_distance(coordinate: usize) -> i32 {
+    coordinate.min(7 - coordinate) as i32
+}
+
+fn king_square(board: &ChessBoard, team: Team) -> usize {
+    board.bits.get_pieces(team, Pieces::KING).0.trailing_zeros() as usize
+}
+
+// Bonus for the defending king being close to the edge of the board
+fn push_to_edge(square: usize) -> i32 {
+    let rank = edge_distance(square / 8);
+    let file = edge_distance(square % 8);
+    90 - (7 * file * file / 2 + 7 * rank * rank / 2)
+}
+
+fn push_close(a: usize, b: usize) -> i32 {
+    140 - 20 * distance(a, b)
+}
+
+// Mating material against a lone king: drive it to the edge and bring the kings together
+fn evaluate_kxk(board: &ChessBoard, strong: Team) -> i32 {
+    let strong_king = king_square(board, strong);
+    let weak_king = king_square(board, get_opposite_team(strong));
+    KNOWN_WIN + board.material(strong) + push_to_edge(weak_king) + push_close(strong_king, weak_king)
+}
+
+// Bishop and knight only mate in a corner of the bishop's colour
+fn evaluate_kbnk(board: &ChessBoard, strong: Team) -> i32 {
+    let strong_king = king_square(board, strong);
+    let weak_king = king_square(board, get_opposite_team(strong));
+    let bishops = board.bits.get_pieces(strong, Pieces::BISHOP).0;
+    // Distance to the a8-h1 diagonal, which is largest in the dark corners a1 and h8
+    let target = if bishops & DARK_SQUARES != 0 { weak_king } else { weak_king ^ 7 };
+    let push_to_corner = (7 - (target / 8) as i32 - (target % 8) as i32).abs();
+    KNOWN_WIN + board.material(strong) + push_close(strong_king, weak_king) + 40 * push_to_corner
+}
+
+// None when the pawn stands on its first rank, which only a set-up position has and the bitbase does not
+// cover. Each step forward is worth more than the last, so the search pushes the pawn rather than the king.
+fn evaluate_kpk(board: &ChessBoard, strong: Team) -> Option<i32> {
+    // The bitbase is stored with white as the strong side and the pawn on files a to d
+    let flip_rank = if strong == Teams::WHITE { 0 } else { 56 };
+    let pawn = board.bits.get_pieces(strong, Pieces::PAWN).0.trailing_zeros() as usize ^ flip_rank;
+    if !(1..7).contains(&(pawn / 8)) {
+        return None;
+    }
+    let flip_file = if pawn % 8 > 3 { 7 } else { 0 };
+    let strong_king = king_square(board, strong) ^ flip_rank ^ flip_file;
+    let weak_king = king_square(board, get_opposite_team(strong)) ^ flip_rank ^ flip_file;
+    let team_to_play = if board.state.team_to_play == strong { Teams::WHITE } else { Teams::BLACK };
+    if !probe_kpk(strong_king, pawn ^ flip_file, weak_king, team_to_play) {
+        return Some(0);
+    }
+    let rank = (pawn / 8) as i32;
+    Some(KNOWN_WIN + PIECE_VALUES[Pieces::PAWN] + 10 * rank * rank)
+}
+
+// Rook pawns with a bishop that does not control the promotion square, and the defending king
+// already in front of them
+fn is_wrong_rook_pawn(board: &ChessBoard, strong: Team) -> bool {
+    let pawns = board.bits.get_pieces(strong, Pieces::PAWN).0;
+    const A_FILE: u64 = 0x0101010101010101;
+    let file = if pawns & !A_FILE == 0 {
+        0
+    } else if pawns & !(A_FILE << 7) == 0 {
+        7
+    } else {
+        return false;
+    };
+    let promotion = if strong == Teams::WHITE { 56 + file } else { file };
+    let bishops = board.bits.get_pieces(strong, Pieces::BISHOP).0;
+    let promotion_is_dark = DARK_SQUARES & (1 << promotion) != 0;
+    let bishop_is_dark = bishops & DARK_SQUARES != 0;
+    promotion_is_dark != bishop_is_dark && distance(king_square(board, get_opposite_team(strong)), promotion) <= 1
+}
+
+fn has_bishop_pair(board: &ChessBoard, team: Team) -> bool {
+    let bishops = board.bits.get_pieces(team, Pieces::BISHOP).0;
+    bishops & DARK_SQUARES != 0 && bishops & !DARK_SQUARES != 0
+}
@@ -0,0 +1,169 @@
+use std::str::FromStr;
+use std::sync::Arc;
+
+use serde::Deserialize;
+
+use crate::board::{CompletedMove, get_opposite_team, Team};
+use crate::board::board::ChessBoard;
+use crate::engine::endgame::evaluate_endgame;
+use crate::engine::king_safety::evaluate_king_safety;
+use crate::engine::mobility::evaluate_mobility;
+use crate::engine::nnue::{Network, NnueEvaluator};
+use crate::engine::pawn_structure::{evaluate_pawn_structure, PawnHashTable};
+use crate::engine::pst::taper;
+
+// Static evaluation used at the leaves of the search, in centipawns from the reference team's point of view.
+// The make/unmake hooks are called by the search around every move, for evaluators keeping incremental state,
+// and prepare is called once on the board a search is about to walk.
+pub trait Evaluator: Clone + Send {
+    fn evaluate(&mut self, board: &ChessBoard, reference: Team) -> i32;
+
+    fn prepare(&mut self, _board: &mut ChessBoard) {}
+
+    fn on_make(&mut self, _board: &ChessBoard, _mv: &CompletedMove) {}
+
+    fn on_unmake(&mut self, _board: &ChessBoard, _mv: &CompletedMove) {}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EvaluatorKind {
+    Material,
+    #[default]
+    HandCrafted,
+    Nnue
+}
+
+impl EvaluatorKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            EvaluatorKind::Material => "material",
+            EvaluatorKind::HandCrafted => "hand_crafted",
+            EvaluatorKind::Nnue => "nnue"
+        }
+    }
+}
+
+impl FromStr for EvaluatorKind {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "material" => Ok(EvaluatorKind::Material),
+            "hand_crafted" => Ok(EvaluatorKind::HandCrafted),
+            "nnue" => Ok(EvaluatorKind::Nnue),
+            _ => Err(format!("Unknown evaluator {}", value))
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct MaterialEvaluator;
+
+impl Evaluator for MaterialEvaluator {
+    fn evaluate(&mut self, board: &ChessBoard, reference: Team) -> i32 {
+        board.material(reference)
+    }
+}
+
+// Material, tapered piece-square tables, pawn structure, mobility and king safety,
+// with dedicated rules for the endgames against a lone king
+#[derive(Clone, Default)]
+pub struct HandCraftedEvaluator {
+    pawn_table: PawnHashTable
+}
+
+impl Evaluator for HandCraftedEvaluator {
+    fn evaluate(&mut self, board: &ChessBoard, reference: Team) -> i32 {
+        if let Some(score) = evaluate_endgame(board, reference) {
+            return score;
+        }
+        let opponent = get_opposite_team(reference);
+        let (pawns_middlegame, pawns_endgame) = evaluate_pawn_structure(board, reference, &mut self.pawn_table);
+        let (mobility_middlegame, mobility_endgame) = evaluate_mobility(board, reference);
+        let (king_middlegame, king_endgame) = evaluate_king_safety(board, reference);
+        let middlegame = pawns_middlegame + mobility_middlegame + king_middlegame;
+        let endgame = pawns_endgame + mobility_endgame + king_endgame;
+        board.material(reference) + board.psqt.tapered(reference, opponent) + taper(middlegame, endgame, board.psqt.phase())
+    }
+}
+
+// Runtime choice between the evaluators, so a single engine type can serve any request
+#[derive(Clone)]
+pub enum AnyEvaluator {
+    Material(MaterialEvaluator),
+    HandCrafted(Box<HandCraftedEvaluator>),
+    Nnue(NnueEvaluator)
+}
+
+impl AnyEvaluator {
+    pub fn kind(&self) -> EvaluatorKind {
+        match self {
+            AnyEvaluator::Material(_) => EvaluatorKind::Material,
+            AnyEvaluator::HandCrafted(_) => EvaluatorKind::HandCrafted,
+            AnyEvaluator::Nnue(_) => EvaluatorKind::Nnue
+        }
+    }
+}
+
+impl Default for AnyEvaluator {
+    fn default() -> Self {
+        AnyEvaluator::HandCrafted(Box::default())
+    }
+}
+
+// Evaluators the server can hand out, decided at startup
+#[derive(Clone, Default)]
+pub struct EvaluatorRegistry {
+    pub default: EvaluatorKind,
+    network: Option<Arc<Network>>
+}
+
+impl EvaluatorRegistry {
+    pub fn new(default: EvaluatorKind, network: Option<Network>) -> Self {
+        EvaluatorRegistry { default, network: network.map(Arc::new) }
+    }
+
+    // None when the evaluator needs a network that was not loaded
+    pub fn create(&self, kind: EvaluatorKind) -> Option<AnyEvaluator> {
+        match kind {
+            EvaluatorKind::Material => Some(AnyEvaluator::Material(MaterialEvaluator)),
+            EvaluatorKind::HandCrafted => Some(AnyEvaluator::HandCrafted(Box::default())),
+            EvaluatorKind::Nnue => self.network.clone().map(|network| AnyEvaluator::Nnue(NnueEvaluator::new(network)))
+        }
+    }
+}
+
+impl Evaluator for AnyEvaluator {
+    fn evaluate(&mut self, board: &ChessBoard, reference: Team) -> i32 {
+        match self {
+            AnyEvaluator::Material(evaluator) => evaluator.evaluate(board, reference),
+            AnyEvaluator::HandCrafted(evaluator) => evaluator.evaluate(board, reference),
+            AnyEvaluator::Nnue(evaluator) => evaluator.evaluate(board, reference)
+        }
+    }
+
+    fn prepare(&mut self, board: &mut ChessBoard) {
+        match self {
+            AnyEvaluator::Material(evaluator) => evaluator.prepare(board),
+            AnyEvaluator::HandCrafted(evaluator) => evaluator.prepare(board),
+            AnyEvaluator::Nnue(evaluator) => evaluator.prepare(board)
+        }
+    }
+
+    fn on_make(&mut self, board: &ChessBoard, mv: &CompletedMove) {
+        match self {
+            AnyEvaluator::Material(evaluator) => evaluator.on_make(board, mv),
+            AnyEvaluator::HandCrafted(evaluator) => evaluator.on_make(board, mv),
+            AnyEvaluator::Nnue(evaluator) => evaluator.on_make(board, mv)
+        }
+    }
+
+    fn on_unmake(&mut self, board: &ChessBoard, mv: &CompletedMove) {
+        match self {
+            AnyEvaluator::Material(evaluator) => evaluator.on_unmake(board, mv),
+            AnyEvaluator::HandCrafted(evaluator) => evaluator.on_unmake(board, mv),
+            AnyEvaluator::Nnue(evaluator) => evaluator.on_unmake(board, mv)
+        }
+    }
+}
@@ -0,0 +1,111 @@
+use std::fmt::Display;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::board::CompletedMove;
+use crate::engine::{MATE_SCORE, MAX_PLY};
+
+pub type InfoCallback = Arc<dyn Fn(&SearchInfo) + Send + Sync>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Score {
+    // Centipawns from the side to move's point of view
+    Cp(i32),
+    // Moves (not plies) until mate, negative when the side to move is getting mated
+    Mate(i32)
+}
+
+impl Score {
+    pub fn from_internal(score: i32) -> Score {
+        if score.abs() >= MATE_SCORE - MAX_PLY as i32 {
+            let moves = (MATE_SCORE - score.abs() + 1) / 2;
+            Score::Mate(if score > 0 { moves } else { -moves })
+        } else {
+            Score::Cp(score)
+        }
+    }
+}
+
+impl Display for Score {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Score::Cp(cp) => write!(f, "cp {}", cp),
+            Score::Mate(moves) => write!(f, "mate {}", moves)
+        }
+    }
+}
+
+// Progress of an iterative deepening search, reported after every completed iteration
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchInfo {
+    pub depth: u8,
+    pub seldepth: u8,
+    pub nodes: u64,
+    pub nps: u64,
+    pub hashfull: u16,
+    // Rank of the line among the multi-PV lines, starting at 1
+    pub multipv: usize,
+    pub score: Score,
+    pub pv: Vec<CompletedMove>,
+    pub elapsed_ms: u64
+}
+
+impl Display for SearchInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "info depth {} seldepth {} multipv {} score {} nodes {} nps {} hashfull {} time {} pv",
+            self.depth, self.seldepth, self.multipv, self.score, self.nodes, self.nps, self.hashfull, self.elapsed_ms
+        )?;
+        for mv in &self.pv {
+            write!(f, " {}", mv)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchLine {
+    pub score: Score,
+    pub pv: Vec<CompletedMove>
+}
+
+// Move chosen by a search, serialized with the move's own fields at the top level
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchResult {
+    #[serde(flatten)]
+    pub best_move: CompletedMove,
+    // None when the move comes from the opening book or the tablebases rather than a search
+    pub score: Option<Score>,
+    pub depth: u8,
+    pub nodes: u64,
+    pub time_ms: u64,
+    pub pv: Vec<CompletedMove>,
+    // The best lines in order, as many as the multi-PV count asked for
+    pub lines: Vec<SearchLine>
+}
+
+impl SearchResult {
+    pub fn unsearched(best_move: CompletedMove) -> Self {
+        SearchResult {
+            pv: vec![best_move.clone()],
+            best_move,
+            score: None,
+            depth: 0,
+            nodes: 0,
+            time_ms: 0,
+            lines: Vec::new()
+        }
+    }
+}
+
+// Best lines of a position, best first, empty when the side to move has no legal move
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Analysis {
+    pub depth: u8,
+    pub nodes: u64,
+    pub time_ms: u64,
+    pub lines: Vec<SearchLine>
+}
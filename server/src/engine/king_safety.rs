@@ -0,0 +1,78 @@
+use crate::board::{get_opposite_team, Pieces, Team, Teams};
+use crate::board::board::ChessBoard;
+use crate::engine::mobility::piece_attacks;
+use crate::engine::pawn_structure::{adjacent_files_mask, file_mask, relative_rank};
+use crate::math::iterate_bits;
+use crate::math::kings::mask_king_moves;
+
+// Weight of each piece attacking the king zone, indexed by piece
+pub const ATTACK_WEIGHTS: [i32; 6] = [0, 2, 2, 3, 5, 0];
+// Middlegame penalty by accumulated attack weight, grows faster as more attackers join in
+pub const SAFETY_TABLE: [i32; 32] = [
+    0, 0, 1, 2, 3, 5, 7, 9, 12, 15, 18, 22, 26, 30, 35, 39,
+    44, 50, 56, 62, 68, 75, 82, 85, 89, 97, 105, 113, 122, 131, 140, 150
+];
+// Bonus for a pawn one or two squares in front of the king, penalty when a file has neither
+pub const SHIELD_BONUS: [i32; 2] = [12, 6];
+pub const MISSING_SHIELD_PENALTY: i32 = -15;
+pub const SEMI_OPEN_FILE_PENALTY: i32 = -12;
+pub const OPEN_FILE_PENALTY: i32 = -25;
+
+// King safety from the reference team's point of view. It only matters with pieces on the board,
+// so it is a middlegame-only term and fades out through the tapered evaluation.
+pub fn evaluate_king_safety(board: &ChessBoard, reference: Team) -> (i32, i32) {
+    let middlegame = team_king_safety(board, reference) - team_king_safety(board, get_opposite_team(reference));
+    (middlegame, 0)
+}
+
+fn team_king_safety(board: &ChessBoard, team: Team) -> i32 {
+    let king = board.bits.get_pieces(team, Pieces::KING).0;
+    if king == 0 {
+        return 0;
+    }
+    let square = king.trailing_zeros() as usize;
+    let zone = king | mask_king_moves(king, None).0;
+    let enemy = get_opposite_team(team);
+    let occupied = !board.bits.empty_squares();
+
+    let mut attackers = 0;
+    let mut attack_weight = 0;
+    for piece in [Pieces::KNIGHT, Pieces::BISHOP, Pieces::ROOK, Pieces::QUEEN] {
+        iterate_bits(board.bits.get_pieces(enemy, piece).0, |bit| {
+            let hits = (piece_attacks(piece, bit, &occupied).0 & zone).count_ones() as i32;
+            if hits > 0 {
+                attackers += 1;
+                attack_weight += ATTACK_WEIGHTS[piece] * hits;
+            }
+        });
+    }
+    // A lone attacker is rarely dangerous
+    let mut score = if attackers >= 2 {
+        -SAFETY_TABLE[(attack_weight as usize).min(SAFETY_TABLE.len() - 1)]
+    } else {
+        0
+    };
+
+    let own_pawns = board.bits.get_pieces(team, Pieces::PAWN).0;
+    let enemy_pawns = board.bits.get_pieces(enemy, Pieces::PAWN).0;
+    let king_file = square % 8;
+    let files = file_mask(king_file) | adjacent_files_mask(king_file);
+    iterate_bits(files & 0xFF, |file_bit| {
+        let file = file_mask(file_bit.trailing_zeros() as usize);
+        if relative_rank(team, square) <= 1 {
+            let one_ahead = if team == Teams::WHITE { file_bit << (8 * (square / 8 + 1)) } else { file_bit << (8 * (square / 8 - 1)) };
+            let two_ahead = if team == Teams::WHITE { one_ahead << 8 } else { one_ahead >> 8 };
+            if own_pawns & one_ahead != 0 {
+                score += SHIELD_BONUS[0];
+            } else if own_pawns & two_ahead != 0 {
+                score += SHIELD_BONUS[1];
+            } else {
+                score += MISSING_SHIELD_PENALTY;
+            }
+        }
+        if own_pawns & file == 0 {
+            score += if enemy_pawns & file == 0 { OPEN_FILE_PENALTY } else { SEMI_OPEN_FILE_PENALTY };
+        }
+    });
+    score
+}
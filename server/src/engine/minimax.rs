@@ -1,92 +1,575 @@
-use std::collections::HashMap;
-use crate::board::{CompletedMove, get_opposite_team, PossibleMove, Team};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Instant;
+
+use rand::Rng;
+
+use crate::board::{CompletedMove, Piece, Pieces, Team};
 use crate::board::board::ChessBoard;
-use crate::engine::ChessEngine;
+use crate::board::variant::Variant;
+use crate::book::BookSettings;
+use crate::book::polyglot::PolyglotBook;
+use crate::engine::{ChessEngine, get_piece_value, INFINITY, MATE_SCORE, MAX_PLY, PIECE_VALUES, SearchLimits, StopHandle};
+use crate::engine::evaluator::{Evaluator, HandCraftedEvaluator};
+use crate::engine::info::{Analysis, InfoCallback, Score, SearchInfo, SearchLine, SearchResult};
+#[cfg(feature = "syzygy")]
+use crate::engine::syzygy::{Tablebase, Wdl};
+use crate::engine::transposition::{Bound, score_from_tt, score_to_tt, TranspositionTable};
+use crate::engine::variants::evaluate_variant;
 use crate::hash::ZobristHash;
 
+const ASPIRATION_WINDOW: i32 = 50;
+const ASPIRATION_MIN_DEPTH: u8 = 3;
+// Helper threads deepen until the main thread is done, this only bounds them
+const HELPER_MAX_DEPTH: u8 = (MAX_PLY / 2) as u8;
+const HELPER_STACK_SIZE: usize = 32 * 1024 * 1024;
+// Lines searched when playing below full strength
+const SKILL_LINES: usize = 4;
+// Tablebase wins rank below every mate found by the search
+#[cfg(feature = "syzygy")]
+const TABLEBASE_WIN: i32 = MATE_SCORE - 2 * MAX_PLY as i32;
+
+// Clones share the transposition table, which is how helper threads cooperate
 #[derive(Clone)]
-pub struct MinimaxEngine {
+pub struct MinimaxEngine<E: Evaluator = HandCraftedEvaluator> {
     zobrist: ZobristHash,
-    transposition_table: HashMap<u64, i32>,
+    transposition_table: Arc<TranspositionTable>,
+    evaluator: E,
+    info_callback: Option<InfoCallback>,
+    nodes: u64,
+    seldepth: u8,
+    // Triangular principal variation table, one line per ply
+    pv: Vec<Vec<CompletedMove>>,
+    book: Option<Arc<PolyglotBook>>,
+    book_settings: BookSettings,
+    #[cfg(feature = "syzygy")]
+    tablebase: Option<Arc<Tablebase>>,
+    threads: usize,
+    stop: StopHandle,
+    limits: SearchLimits,
+    start: Instant,
+    limit_reached: bool,
+    // Root moves left out of the current multi-PV line
+    excluded: Vec<(u8, u8, Option<Piece>)>,
+    // Raised by the main thread once its search is over, helper threads stop on it
+    helpers_stop: Arc<AtomicBool>,
 }
 
-impl MinimaxEngine {
-    fn minimax(&mut self, board: &mut ChessBoard, depth: u8, team: Team, alpha: i32, beta: i32) -> i32 {
-        let maximizing = team == board.state.team_to_play;
-        let zobrist = self.zobrist.hash(&board);
+impl<E: Evaluator> MinimaxEngine<E> {
+    pub fn with_evaluator(evaluator: E) -> Self {
+        MinimaxEngine {
+            zobrist: ZobristHash::new(),
+            transposition_table: Arc::new(TranspositionTable::new(TranspositionTable::DEFAULT_SIZE_MB)),
+            evaluator,
+            info_callback: None,
+            nodes: 0,
+            seldepth: 0,
+            pv: vec![Vec::new(); MAX_PLY],
+            book: None,
+            book_settings: BookSettings::default(),
+            #[cfg(feature = "syzygy")]
+            tablebase: None,
+            threads: 1,
+            stop: StopHandle::default(),
+            limits: SearchLimits::depth(1),
+            start: Instant::now(),
+            limit_reached: false,
+            excluded: Vec::new(),
+            helpers_stop: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    // An engine for an independent search, with the same settings and transposition table but its own stop signals
+    pub fn fork(&self) -> Self {
+        let mut engine = self.clone();
+        engine.stop = StopHandle::default();
+        engine.helpers_stop = Arc::new(AtomicBool::new(false));
+        engine
+    }
+
+    // Engines cloned or forked before keep the previous table
+    pub fn set_hash_size(&mut self, size_mb: usize) {
+        self.transposition_table = Arc::new(TranspositionTable::new(size_mb));
+    }
+
+    // Forgets everything searched so far, for a new game. Forks sharing the table lose it too.
+    pub fn clear_hash(&mut self) {
+        self.transposition_table.clear();
+    }
+
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+
+    // Threads searching each position, the main one included
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = threads.max(1);
+    }
 
-        if let Some(score) = self.transposition_table.get(&zobrist) {
-            return *score;
+    pub fn evaluator(&self) -> &E {
+        &self.evaluator
+    }
+
+    // Scores from the previous evaluator would poison the transposition table, so it is cleared
+    pub fn set_evaluator(&mut self, evaluator: E) {
+        self.evaluator = evaluator;
+        self.transposition_table.clear();
+    }
+
+    pub fn set_info_callback<F>(&mut self, callback: F)
+    where
+        F: Fn(&SearchInfo) + Send + Sync + 'static
+    {
+        self.info_callback = Some(Arc::new(callback));
+    }
+
+    pub fn clear_info_callback(&mut self) {
+        self.info_callback = None;
+    }
+
+    pub fn set_book(&mut self, book: Option<Arc<PolyglotBook>>) {
+        self.book = book;
+    }
+
+    pub fn book_settings(&self) -> &BookSettings {
+        &self.book_settings
+    }
+
+    pub fn set_book_settings(&mut self, settings: BookSettings) {
+        self.book_settings = settings;
+    }
+
+    fn book_move(&self, board: &ChessBoard) -> Option<CompletedMove> {
+        let settings = &self.book_settings;
+        // Opening books are built from standard games
+        if !settings.enabled || board.state.ply >= settings.max_ply || board.state.variant != Variant::Standard {
+            return None;
         }
+        self.book.as_ref()?.pick(board, settings.selection)
+    }
 
-        if depth == 0 {
-            let score = board.evaluate(team);
-            self.transposition_table.insert(zobrist, score);
+    #[cfg(feature = "syzygy")]
+    pub fn set_tablebase(&mut self, tablebase: Option<Arc<Tablebase>>) {
+        self.tablebase = tablebase;
+        self.transposition_table.clear();
+    }
+
+    #[cfg(feature = "syzygy")]
+    fn tablebase_move(&self, board: &ChessBoard) -> Option<CompletedMove> {
+        self.tablebase.as_ref()?.best_move(board).map(|(mv, _)| mv)
+    }
+
+    #[cfg(feature = "syzygy")]
+    fn tablebase_score(&self, board: &ChessBoard, ply: usize) -> Option<i32> {
+        let tablebase = self.tablebase.as_ref()?;
+        if !tablebase.can_probe(board) {
+            return None;
+        }
+        Some(match tablebase.probe_wdl(board)? {
+            Wdl::Win => TABLEBASE_WIN - ply as i32,
+            Wdl::Loss => -TABLEBASE_WIN + ply as i32,
+            // Decided by the fifty-move rule, kept just apart from a plain draw
+            Wdl::CursedWin => 1,
+            Wdl::BlessedLoss => -1,
+            Wdl::Draw => 0
+        })
+    }
+
+    fn stopped(&self) -> bool {
+        self.limit_reached || self.stop.is_stopped() || self.helpers_stop.load(Ordering::Relaxed)
+    }
+
+    // Lazy SMP: helpers search the same root and only share what they find through the transposition table.
+    // Odd helpers start one ply deeper so the threads do not walk the same depths in lockstep.
+    fn helper_search(&mut self, board: &ChessBoard, id: usize) {
+        let mut board = board.clone();
+        self.evaluator.prepare(&mut board);
+        let mut score = 0;
+        for depth in (1 + id % 2) as u8..=HELPER_MAX_DEPTH {
+            score = self.aspiration_search(&mut board, depth, score);
+            if self.stopped() {
+                break;
+            }
+        }
+    }
+
+    // Searches with the helper threads, if any
+    fn run(&mut self, board: &ChessBoard, limits: &SearchLimits) -> SearchResult {
+        self.limits = *limits;
+        self.helpers_stop.store(false, Ordering::Relaxed);
+        if self.threads == 1 {
+            return self.iterative_deepening(board);
+        }
+        thread::scope(|scope| {
+            for id in 1..self.threads {
+                let mut helper = self.clone();
+                helper.info_callback = None;
+                helper.limits = SearchLimits::depth(HELPER_MAX_DEPTH);
+                thread::Builder::new()
+                    .name(format!("negamax-helper-{}", id))
+                    .stack_size(HELPER_STACK_SIZE)
+                    .spawn_scoped(scope, move || helper.helper_search(board, id))
+                    .expect("Could not spawn a search thread");
+            }
+            let result = self.iterative_deepening(board);
+            self.helpers_stop.store(true, Ordering::Relaxed);
+            result
+        })
+    }
+
+    fn iterative_deepening(&mut self, board: &ChessBoard) -> SearchResult {
+        let mut board = board.clone();
+        self.evaluator.prepare(&mut board);
+        self.start = Instant::now();
+        self.nodes = 0;
+        self.seldepth = 0;
+        self.limit_reached = false;
+        let limits = self.limits;
+        // Weaker levels pick among several lines found at a shallow depth
+        let weakened = limits.skill < SearchLimits::MAX_SKILL;
+        let (multipv, max_depth) = if weakened {
+            (limits.multipv.max(SKILL_LINES), limits.depth.min(1 + limits.skill))
+        } else {
+            (limits.multipv.max(1), limits.depth)
+        };
+        // A root with every move excluded has no line left to search
+        let multipv = multipv.min(board.generate_moves(board.state.team_to_play).len());
+
+        // Best lines of the last completed iteration, as (score, principal variation)
+        let mut lines: Vec<(i32, Vec<CompletedMove>)> = Vec::new();
+        let mut completed_depth = 0;
+        'deepening: for depth in 1..=max_depth.max(1) {
+            let mut iteration = Vec::new();
+            self.excluded.clear();
+            for index in 0..multipv {
+                let previous_score = lines.get(index).map_or(0, |line| line.0);
+                let score = self.aspiration_search(&mut board, depth, previous_score);
+                if self.stopped() {
+                    // An interrupted search only fills the principal variation with fully searched moves
+                    if index == 0 && !self.pv[0].is_empty() {
+                        match lines.first_mut() {
+                            Some(line) => line.1 = self.pv[0].clone(),
+                            None => lines.push((score, self.pv[0].clone()))
+                        }
+                    }
+                    break 'deepening;
+                }
+                // Nothing left to search at the root
+                let Some(first) = self.pv[0].first() else { break };
+                self.excluded.push((first.origin, first.target, first.get_promotion()));
+                iteration.push((score, self.pv[0].clone()));
+            }
+            iteration.sort_by_key(|line| -line.0);
+            for (index, (score, pv)) in iteration.iter().enumerate().take(limits.multipv) {
+                self.report(depth, index + 1, *score, pv);
+            }
+            lines = iteration;
+            completed_depth = depth;
+        }
+        self.excluded.clear();
+
+        if completed_depth == 0 {
+            // Stopped inside the first iteration: the move is the best found so far, with no score to back it
+            let pv = lines.pop().map(|(_, pv)| pv).unwrap_or_else(|| {
+                board.generate_moves(board.state.team_to_play).into_iter().take(1).collect()
+            });
+            return SearchResult {
+                best_move: pv[0].clone(),
+                score: None,
+                depth: 0,
+                nodes: self.nodes,
+                time_ms: self.start.elapsed().as_millis() as u64,
+                pv,
+                lines: Vec::new()
+            };
+        }
+        let chosen = if weakened { pick_weakened(&lines, limits.skill) } else { 0 };
+        let (score, pv) = &lines[chosen];
+        SearchResult {
+            best_move: pv[0].clone(),
+            score: Some(Score::from_internal(*score)),
+            depth: completed_depth,
+            nodes: self.nodes,
+            time_ms: self.start.elapsed().as_millis() as u64,
+            pv: pv.clone(),
+            lines: lines.iter().take(limits.multipv).map(|(score, pv)| SearchLine {
+                score: Score::from_internal(*score),
+                pv: pv.clone()
+            }).collect()
+        }
+    }
+
+    // Raises limit_reached once the node budget or the move time is spent, the clock being read every 1024 nodes
+    fn check_limits(&mut self) {
+        if self.limits.nodes.is_some_and(|nodes| self.nodes >= nodes) {
+            self.limit_reached = true;
+        }
+        if self.nodes.is_multiple_of(1024) && self.limits.movetime.is_some_and(|movetime| self.start.elapsed() >= movetime) {
+            self.limit_reached = true;
+        }
+    }
+
+    fn aspiration_search(&mut self, board: &mut ChessBoard, depth: u8, previous_score: i32) -> i32 {
+        if depth < ASPIRATION_MIN_DEPTH || previous_score.abs() >= MATE_SCORE - MAX_PLY as i32 {
+            return self.negamax(board, depth, 0, -INFINITY, INFINITY);
+        }
+        let mut delta = ASPIRATION_WINDOW;
+        let mut alpha = previous_score - delta;
+        let mut beta = previous_score + delta;
+        loop {
+            let score = self.negamax(board, depth, 0, alpha, beta);
+            if self.stopped() {
+                return score;
+            }
+            if score <= alpha {
+                alpha = (score - delta).max(-INFINITY);
+            } else if score >= beta {
+                beta = (score + delta).min(INFINITY);
+            } else {
+                return score;
+            }
+            delta *= 2;
+        }
+    }
+
+    fn evaluate(&mut self, board: &ChessBoard, team: Team) -> i32 {
+        self.evaluator.evaluate(board, team) + evaluate_variant(board, team)
+    }
+
+    fn negamax(&mut self, board: &mut ChessBoard, depth: u8, ply: usize, alpha: i32, beta: i32) -> i32 {
+        self.nodes += 1;
+        self.check_limits();
+        self.pv[ply].clear();
+        if self.stopped() {
+            return 0;
+        }
+        let team = board.state.team_to_play;
+        if let Some(score) = variant_win_score(board, ply) {
             return score;
         }
+        if ply >= MAX_PLY - 1 {
+            return self.evaluate(board, team);
+        }
+        if depth == 0 {
+            return self.quiescence(board, ply, alpha, beta);
+        }
+        self.seldepth = self.seldepth.max(ply as u8);
+
+        let zobrist = self.zobrist.hash(board);
+        let mut tt_move = None;
+        if let Some(entry) = self.transposition_table.probe(zobrist) {
+            tt_move = entry.best_move();
+            if ply > 0 && entry.depth >= depth {
+                let score = score_from_tt(entry.score, ply);
+                match entry.bound {
+                    Bound::Exact => return score,
+                    Bound::Lower if score >= beta => return score,
+                    Bound::Upper if score <= alpha => return score,
+                    _ => {}
+                }
+            }
+        }
 
+        #[cfg(feature = "syzygy")]
+        if ply > 0 {
+            if let Some(score) = self.tablebase_score(board, ply) {
+                self.transposition_table.store(zobrist, depth, score_to_tt(score, ply), Bound::Exact, None);
+                return score;
+            }
+        }
+
+        let mut moves = board.generate_moves(team);
+        if moves.is_empty() {
+            return if board.is_in_check(team) { -MATE_SCORE + ply as i32 } else { 0 };
+        }
+        if ply == 0 && !self.excluded.is_empty() {
+            // Multi-PV: moves already heading a better line are left out
+            moves.retain(|mv| !self.excluded.contains(&(mv.origin, mv.target, mv.get_promotion())));
+        }
+        order_moves(board, &mut moves, tt_move);
+
+        let original_alpha = alpha;
         let mut alpha = alpha;
-        let mut beta = beta;
-
-        if maximizing {
-            let mut best_score = i32::MIN;
-            for mv in board.generate_moves(team) {
-                if let Some(mov) = board.play_move(mv.origin, mv.target, true) {
-                    let score = -self.minimax(board, depth - 1, get_opposite_team(team), alpha, beta);
-                    board.undo_move(&mov);
-                    best_score = best_score.max(score);
-                    alpha = alpha.max(best_score);
-                    if beta <= alpha {
-                        break; // Beta cut-off
+        let mut best_score = -INFINITY;
+        let mut best_move = None;
+        for mv in moves {
+            let state = board.state;
+            if let Some(mov) = board.play_move(mv.origin, mv.target, mv.get_promotion(), true) {
+                self.evaluator.on_make(board, &mov);
+                let score = -self.negamax(board, depth - 1, ply + 1, -beta, -alpha);
+                board.undo_move(&mov);
+                board.state = state;
+                self.evaluator.on_unmake(board, &mov);
+                if self.stopped() {
+                    return 0;
+                }
+                if score > best_score {
+                    best_score = score;
+                    best_move = Some((mov.origin, mov.target));
+                    if score > alpha {
+                        alpha = score;
+                        let mut line = vec![mov];
+                        line.extend_from_slice(&self.pv[ply + 1]);
+                        self.pv[ply] = line;
                     }
                 }
+                if alpha >= beta {
+                    break; // Beta cut-off
+                }
             }
-            self.transposition_table.insert(zobrist, best_score);
-            return best_score;
+        }
+
+        let bound = if best_score >= beta {
+            Bound::Lower
+        } else if best_score > original_alpha {
+            Bound::Exact
         } else {
-            let mut best_score = i32::MAX;
-            for mv in board.generate_moves(team) {
-                if let Some(mov) = board.play_move(mv.origin, mv.target, true) {
-                    let score = self.minimax(board, depth - 1, get_opposite_team(team), alpha, beta);
-                    board.undo_move(&mov);
-                    best_score = best_score.min(score);
-                    beta = beta.min(best_score);
-                    if beta <= alpha {
-                        break; // Alpha cut-off
-                    }
+            Bound::Upper
+        };
+        // A root searched without some of its moves does not have its true score
+        if ply > 0 || self.excluded.is_empty() {
+            self.transposition_table.store(zobrist, depth, score_to_tt(best_score, ply), bound, best_move);
+        }
+        best_score
+    }
+
+    // Resolves captures until the position is quiet, so the static evaluation is not taken mid-exchange
+    fn quiescence(&mut self, board: &mut ChessBoard, ply: usize, alpha: i32, beta: i32) -> i32 {
+        self.nodes += 1;
+        self.check_limits();
+        if self.stopped() {
+            return 0;
+        }
+        self.seldepth = self.seldepth.max(ply as u8);
+        let team = board.state.team_to_play;
+        if let Some(score) = variant_win_score(board, ply) {
+            return score;
+        }
+        let stand_pat = self.evaluate(board, team);
+        if stand_pat >= beta || ply >= MAX_PLY - 1 {
+            return stand_pat;
+        }
+        let mut alpha = alpha.max(stand_pat);
+
+        let mut captures: Vec<CompletedMove> = board.generate_moves(team)
+            .into_iter()
+            .filter(|mv| mv.is_capture() || mv.get_promotion() == Some(Pieces::QUEEN))
+            .collect();
+        order_moves(board, &mut captures, None);
+        for mv in captures {
+            let state = board.state;
+            if let Some(mov) = board.play_move(mv.origin, mv.target, mv.get_promotion(), true) {
+                self.evaluator.on_make(board, &mov);
+                let score = -self.quiescence(board, ply + 1, -beta, -alpha);
+                board.undo_move(&mov);
+                board.state = state;
+                self.evaluator.on_unmake(board, &mov);
+                if score >= beta {
+                    return score;
                 }
+                alpha = alpha.max(score);
             }
-            self.transposition_table.insert(zobrist, best_score);
-            return best_score;
+        }
+        alpha
+    }
+
+    fn report(&self, depth: u8, multipv: usize, score: i32, pv: &[CompletedMove]) {
+        if let Some(callback) = &self.info_callback {
+            let elapsed_ms = self.start.elapsed().as_millis() as u64;
+            let info = SearchInfo {
+                depth,
+                seldepth: self.seldepth,
+                nodes: self.nodes,
+                nps: self.nodes * 1000 / elapsed_ms.max(1),
+                hashfull: self.transposition_table.hashfull(),
+                multipv,
+                score: Score::from_internal(score),
+                pv: pv.to_vec(),
+                elapsed_ms
+            };
+            callback(&info);
         }
     }
 }
 
-impl ChessEngine for MinimaxEngine {
-    fn new() -> Self {
-        MinimaxEngine {
-            zobrist: ZobristHash::new(),
-            transposition_table: HashMap::new(),
+// Scores a game the variant's rules already decided like a mate found at this ply
+fn variant_win_score(board: &ChessBoard, ply: usize) -> Option<i32> {
+    let winner = board.state.variant.winner(board)?;
+    Some(if winner == board.state.team_to_play { MATE_SCORE - ply as i32 } else { -MATE_SCORE + ply as i32 })
+}
+
+// Skill levels below the maximum add a random push to each line, larger for weaker levels and for lines
+// further behind, and play the line with the best pushed score
+fn pick_weakened(lines: &[(i32, Vec<CompletedMove>)], skill: u8) -> usize {
+    let weakness = 120 - 2 * skill as i32;
+    let top = lines[0].0;
+    let delta = (top - lines[lines.len() - 1].0).min(PIECE_VALUES[Pieces::PAWN]);
+    let mut rng = rand::thread_rng();
+    let mut best = 0;
+    let mut best_score = -INFINITY;
+    for (index, (score, _)) in lines.iter().enumerate() {
+        let push = (weakness * (top - score) + delta * rng.gen_range(0..weakness)) / 128;
+        if score + push >= best_score {
+            best_score = score + push;
+            best = index;
         }
     }
+    best
+}
 
-    fn get_best_move(&mut self, board: &ChessBoard, depth: u8) -> CompletedMove {
-        let mut board_clone = board.clone();
-        let mut best_move = None;
-        let mut best_score = i32::MIN;
+// Transposition table move first, then captures and promotions by most valuable gain / least valuable attacker
+fn order_moves(board: &ChessBoard, moves: &mut [CompletedMove], tt_move: Option<(u8, u8)>) {
+    moves.sort_by_cached_key(|mv| {
+        if tt_move == Some((mv.origin, mv.target)) {
+            return i32::MIN;
+        }
+        if !mv.is_capture() && !mv.is_promotion() {
+            return 0;
+        }
+        let origin = (mv.origin / 8 * 16 + mv.origin % 8) as usize;
+        let attacker = board.mailbox.get_piece_at(origin).map_or(0, |piece| get_piece_value(piece.get_piece()));
+        let victim = if mv.is_capture() { get_piece_value(mv.get_capture()) } else { 0 };
+        let promotion = mv.get_promotion().map_or(0, get_piece_value);
+        -((victim + promotion) * 10 - attacker.min(1000))
+    });
+}
 
-        for mv in board.generate_moves(board.state.team_to_play) {
-            if let Some(mov) = board_clone.play_move(mv.origin, mv.target, true) {
-                let score = -self.minimax(&mut board_clone, depth - 1, get_opposite_team(board.state.team_to_play), i32::MIN, i32::MAX);
-                board_clone.undo_move(&mov);
-                println!("Move: {:?}, Score: {}", mv, score); // Debug log
-                if score > best_score {
-                    best_score = score;
-                    best_move = Some(mov);
-                }
+impl<E: Evaluator + Default> ChessEngine for MinimaxEngine<E> {
+    fn new() -> Self {
+        MinimaxEngine::with_evaluator(E::default())
+    }
+
+    fn search(&mut self, board: &ChessBoard, limits: &SearchLimits) -> Option<SearchResult> {
+        if board.generate_moves(board.state.team_to_play).is_empty() {
+            return None;
+        }
+        // Book and tablebase moves are only trusted at full strength
+        if limits.skill == SearchLimits::MAX_SKILL && limits.multipv <= 1 {
+            if let Some(mv) = self.book_move(board) {
+                return Some(SearchResult::unsearched(mv));
             }
+            #[cfg(feature = "syzygy")]
+            if let Some(mv) = self.tablebase_move(board) {
+                return Some(SearchResult::unsearched(mv));
+            }
+        }
+        Some(self.run(board, limits))
+    }
+
+    fn analyse(&mut self, board: &ChessBoard, limits: &SearchLimits) -> Analysis {
+        if board.generate_moves(board.state.team_to_play).is_empty() {
+            return Analysis { depth: 0, nodes: 0, time_ms: 0, lines: Vec::new() };
         }
-        println!("Best move: {:?}, Best score: {}", best_move, best_score); // Debug log
-        best_move.expect("No moves found")
+        let result = self.run(board, limits);
+        Analysis { depth: result.depth, nodes: result.nodes, time_ms: result.time_ms, lines: result.lines }
+    }
+
+    fn stop_handle(&self) -> StopHandle {
+        self.stop.clone()
+    }
+
+    fn set_stop_handle(&mut self, handle: StopHandle) {
+        self.stop = handle;
     }
 }
@@ -0,0 +1,47 @@
+use crate::board::{BitBoard, get_opposite_team, Piece, Pieces, Team};
+use crate::board::board::ChessBoard;
+use crate::engine::pawn_structure::pawn_attacks;
+use crate::math::iterate_bits;
+use crate::math::knights::mask_knight_moves;
+use crate::math::sliding::{properly_mask_bishop_moves, properly_mask_queen_moves, properly_mask_rook_moves};
+
+// (middlegame, endgame) score per reachable square, indexed by piece
+pub const MOBILITY_WEIGHTS: [(i32, i32); 6] = [(0, 0), (5, 5), (4, 4), (2, 4), (1, 2), (0, 0)];
+// Typical number of squares for each piece, so an average piece scores zero
+pub const MOBILITY_BASELINE: [i32; 6] = [0, 7, 4, 7, 14, 0];
+
+pub fn piece_attacks(piece: Piece, bit: u64, occupied: &BitBoard) -> BitBoard {
+    match piece {
+        Pieces::KNIGHT => mask_knight_moves(bit),
+        Pieces::BISHOP => properly_mask_bishop_moves(bit, occupied),
+        Pieces::ROOK => properly_mask_rook_moves(bit, occupied),
+        Pieces::QUEEN => properly_mask_queen_moves(bit, occupied),
+        _ => BitBoard(0)
+    }
+}
+
+// Mobility from the reference team's point of view, as (middlegame, endgame)
+pub fn evaluate_mobility(board: &ChessBoard, reference: Team) -> (i32, i32) {
+    let (own_middlegame, own_endgame) = team_mobility(board, reference);
+    let (enemy_middlegame, enemy_endgame) = team_mobility(board, get_opposite_team(reference));
+    (own_middlegame - enemy_middlegame, own_endgame - enemy_endgame)
+}
+
+fn team_mobility(board: &ChessBoard, team: Team) -> (i32, i32) {
+    let occupied = !board.bits.empty_squares();
+    let enemy_pawns = board.bits.get_pieces(get_opposite_team(team), Pieces::PAWN).0;
+    // Squares guarded by enemy pawns or holding our own pieces are not counted
+    let available = !(board.bits.get_team_pieces(team).0 | pawn_attacks(get_opposite_team(team), enemy_pawns));
+
+    let mut middlegame = 0;
+    let mut endgame = 0;
+    for piece in [Pieces::KNIGHT, Pieces::BISHOP, Pieces::ROOK, Pieces::QUEEN] {
+        let (middlegame_weight, endgame_weight) = MOBILITY_WEIGHTS[piece];
+        iterate_bits(board.bits.get_pieces(team, piece).0, |bit| {
+            let squares = (piece_attacks(piece, bit, &occupied).0 & available).count_ones() as i32;
+            middlegame += middlegame_weight * (squares - MOBILITY_BASELINE[piece]);
+            endgame += endgame_weight * (squares - MOBILITY_BASELINE[piece]);
+        });
+    }
+    (middlegame, endgame)
+}
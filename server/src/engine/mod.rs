@@ -1,21 +1,85 @@
-use crate::board::{CompletedMove, Piece, Pieces, PossibleMove};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use crate::board::{CompletedMove, Piece, Pieces};
 use crate::board::board::ChessBoard;
+use crate::engine::info::{Analysis, SearchResult};
 
 pub mod minimax;
+pub mod info;
+pub mod transposition;
+pub mod pst;
+pub mod pawn_structure;
+pub mod mobility;
+pub mod king_safety;
+pub mod evaluator;
+pub mod nnue;
+pub mod bitbase;
+pub mod endgame;
+pub mod variants;
+pub mod time;
+#[cfg(feature = "syzygy")]
+pub mod syzygy;
+
+pub const MATE_SCORE: i32 = 1_000_000;
+pub const INFINITY: i32 = 2_000_000;
+pub const MAX_PLY: usize = 128;
 
 pub trait ChessEngine {
     fn new() -> Self;
-    fn get_best_move(&mut self, board: &ChessBoard, depth: u8) -> CompletedMove;
+    // None when the side to play has no legal move
+    fn search(&mut self, board: &ChessBoard, limits: &SearchLimits) -> Option<SearchResult>;
+    fn get_best_move(&mut self, board: &ChessBoard, depth: u8) -> Option<CompletedMove> {
+        self.search(board, &SearchLimits::depth(depth)).map(|result| result.best_move)
+    }
+    // Searches the best lines as many as the multi-PV count asks for, never answering from the book or tablebases
+    fn analyse(&mut self, board: &ChessBoard, limits: &SearchLimits) -> Analysis;
+    // Searches poll this handle and return the best move found so far once it is stopped
+    fn stop_handle(&self) -> StopHandle;
+    fn set_stop_handle(&mut self, handle: StopHandle);
+}
+
+// When a search ends: the first limit reached stops it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SearchLimits {
+    pub depth: u8,
+    pub movetime: Option<Duration>,
+    pub nodes: Option<u64>,
+    // Number of best lines searched and returned
+    pub multipv: usize,
+    // From 0 to MAX_SKILL, lower levels play worse moves on purpose
+    pub skill: u8
+}
+
+impl SearchLimits {
+    pub const MAX_SKILL: u8 = 20;
+
+    pub fn depth(depth: u8) -> Self {
+        SearchLimits { depth, movetime: None, nodes: None, multipv: 1, skill: Self::MAX_SKILL }
+    }
 }
 
+// Shared flag used to interrupt a search from another thread
+#[derive(Debug, Clone, Default)]
+pub struct StopHandle(Arc<AtomicBool>);
+
+impl StopHandle {
+    pub fn stop(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+// Indexed by piece, the king's value only matters for move ordering since both sides always have one
+pub const PIECE_VALUES: [i32; 6] = [100, 330, 320, 500, 900, 10000];
+
 pub fn get_piece_value(piece: Piece) -> i32 {
     match piece {
-        Pieces::PAWN => 100,
-        Pieces::BISHOP => 330,
-        Pieces::KNIGHT => 320,
-        Pieces::ROOK => 500,
-        Pieces::QUEEN => 900,
-        Pieces::KING => 10000,
+        Pieces::PAWN..=Pieces::KING => PIECE_VALUES[piece],
         _ => panic!("Invalid piece")
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,197 @@
+use std::fmt::Debug;
+use std::fs::File;
+use std::hash::{Hash, Hasher};
+use std::io::{self, Read};
+use std::sync::Arc;
+
+use crate::board::{BitPosition, get_opposite_team, Piece, Pieces, Team, Teams};
+use crate::board::board::ChessBoard;
+use crate::engine::endgame::evaluate_endgame;
+use crate::engine::evaluator::Evaluator;
+use crate::math::iterate_bits;
+
+// One input per (relative side, piece, square), seen from each side's perspective
+pub const INPUTS: usize = 768;
+// Quantisation of the accumulator and the output weights, and the centipawn scale of the output
+const QA: i32 = 255;
+const QB: i32 = 64;
+const SCALE: i32 = 400;
+const MAGIC: &[u8; 4] = b"CNNU";
+
+// A (768 -> N)x2 -> 1 perspective network with clipped ReLU activation.
+// Weights file layout, all little endian: magic "CNNU", u32 hidden size N, i16 feature weights [768][N],
+// i16 feature biases [N], i16 output weights [2N] (side to move half first), i32 output bias.
+pub struct Network {
+    hidden: usize,
+    feature_weights: Vec<i16>,
+    feature_biases: Vec<i16>,
+    output_weights: Vec<i16>,
+    output_bias: i32
+}
+
+impl Network {
+    pub fn load(path: &str) -> io::Result<Network> {
+        let mut bytes = Vec::new();
+        File::open(path)?.read_to_end(&mut bytes)?;
+        Self::from_bytes(&bytes)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<Network> {
+        let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+        if bytes.len() < 8 || &bytes[..4] != MAGIC {
+            return Err(invalid("not a caissa network file"));
+        }
+        let hidden = u32::from_le_bytes(bytes[4..8].try_into().unwrap()) as usize;
+        let weights_count = INPUTS * hidden + hidden + 2 * hidden;
+        if hidden == 0 || bytes.len() != 8 + weights_count * 2 + 4 {
+            return Err(invalid("network file has an unexpected size"));
+        }
+        let mut values = bytes[8..8 + weights_count * 2]
+            .chunks_exact(2)
+            .map(|pair| i16::from_le_bytes([pair[0], pair[1]]));
+        let feature_weights = values.by_ref().take(INPUTS * hidden).collect();
+        let feature_biases = values.by_ref().take(hidden).collect();
+        let output_weights = values.take(2 * hidden).collect();
+        let output_bias = i32::from_le_bytes(bytes[bytes.len() - 4..].try_into().unwrap());
+        Ok(Network { hidden, feature_weights, feature_biases, output_weights, output_bias })
+    }
+
+    pub fn hidden_size(&self) -> usize {
+        self.hidden
+    }
+
+    fn feature_row(&self, index: usize) -> &[i16] {
+        &self.feature_weights[index * self.hidden..(index + 1) * self.hidden]
+    }
+}
+
+pub fn feature_index(perspective: Team, team: Team, piece: Piece, square: usize) -> usize {
+    let side = (team != perspective) as usize;
+    let square = if perspective == Teams::WHITE { square } else { square ^ 56 };
+    side * 384 + piece * 64 + square
+}
+
+// Hidden layer values for both perspectives, updated as pieces are placed and lifted on the board
+#[derive(Clone)]
+pub struct NnueAccumulator {
+    network: Arc<Network>,
+    values: [Vec<i16>; 2]
+}
+
+impl NnueAccumulator {
+    pub fn new(network: Arc<Network>, bits: &BitPosition) -> Self {
+        let mut accumulator = NnueAccumulator {
+            values: [network.feature_biases.clone(), network.feature_biases.clone()],
+            network
+        };
+        for team in [Teams::WHITE, Teams::BLACK] {
+            for piece in Pieces::iter() {
+                iterate_bits(bits.get_pieces(team, piece).0, |bit| {
+                    accumulator.add(team, piece, bit.trailing_zeros() as usize);
+                });
+            }
+        }
+        accumulator
+    }
+
+    pub fn add(&mut self, team: Team, piece: Piece, square: usize) {
+        for perspective in [Teams::WHITE, Teams::BLACK] {
+            let row = self.network.feature_row(feature_index(perspective, team, piece, square));
+            for (value, weight) in self.values[perspective].iter_mut().zip(row) {
+                *value = value.wrapping_add(*weight);
+            }
+        }
+    }
+
+    pub fn remove(&mut self, team: Team, piece: Piece, square: usize) {
+        for perspective in [Teams::WHITE, Teams::BLACK] {
+            let row = self.network.feature_row(feature_index(perspective, team, piece, square));
+            for (value, weight) in self.values[perspective].iter_mut().zip(row) {
+                *value = value.wrapping_sub(*weight);
+            }
+        }
+    }
+
+    pub fn evaluate(&self, reference: Team) -> i32 {
+        let hidden = self.network.hidden;
+        let weights = &self.network.output_weights;
+        let sum = crelu_dot(&self.values[reference], &weights[..hidden])
+            + crelu_dot(&self.values[get_opposite_team(reference)], &weights[hidden..]);
+        (sum + self.network.output_bias) * SCALE / (QA * QB)
+    }
+}
+
+impl Debug for NnueAccumulator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("NnueAccumulator").field("hidden", &self.network.hidden).finish()
+    }
+}
+
+impl Hash for NnueAccumulator {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.values.hash(state);
+    }
+}
+
+fn crelu_dot(values: &[i16], weights: &[i16]) -> i32 {
+    #[cfg(target_arch = "x86_64")]
+    if is_x86_feature_detected!("avx2") {
+        // Safety: the required CPU feature was just detected
+        return unsafe { crelu_dot_avx2(values, weights) };
+    }
+    crelu_dot_scalar(values, weights)
+}
+
+fn crelu_dot_scalar(values: &[i16], weights: &[i16]) -> i32 {
+    values.iter()
+        .zip(weights)
+        .map(|(value, weight)| (*value as i32).clamp(0, QA) * *weight as i32)
+        .sum()
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+unsafe fn crelu_dot_avx2(values: &[i16], weights: &[i16]) -> i32 {
+    use std::arch::x86_64::*;
+
+    let zero = _mm256_setzero_si256();
+    let max = _mm256_set1_epi16(QA as i16);
+    let mut sum = _mm256_setzero_si256();
+    let chunks = values.len().min(weights.len()) / 16;
+    for chunk in 0..chunks {
+        let value = _mm256_loadu_si256(values.as_ptr().add(chunk * 16) as *const __m256i);
+        let weight = _mm256_loadu_si256(weights.as_ptr().add(chunk * 16) as *const __m256i);
+        let clipped = _mm256_min_epi16(_mm256_max_epi16(value, zero), max);
+        sum = _mm256_add_epi32(sum, _mm256_madd_epi16(clipped, weight));
+    }
+    let mut lanes = [0i32; 8];
+    _mm256_storeu_si256(lanes.as_mut_ptr() as *mut __m256i, sum);
+    lanes.iter().sum::<i32>() + crelu_dot_scalar(&values[chunks * 16..], &weights[chunks * 16..])
+}
+
+#[derive(Clone)]
+pub struct NnueEvaluator {
+    network: Arc<Network>
+}
+
+impl NnueEvaluator {
+    pub fn new(network: Arc<Network>) -> Self {
+        NnueEvaluator { network }
+    }
+}
+
+impl Evaluator for NnueEvaluator {
+    fn evaluate(&mut self, board: &ChessBoard, reference: Team) -> i32 {
+        if let Some(score) = evaluate_endgame(board, reference) {
+            return score;
+        }
+        match &board.nnue {
+            Some(accumulator) => accumulator.evaluate(reference),
+            None => NnueAccumulator::new(self.network.clone(), &board.bits).evaluate(reference)
+        }
+    }
+
+    fn prepare(&mut self, board: &mut ChessBoard) {
+        board.nnue = Some(NnueAccumulator::new(self.network.clone(), &board.bits));
+    }
+}
@@ -0,0 +1,214 @@
+use crate::board::{BitBoard, get_opposite_team, Pieces, Team, Teams};
+use crate::board::board::ChessBoard;
+use crate::hash::ZobristHash;
+use crate::math::iterate_bits;
+use crate::math::pawns::mask_all_pawn_capture_moves;
+
+const FILE_A: u64 = 0x0101010101010101;
+
+// (middlegame, endgame) penalties and bonuses, indexed by relative rank where relevant
+pub const DOUBLED_PENALTY: (i32, i32) = (-10, -20);
+pub const ISOLATED_PENALTY: (i32, i32) = (-10, -15);
+pub const BACKWARD_PENALTY: (i32, i32) = (-8, -10);
+pub const CONNECTED_BONUS: [i32; 8] = [0, 3, 5, 8, 14, 24, 40, 0];
+pub const PASSED_BONUS_MIDDLEGAME: [i32; 8] = [0, 5, 10, 15, 25, 45, 70, 0];
+pub const PASSED_BONUS_ENDGAME: [i32; 8] = [0, 10, 20, 35, 60, 100, 150, 0];
+// Extra endgame bonus when nothing stands between a passed pawn and its promotion square
+pub const FREE_PATH_BONUS: [i32; 8] = [0, 0, 5, 10, 20, 35, 60, 0];
+
+pub fn file_mask(file: usize) -> u64 {
+    FILE_A << file
+}
+
+pub fn adjacent_files_mask(file: usize) -> u64 {
+    let mut mask = 0;
+    if file > 0 { mask |= file_mask(file - 1); }
+    if file < 7 { mask |= file_mask(file + 1); }
+    mask
+}
+
+// Every square on the ranks strictly in front of `rank` from the team's point of view
+pub fn forward_ranks_mask(team: Team, rank: usize) -> u64 {
+    if team == Teams::WHITE {
+        if rank >= 7 { 0 } else { !0u64 << (8 * (rank + 1)) }
+    } else {
+        (1u64 << (8 * rank)) - 1
+    }
+}
+
+pub fn relative_rank(team: Team, square: usize) -> usize {
+    if team == Teams::WHITE { square / 8 } else { 7 - square / 8 }
+}
+
+pub fn pawn_attacks(team: Team, pawns: u64) -> u64 {
+    mask_all_pawn_capture_moves(&BitBoard(pawns), &BitBoard(!0), &None, team).0
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct PawnEntry {
+    key: u64,
+    // White minus black
+    middlegame: i32,
+    endgame: i32,
+    passed: [u64; 2]
+}
+
+impl PawnEntry {
+    pub fn compute(key: u64, white_pawns: u64, black_pawns: u64) -> PawnEntry {
+        let mut entry = PawnEntry { key, middlegame: 0, endgame: 0, passed: [0; 2] };
+        let pawns = [white_pawns, black_pawns];
+        for team in [Teams::WHITE, Teams::BLACK] {
+            let sign = if team == Teams::WHITE { 1 } else { -1 };
+            let trace = trace_team_pawns(team, pawns[team], pawns[get_opposite_team(team)]);
+            let (middlegame, endgame) = trace.score();
+            entry.middlegame += sign * middlegame;
+            entry.endgame += sign * endgame;
+            entry.passed[team] = trace.passed_pawns;
+        }
+        entry
+    }
+}
+
+// How often each pawn term applies to one side, indexed by relative rank where the bonus depends on it.
+// Keeping the counts apart from the weights lets the tuner rebuild the score for any set of weights.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PawnTrace {
+    pub doubled: i32,
+    pub isolated: i32,
+    pub backward: i32,
+    pub connected: [i32; 8],
+    pub passed: [i32; 8],
+    pub passed_pawns: u64
+}
+
+impl PawnTrace {
+    pub fn score(&self) -> (i32, i32) {
+        let mut middlegame = self.doubled * DOUBLED_PENALTY.0 + self.isolated * ISOLATED_PENALTY.0 + self.backward * BACKWARD_PENALTY.0;
+        let mut endgame = self.doubled * DOUBLED_PENALTY.1 + self.isolated * ISOLATED_PENALTY.1 + self.backward * BACKWARD_PENALTY.1;
+        for rank in 0..8 {
+            middlegame += self.connected[rank] * CONNECTED_BONUS[rank] + self.passed[rank] * PASSED_BONUS_MIDDLEGAME[rank];
+            endgame += self.connected[rank] * CONNECTED_BONUS[rank] + self.passed[rank] * PASSED_BONUS_ENDGAME[rank];
+        }
+        (middlegame, endgame)
+    }
+}
+
+pub fn trace_team_pawns(team: Team, own: u64, enemy: u64) -> PawnTrace {
+    let mut trace = PawnTrace::default();
+    let own_attacks = pawn_attacks(team, own);
+    let enemy_attacks = pawn_attacks(get_opposite_team(team), enemy);
+
+    iterate_bits(own, |bit| {
+        let square = bit.trailing_zeros() as usize;
+        let (file, rank) = (square % 8, square / 8);
+        let relative = relative_rank(team, square);
+        let forward = forward_ranks_mask(team, rank);
+        let adjacent = adjacent_files_mask(file);
+
+        if own & file_mask(file) & forward != 0 {
+            trace.doubled += 1;
+        }
+
+        let isolated = own & adjacent == 0;
+        if isolated {
+            trace.isolated += 1;
+        } else {
+            // No friendly pawn beside or behind to support the advance, and the stop square is covered
+            let stop_square = if team == Teams::WHITE { bit << 8 } else { bit >> 8 };
+            if own & adjacent & !forward == 0 && enemy_attacks & stop_square != 0 {
+                trace.backward += 1;
+            }
+        }
+
+        let phalanx = own & adjacent & (0xFF << (8 * rank)) != 0;
+        let supported = own_attacks & bit != 0;
+        if phalanx || supported {
+            trace.connected[relative] += 1;
+        }
+
+        if enemy & (file_mask(file) | adjacent) & forward == 0 {
+            trace.passed_pawns |= bit;
+            trace.passed[relative] += 1;
+        }
+    });
+    trace
+}
+
+#[derive(Clone)]
+pub struct PawnHashTable {
+    zobrist: ZobristHash,
+    entries: Vec<Option<PawnEntry>>,
+    mask: usize
+}
+
+impl PawnHashTable {
+    pub const DEFAULT_ENTRIES: usize = 1 << 14;
+
+    pub fn new(entries: usize) -> Self {
+        let entries = entries.next_power_of_two();
+        PawnHashTable {
+            zobrist: ZobristHash::new(),
+            entries: vec![None; entries],
+            mask: entries - 1
+        }
+    }
+
+    pub fn probe(&mut self, board: &ChessBoard) -> PawnEntry {
+        let key = self.zobrist.pawn_hash(board);
+        let slot = &mut self.entries[key as usize & self.mask];
+        match slot {
+            Some(entry) if entry.key == key => *entry,
+            _ => {
+                let entry = PawnEntry::compute(
+                    key,
+                    board.bits.get_pieces(Teams::WHITE, Pieces::PAWN).0,
+                    board.bits.get_pieces(Teams::BLACK, Pieces::PAWN).0
+                );
+                *slot = Some(entry);
+                entry
+            }
+        }
+    }
+}
+
+impl Default for PawnHashTable {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_ENTRIES)
+    }
+}
+
+// Passed pawns with nothing in front of them, counted by relative rank
+pub fn trace_free_paths(board: &ChessBoard, team: Team, passed_pawns: u64) -> [i32; 8] {
+    let occupied = !board.bits.empty_squares().0;
+    let mut free_paths = [0; 8];
+    iterate_bits(passed_pawns, |bit| {
+        let square = bit.trailing_zeros() as usize;
+        let path = file_mask(square % 8) & forward_ranks_mask(team, square / 8);
+        if occupied & path == 0 {
+            free_paths[relative_rank(team, square)] += 1;
+        }
+    });
+    free_paths
+}
+
+// Pawn structure score from the reference team's point of view, as (middlegame, endgame)
+pub fn evaluate_pawn_structure(board: &ChessBoard, reference: Team, pawn_table: &mut PawnHashTable) -> (i32, i32) {
+    let entry = pawn_table.probe(board);
+    let middlegame = entry.middlegame;
+    let mut endgame = entry.endgame;
+
+    // Free paths depend on every piece, so they are added on top of the cached pawn-only score
+    for team in [Teams::WHITE, Teams::BLACK] {
+        let sign = if team == Teams::WHITE { 1 } else { -1 };
+        let free_paths = trace_free_paths(board, team, entry.passed[team]);
+        for rank in 0..8 {
+            endgame += sign * free_paths[rank] * FREE_PATH_BONUS[rank];
+        }
+    }
+
+    if reference == Teams::WHITE {
+        (middlegame, endgame)
+    } else {
+        (-middlegame, -endgame)
+    }
+}
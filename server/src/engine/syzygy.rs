@@ -0,0 +1,951 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::ops::Neg;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+
+use lazy_static::lazy_static;
+
+use crate::board::{CompletedMove, get_opposite_team, Pieces, Teams};
+use crate::board::board::ChessBoard;
+use crate::board::state::CastlingRights;
+use crate::board::variant::Variant;
+use crate::game::Vector;
+use crate::math::iterate_bits;
+use crate::math::kings::mask_king_moves;
+
+const WDL_MAGIC: [u8; 4] = [0x71, 0xE8, 0x23, 0x5D];
+const DTZ_MAGIC: [u8; 4] = [0xD7, 0x66, 0x0C, 0xA5];
+const MAX_PIECES: usize = 7;
+
+// Flags stored with every block of compressed data
+const FLAG_STM: u8 = 1;
+const FLAG_MAPPED: u8 = 2;
+const FLAG_WIN_PLIES: u8 = 4;
+const FLAG_LOSS_PLIES: u8 = 8;
+const FLAG_WIDE: u8 = 16;
+const FLAG_SINGLE_VALUE: u8 = 128;
+
+// Syzygy piece codes (pawn 1, knight 2, bishop 3, rook 4, queen 5, king 6, plus 8 for black) by our piece constants
+const TB_PIECE: [u8; 6] = [1, 3, 2, 4, 5, 6];
+// Order of the pieces in table names
+const NAME_ORDER: [(usize, char); 6] = [
+    (Pieces::KING, 'K'),
+    (Pieces::QUEEN, 'Q'),
+    (Pieces::ROOK, 'R'),
+    (Pieces::BISHOP, 'B'),
+    (Pieces::KNIGHT, 'N'),
+    (Pieces::PAWN, 'P')
+];
+
+// Outcome for the side to move. Cursed wins and blessed losses are decided by the fifty-move rule.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Wdl {
+    Loss = -2,
+    BlessedLoss = -1,
+    Draw = 0,
+    CursedWin = 1,
+    Win = 2
+}
+
+impl Wdl {
+    fn from_value(value: i32) -> Option<Wdl> {
+        match value {
+            -2 => Some(Wdl::Loss),
+            -1 => Some(Wdl::BlessedLoss),
+            0 => Some(Wdl::Draw),
+            1 => Some(Wdl::CursedWin),
+            2 => Some(Wdl::Win),
+            _ => None
+        }
+    }
+
+    fn sign(self) -> i32 {
+        (self as i32).signum()
+    }
+
+    // Distance to zero of the move resetting the fifty-move counter
+    fn dtz_before_zeroing(self) -> i32 {
+        match self {
+            Wdl::Win => 1,
+            Wdl::CursedWin => 101,
+            Wdl::BlessedLoss => -101,
+            Wdl::Loss => -1,
+            Wdl::Draw => 0
+        }
+    }
+}
+
+impl Neg for Wdl {
+    type Output = Wdl;
+
+    fn neg(self) -> Wdl {
+        Wdl::from_value(-(self as i32)).unwrap()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum TableKind {
+    Wdl,
+    Dtz
+}
+
+// DTZ tables only store one side to move, the other one is resolved with a one ply search
+enum Lookup {
+    Value(i32),
+    ChangeStm
+}
+
+struct IndexTables {
+    binomial: [[u64; 64]; 6],
+    map_b1h1h7: [u64; 64],
+    map_a1d1d4: [u64; 64],
+    map_kk: [[u64; 64]; 10],
+    map_pawns: [u64; 64],
+    lead_pawn_idx: [[u64; 64]; 6],
+    lead_pawns_size: [[u64; 4]; 6]
+}
+
+lazy_static! {
+    static ref INDEX: IndexTables = IndexTables::new();
+}
+
+fn diagonal_offset(square: usize) -> i32 {
+    (square / 8) as i32 - (square % 8) as i32
+}
+
+impl IndexTables {
+    fn new() -> IndexTables {
+        let mut tables = IndexTables {
+            binomial: [[0; 64]; 6],
+            map_b1h1h7: [0; 64],
+            map_a1d1d4: [0; 64],
+            map_kk: [[0; 64]; 10],
+            map_pawns: [0; 64],
+            lead_pawn_idx: [[0; 64]; 6],
+            lead_pawns_size: [[0; 4]; 6]
+        };
+
+        // Squares below the a1-h8 diagonal, and of the a1-d1-d4 triangle with its diagonal squares last
+        let mut code = 0;
+        for square in 0..64 {
+            if diagonal_offset(square) < 0 {
+                tables.map_b1h1h7[square] = code;
+                code += 1;
+            }
+        }
+        code = 0;
+        let mut diagonal = Vec::new();
+        for square in 0..28 {
+            if square % 8 > 3 {
+                continue;
+            }
+            if diagonal_offset(square) < 0 {
+                tables.map_a1d1d4[square] = code;
+                code += 1;
+            } else if diagonal_offset(square) == 0 {
+                diagonal.push(square);
+            }
+        }
+        for square in diagonal {
+            tables.map_a1d1d4[square] = code;
+            code += 1;
+        }
+
+        // Legal placements of two kings with the first one in the triangle, both on the diagonal last
+        code = 0;
+        let mut both_on_diagonal = Vec::new();
+        for index in 0..10 {
+            for first in 0..28 {
+                if first % 8 > 3 || tables.map_a1d1d4[first] != index as u64 || (index == 0 && first != 1) {
+                    continue;
+                }
+                let near = mask_king_moves(1 << first, None).0 | 1 << first;
+                for second in 0..64 {
+                    // Adjacent kings, or the first king on the diagonal and the second above it
+                    if near & (1 << second) != 0 || (diagonal_offset(first) == 0 && diagonal_offset(second) > 0) {
+                        continue;
+                    } else if diagonal_offset(first) == 0 && diagonal_offset(second) == 0 {
+                        both_on_diagonal.push((index, second));
+                    } else {
+                        tables.map_kk[index][second] = code;
+                        code += 1;
+                    }
+                }
+            }
+        }
+        for (index, square) in both_on_diagonal {
+            tables.map_kk[index][square] = code;
+            code += 1;
+        }
+
+        tables.binomial[0][0] = 1;
+        for n in 1..64 {
+            for k in 0..6.min(n + 1) {
+                let left = if k > 0 { tables.binomial[k - 1][n - 1] } else { 0 };
+                let right = if k < n { tables.binomial[k][n - 1] } else { 0 };
+                tables.binomial[k][n] = left + right;
+            }
+        }
+
+        // Lead pawns are numbered from the edge towards the centre, a2 and h2 first
+        let mut available = 47;
+        for lead_pawns in 1..6 {
+            for file in 0..4 {
+                let mut index = 0;
+                for rank in 1..7 {
+                    let square = rank * 8 + file;
+                    if lead_pawns == 1 {
+                        tables.map_pawns[square] = available;
+                        tables.map_pawns[square ^ 7] = available - 1;
+                        available = available.saturating_sub(2);
+                    }
+                    tables.lead_pawn_idx[lead_pawns][square] = index;
+                    index += tables.binomial[lead_pawns - 1][tables.map_pawns[square] as usize];
+                }
+                tables.lead_pawns_size[lead_pawns][file] = index;
+            }
+        }
+        tables
+    }
+}
+
+// Material signature of a table, white being the side written first in its name
+#[derive(Debug, Clone, Copy)]
+struct Material {
+    piece_count: usize,
+    has_pawns: bool,
+    has_unique_pieces: bool,
+    // Pawns of the leading colour first
+    pawn_count: [usize; 2],
+    symmetric: bool
+}
+
+impl Material {
+    fn from_name(name: &str) -> Option<Material> {
+        let (white, black) = name.split_once('v')?;
+        let count = |side: &str, letter: char| side.chars().filter(|c| *c == letter).count();
+        let has_unique_pieces = [white, black].iter()
+            .any(|side| "QRBNP".chars().any(|letter| count(side, letter) == 1));
+        let (white_pawns, black_pawns) = (count(white, 'P'), count(black, 'P'));
+        let white_leads = black_pawns == 0 || (white_pawns > 0 && black_pawns >= white_pawns);
+        Some(Material {
+            piece_count: white.len() + black.len(),
+            has_pawns: white_pawns + black_pawns > 0,
+            has_unique_pieces,
+            pawn_count: if white_leads { [white_pawns, black_pawns] } else { [black_pawns, white_pawns] },
+            symmetric: white == black
+        })
+    }
+}
+
+// Decoding data of one side and file of a table, positions are byte offsets into the table file
+#[derive(Debug, Clone, Default)]
+struct PairsData {
+    flags: u8,
+    min_sym_len: u8,
+    blocks_num: usize,
+    size_of_block: usize,
+    span: usize,
+    lowest_sym: usize,
+    btree: usize,
+    block_length: usize,
+    block_length_size: usize,
+    sparse_index: usize,
+    sparse_index_size: usize,
+    data: usize,
+    base64: Vec<u64>,
+    symlen: Vec<u8>,
+    pieces: [u8; MAX_PIECES],
+    group_idx: [u64; MAX_PIECES + 1],
+    group_len: [usize; MAX_PIECES + 1],
+    // DTZ value maps, one per WDL outcome
+    map_idx: [usize; 4]
+}
+
+struct Table {
+    kind: TableKind,
+    material: Material,
+    bytes: Vec<u8>,
+    // Indexed by side to move and lead pawn file, pawnless tables only use file 0
+    items: [[PairsData; 4]; 2],
+    map: usize
+}
+
+fn read_u16(bytes: &[u8], position: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(bytes.get(position..position + 2)?.try_into().ok()?))
+}
+
+fn read_u32(bytes: &[u8], position: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(bytes.get(position..position + 4)?.try_into().ok()?))
+}
+
+// Left and right children of a symbol, packed as two 12 bit values
+fn btree_children(bytes: &[u8], d: &PairsData, symbol: usize) -> Option<(usize, usize)> {
+    let lr = bytes.get(d.btree + symbol * 3..d.btree + symbol * 3 + 3)?;
+    let left = ((lr[1] as usize & 0xF) << 8) | lr[0] as usize;
+    let right = ((lr[2] as usize) << 4) | (lr[1] as usize >> 4);
+    Some((left, right))
+}
+
+impl Table {
+    fn parse(kind: TableKind, name: &str, bytes: Vec<u8>) -> Option<Table> {
+        let material = Material::from_name(name)?;
+        let magic = if kind == TableKind::Wdl { WDL_MAGIC } else { DTZ_MAGIC };
+        if bytes.len() < 5 || bytes[..4] != magic {
+            return None;
+        }
+        let mut items: [[PairsData; 4]; 2] = Default::default();
+        let map = Self::layout(kind, &material, &bytes, &mut items)?;
+        Some(Table { kind, material, bytes, items, map })
+    }
+
+    fn layout(kind: TableKind, material: &Material, bytes: &[u8], items: &mut [[PairsData; 4]; 2]) -> Option<usize> {
+        let has_pawns = bytes[4] & 2 != 0;
+        if has_pawns != material.has_pawns {
+            return None;
+        }
+        let sides = if kind == TableKind::Wdl && !material.symmetric { 2 } else { 1 };
+        let files = if material.has_pawns { 4 } else { 1 };
+        let both_pawns = material.has_pawns && material.pawn_count[1] > 0;
+        let mut position = 5;
+
+        for file in 0..files {
+            let first = *bytes.get(position)?;
+            let second = if both_pawns { *bytes.get(position + 1)? } else { 0xFF };
+            let order = [[first & 0xF, second & 0xF], [first >> 4, second >> 4]];
+            position += 1 + both_pawns as usize;
+            for k in 0..material.piece_count {
+                let byte = *bytes.get(position)?;
+                for (side, side_items) in items.iter_mut().enumerate().take(sides) {
+                    side_items[file].pieces[k] = if side == 1 { byte >> 4 } else { byte & 0xF };
+                }
+                position += 1;
+            }
+            for (side, side_items) in items.iter_mut().enumerate().take(sides) {
+                set_groups(material, &mut side_items[file], order[side], file);
+            }
+        }
+        position += position & 1;
+
+        for file in 0..files {
+            for side_items in items.iter_mut().take(sides) {
+                position = set_sizes(&mut side_items[file], bytes, position)?;
+            }
+        }
+
+        let map = position;
+        if kind == TableKind::Dtz {
+            for d in items[0].iter_mut().take(files) {
+                if d.flags & FLAG_MAPPED == 0 {
+                    continue;
+                }
+                if d.flags & FLAG_WIDE != 0 {
+                    position += position & 1;
+                    for index in 0..4 {
+                        d.map_idx[index] = (position - map) / 2 + 1;
+                        position += 2 * read_u16(bytes, position)? as usize + 2;
+                    }
+                } else {
+                    for index in 0..4 {
+                        d.map_idx[index] = position - map + 1;
+                        position += *bytes.get(position)? as usize + 1;
+                    }
+                }
+            }
+            position += position & 1;
+        }
+
+        for file in 0..files {
+            for side_items in items.iter_mut().take(sides) {
+                side_items[file].sparse_index = position;
+                position += side_items[file].sparse_index_size * 6;
+            }
+        }
+        for file in 0..files {
+            for side_items in items.iter_mut().take(sides) {
+                side_items[file].block_length = position;
+                position += side_items[file].block_length_size * 2;
+            }
+        }
+        for file in 0..files {
+            for side_items in items.iter_mut().take(sides) {
+                position = (position + 0x3F) & !0x3F;
+                side_items[file].data = position;
+                position += side_items[file].blocks_num * side_items[file].size_of_block;
+            }
+        }
+        if position > bytes.len() {
+            return None;
+        }
+        Some(map)
+    }
+
+    fn get(&self, stm: usize, file: usize) -> &PairsData {
+        let side = if self.kind == TableKind::Wdl && !self.material.symmetric { stm } else { 0 };
+        &self.items[side][if self.material.has_pawns { file } else { 0 }]
+    }
+
+    fn check_dtz_stm(&self, stm: usize, file: usize) -> bool {
+        (self.get(stm, file).flags & FLAG_STM) as usize == stm || (self.material.symmetric && !self.material.has_pawns)
+    }
+
+    fn map_score(&self, file: usize, value: u16, wdl: Wdl) -> Option<i32> {
+        if self.kind == TableKind::Wdl {
+            return Some(value as i32 - 2);
+        }
+        let d = self.get(0, file);
+        let mut value = value as i32;
+        if d.flags & FLAG_MAPPED != 0 {
+            let index = d.map_idx[[1, 3, 0, 2, 0][(wdl as i32 + 2) as usize]] + value as usize;
+            value = if d.flags & FLAG_WIDE != 0 {
+                read_u16(&self.bytes, self.map + index * 2)? as i32
+            } else {
+                *self.bytes.get(self.map + index)? as i32
+            };
+        }
+        let plies = (wdl == Wdl::Win && d.flags & FLAG_WIN_PLIES != 0) || (wdl == Wdl::Loss && d.flags & FLAG_LOSS_PLIES != 0);
+        if !plies {
+            value *= 2;
+        }
+        Some(value + 1)
+    }
+
+    // Value of the index-th position, found through the sparse index and the Huffman coded blocks
+    fn decompress(&self, d: &PairsData, index: u64) -> Option<u16> {
+        let bytes = &self.bytes;
+        if d.flags & FLAG_SINGLE_VALUE != 0 {
+            return Some(d.min_sym_len as u16);
+        }
+        let span = d.span as u64;
+        let entry = d.sparse_index + (index / span) as usize * 6;
+        let mut block = read_u32(bytes, entry)? as usize;
+        let mut offset = read_u16(bytes, entry + 4)? as i64;
+        offset += (index % span) as i64 - (span / 2) as i64;
+        let block_length = |block: usize| read_u16(bytes, d.block_length + block * 2).map(|length| length as i64);
+        while offset < 0 {
+            block = block.checked_sub(1)?;
+            offset += block_length(block)? + 1;
+        }
+        while offset > block_length(block)? {
+            offset -= block_length(block)? + 1;
+            block += 1;
+        }
+
+        let mut pointer = d.data + block * d.size_of_block;
+        let mut buffer = u64::from_be_bytes(bytes.get(pointer..pointer + 8)?.try_into().ok()?);
+        pointer += 8;
+        let mut buffer_size = 64;
+        let mut symbol;
+        loop {
+            let mut length = 0;
+            while buffer < d.base64[length] {
+                length += 1;
+            }
+            symbol = ((buffer - d.base64[length]) >> (64 - length - d.min_sym_len as usize)) as usize;
+            symbol += read_u16(bytes, d.lowest_sym + length * 2)? as usize;
+            let symbol_length = *d.symlen.get(symbol)? as i64 + 1;
+            if offset < symbol_length {
+                break;
+            }
+            offset -= symbol_length;
+            length += d.min_sym_len as usize;
+            buffer <<= length;
+            buffer_size -= length;
+            if buffer_size <= 32 {
+                buffer_size += 32;
+                let next = u32::from_be_bytes(bytes.get(pointer..pointer + 4)?.try_into().ok()?);
+                pointer += 4;
+                buffer |= (next as u64) << (64 - buffer_size);
+            }
+        }
+
+        // Walk down the pair tree to the value the offset lands on
+        while d.symlen[symbol] != 0 {
+            let (left, right) = btree_children(bytes, d, symbol)?;
+            let left_length = *d.symlen.get(left)? as i64 + 1;
+            if offset < left_length {
+                symbol = left;
+            } else {
+                offset -= left_length;
+                symbol = right;
+            }
+        }
+        Some(btree_children(bytes, d, symbol)?.0 as u16)
+    }
+}
+
+fn set_groups(material: &Material, d: &mut PairsData, order: [u8; 2], file: usize) {
+    let index = &*INDEX;
+    let mut n = 0;
+    let mut first_len: i32 = if material.has_pawns { 0 } else if material.has_unique_pieces { 3 } else { 2 };
+    d.group_len[0] = 1;
+    for i in 1..material.piece_count {
+        first_len -= 1;
+        if first_len > 0 || d.pieces[i] == d.pieces[i - 1] {
+            d.group_len[n] += 1;
+        } else {
+            n += 1;
+            d.group_len[n] = 1;
+        }
+    }
+    n += 1;
+    d.group_len[n] = 0;
+
+    // Pawns of the second colour are encoded right after the lead pawns
+    let both_pawns = material.has_pawns && material.pawn_count[1] > 0;
+    let mut next = if both_pawns { 2 } else { 1 };
+    let mut free_squares = 64 - d.group_len[0] - if both_pawns { d.group_len[1] } else { 0 };
+    let mut idx = 1;
+    let mut k = 0;
+    while next < n || k == order[0] as usize || k == order[1] as usize {
+        if k == order[0] as usize {
+            d.group_idx[0] = idx;
+            idx *= if material.has_pawns {
+                index.lead_pawns_size[d.group_len[0]][file]
+            } else if material.has_unique_pieces {
+                31332
+            } else {
+                462
+            };
+        } else if k == order[1] as usize {
+            d.group_idx[1] = idx;
+            idx *= index.binomial[d.group_len[1]][48 - d.group_len[0]];
+        } else {
+            d.group_idx[next] = idx;
+            idx *= index.binomial[d.group_len[next]][free_squares];
+            free_squares -= d.group_len[next];
+            next += 1;
+        }
+        k += 1;
+    }
+    d.group_idx[n] = idx;
+}
+
+fn set_sizes(d: &mut PairsData, bytes: &[u8], mut position: usize) -> Option<usize> {
+    d.flags = *bytes.get(position)?;
+    position += 1;
+    if d.flags & FLAG_SINGLE_VALUE != 0 {
+        // The only value of the table is stored in place of the symbol length
+        d.min_sym_len = *bytes.get(position)?;
+        return Some(position + 1);
+    }
+
+    let end = d.group_len.iter().position(|length| *length == 0)?;
+    let size = d.group_idx[end];
+    d.size_of_block = 1 << *bytes.get(position)?;
+    d.span = 1 << *bytes.get(position + 1)?;
+    d.sparse_index_size = size.div_ceil(d.span as u64) as usize;
+    let padding = *bytes.get(position + 2)? as usize;
+    d.blocks_num = read_u32(bytes, position + 3)? as usize;
+    d.block_length_size = d.blocks_num + padding;
+    let max_sym_len = *bytes.get(position + 7)?;
+    d.min_sym_len = *bytes.get(position + 8)?;
+    position += 9;
+    if max_sym_len < d.min_sym_len {
+        return None;
+    }
+
+    // Canonical Huffman bases, left aligned on 64 bits
+    d.lowest_sym = position;
+    let count = (max_sym_len - d.min_sym_len) as usize + 1;
+    d.base64 = vec![0; count];
+    for i in (0..count - 1).rev() {
+        let lowest = read_u16(bytes, position + i * 2)? as u64;
+        let next_lowest = read_u16(bytes, position + i * 2 + 2)? as u64;
+        d.base64[i] = (d.base64[i + 1] + lowest).checked_sub(next_lowest)? / 2;
+    }
+    for (i, base) in d.base64.iter_mut().enumerate() {
+        *base = base.checked_shl((64 - i - d.min_sym_len as usize) as u32).unwrap_or(0);
+    }
+    position += count * 2;
+
+    let symbols = read_u16(bytes, position)? as usize;
+    position += 2;
+    d.btree = position;
+    d.symlen = vec![0; symbols];
+    let mut visited = vec![false; symbols];
+    for symbol in 0..symbols {
+        if !visited[symbol] {
+            set_symlen(d, bytes, symbol, &mut visited)?;
+        }
+    }
+    Some(position + symbols * 3 + (symbols & 1))
+}
+
+// Number of values a symbol expands to, minus one
+fn set_symlen(d: &mut PairsData, bytes: &[u8], symbol: usize, visited: &mut [bool]) -> Option<()> {
+    visited[symbol] = true;
+    let (left, right) = btree_children(bytes, d, symbol)?;
+    if right == 0xFFF {
+        return Some(());
+    }
+    if left >= visited.len() || right >= visited.len() {
+        return None;
+    }
+    if !visited[left] {
+        set_symlen(d, bytes, left, visited)?;
+    }
+    if !visited[right] {
+        set_symlen(d, bytes, right, visited)?;
+    }
+    d.symlen[symbol] = d.symlen[left].wrapping_add(d.symlen[right]).wrapping_add(1);
+    Some(())
+}
+
+// Syzygy WDL and DTZ tables found in one or more directories, separated by ':'.
+// Tables are read the first time a position needs them.
+pub struct Tablebase {
+    directories: Vec<PathBuf>,
+    max_pieces: usize,
+    tables: RwLock<HashMap<(TableKind, String), LoadedTable>>
+}
+
+// Missing or unreadable tables are remembered as None
+type LoadedTable = Option<Arc<Table>>;
+
+impl Tablebase {
+    pub fn open(path: &str) -> io::Result<Tablebase> {
+        let directories: Vec<PathBuf> = path.split(':').filter(|part| !part.is_empty()).map(PathBuf::from).collect();
+        let mut max_pieces = 0;
+        for directory in &directories {
+            for entry in fs::read_dir(directory)? {
+                let name = entry?.file_name().to_string_lossy().to_string();
+                if let Some(table) = name.strip_suffix(".rtbw") {
+                    if Material::from_name(table).is_some() {
+                        max_pieces = max_pieces.max(table.len() - 1);
+                    }
+                }
+            }
+        }
+        if max_pieces == 0 {
+            return Err(io::Error::new(io::ErrorKind::NotFound, format!("no Syzygy tables found in {}", path)));
+        }
+        Ok(Tablebase { directories, max_pieces: max_pieces.min(MAX_PIECES), tables: RwLock::new(HashMap::new()) })
+    }
+
+    pub fn max_pieces(&self) -> usize {
+        self.max_pieces
+    }
+
+    // Cheap test run before every probe: few enough pieces and no castling rights
+    pub fn can_probe(&self, board: &ChessBoard) -> bool {
+        let occupied = !board.bits.empty_squares().0;
+        // The tables only know the standard rules
+        board.state.variant == Variant::Standard
+            && occupied.count_ones() as usize <= self.max_pieces
+            && board.state.castling_rights == CastlingRights::none()
+    }
+
+    pub fn probe_wdl(&self, board: &ChessBoard) -> Option<Wdl> {
+        if !self.can_probe(board) {
+            return None;
+        }
+        let mut board = board.clone();
+        board.nnue = None;
+        self.search(&mut board, false).map(|(wdl, _)| wdl)
+    }
+
+    // Distance to the next zeroing move in plies, positive when the side to move wins.
+    // Values beyond 100 are cursed wins or blessed losses.
+    pub fn probe_dtz(&self, board: &ChessBoard) -> Option<i32> {
+        if !self.can_probe(board) {
+            return None;
+        }
+        let mut board = board.clone();
+        board.nnue = None;
+        self.dtz(&mut board)
+    }
+
+    // The move keeping the best outcome: the fastest zeroing win, else a draw, else the slowest loss
+    pub fn best_move(&self, board: &ChessBoard) -> Option<(CompletedMove, i32)> {
+        if !self.can_probe(board) {
+            return None;
+        }
+        let mut board = board.clone();
+        board.nnue = None;
+        let team = board.state.team_to_play;
+        let mut ranked = Vec::new();
+        for mv in board.generate_moves(team) {
+            let zeroing = mv.is_capture() || mv.is_en_passant() || is_pawn_move(&board, &mv);
+            let state = board.state;
+            let played = board.play_move(mv.origin, mv.target, mv.get_promotion(), true)?;
+            let dtz = if zeroing {
+                self.search(&mut board, false).map(|(wdl, _)| (-wdl).dtz_before_zeroing())
+            } else {
+                self.dtz(&mut board).map(|dtz| -dtz - dtz.signum())
+            };
+            // A mating move is one ply from the end
+            let mated = dtz == Some(2) && board.is_in_check(get_opposite_team(team))
+                && board.generate_moves(get_opposite_team(team)).is_empty();
+            board.undo_move(&played);
+            board.state = state;
+            ranked.push((mv, if mated { 1 } else { dtz? }));
+        }
+        ranked.into_iter().min_by_key(|(_, dtz)| match dtz.signum() {
+            1 => (0, *dtz),
+            0 => (1, 0),
+            _ => (2, *dtz)
+        })
+    }
+
+    // Resolves captures (and pawn moves when check_zeroing is set) before trusting the table, which assumes
+    // the best move is not one of them. Also reports whether the best result comes from a zeroing move.
+    fn search(&self, board: &mut ChessBoard, check_zeroing: bool) -> Option<(Wdl, bool)> {
+        let team = board.state.team_to_play;
+        let moves = board.generate_moves(team);
+        let mut count = 0;
+        let mut best = Wdl::Loss;
+        for mv in &moves {
+            let capture = mv.is_capture() || mv.is_en_passant();
+            if !capture && (!check_zeroing || !is_pawn_move(board, mv)) {
+                continue;
+            }
+            count += 1;
+            let state = board.state;
+            let played = board.play_move(mv.origin, mv.target, mv.get_promotion(), true)?;
+            let result = self.search(board, false);
+            board.undo_move(&played);
+            board.state = state;
+            let value = -result?.0;
+            if value > best {
+                best = value;
+                if value == Wdl::Win {
+                    return Some((value, true));
+                }
+            }
+        }
+
+        let no_more_moves = count > 0 && count == moves.len();
+        let value = if no_more_moves {
+            best
+        } else {
+            match self.probe_table(board, TableKind::Wdl, Wdl::Draw)? {
+                Lookup::Value(value) => Wdl::from_value(value)?,
+                Lookup::ChangeStm => return None
+            }
+        };
+        if best >= value {
+            return Some((best, best > Wdl::Draw || no_more_moves));
+        }
+        Some((value, false))
+    }
+
+    fn dtz(&self, board: &mut ChessBoard) -> Option<i32> {
+        let (wdl, zeroing) = self.search(board, true)?;
+        if wdl == Wdl::Draw {
+            return Some(0);
+        }
+        if zeroing {
+            return Some(wdl.dtz_before_zeroing());
+        }
+        if let Lookup::Value(dtz) = self.probe_table(board, TableKind::Dtz, wdl)? {
+            let cursed = matches!(wdl, Wdl::CursedWin | Wdl::BlessedLoss);
+            return Some((dtz + if cursed { 100 } else { 0 }) * wdl.sign());
+        }
+
+        // The table only stores the other side to move, so the best reply decides
+        let mut min_dtz = i32::MAX;
+        let team = board.state.team_to_play;
+        for mv in board.generate_moves(team) {
+            let zeroing = mv.is_capture() || mv.is_en_passant() || is_pawn_move(board, &mv);
+            let state = board.state;
+            let played = board.play_move(mv.origin, mv.target, mv.get_promotion(), true)?;
+            let result = if zeroing {
+                self.search(board, false).map(|(wdl, _)| -wdl.dtz_before_zeroing())
+            } else {
+                self.dtz(board).map(|dtz| -dtz)
+            };
+            let mated = result == Some(1) && board.is_in_check(get_opposite_team(team))
+                && board.generate_moves(get_opposite_team(team)).is_empty();
+            board.undo_move(&played);
+            board.state = state;
+            let mut dtz = result?;
+            if mated {
+                min_dtz = 1;
+            }
+            if !zeroing {
+                dtz += dtz.signum();
+            }
+            if dtz < min_dtz && dtz.signum() == wdl.sign() {
+                min_dtz = dtz;
+            }
+        }
+        Some(if min_dtz == i32::MAX { -1 } else { min_dtz })
+    }
+
+    fn table(&self, kind: TableKind, name: &str) -> Option<Arc<Table>> {
+        let key = (kind, name.to_string());
+        if let Some(table) = self.tables.read().unwrap().get(&key) {
+            return table.clone();
+        }
+        let extension = if kind == TableKind::Wdl { "rtbw" } else { "rtbz" };
+        let table = self.directories.iter()
+            .find_map(|directory| fs::read(directory.join(format!("{}.{}", name, extension))).ok())
+            .and_then(|bytes| Table::parse(kind, name, bytes))
+            .map(Arc::new);
+        self.tables.write().unwrap().insert(key, table.clone());
+        table
+    }
+
+    fn probe_table(&self, board: &ChessBoard, kind: TableKind, wdl: Wdl) -> Option<Lookup> {
+        let index = &*INDEX;
+        let occupied = !board.bits.empty_squares().0;
+        if occupied.count_ones() == 2 {
+            return Some(Lookup::Value(0));
+        }
+        let white = material_name(board, Teams::WHITE);
+        let black = material_name(board, Teams::BLACK);
+        let (table, black_stronger) = match self.table(kind, &format!("{}v{}", white, black)) {
+            Some(table) => (table, false),
+            None => (self.table(kind, &format!("{}v{}", black, white))?, true)
+        };
+
+        // Tables are stored with the stronger side as white, and with white to move when symmetric
+        let flip = black_stronger || (table.material.symmetric && board.state.team_to_play == Teams::BLACK);
+        let flip_color = if flip { 8 } else { 0 };
+        let flip_squares = if flip { 56 } else { 0 };
+        let stm = flip as usize ^ board.state.team_to_play;
+
+        let mut squares = [0usize; MAX_PIECES];
+        let mut pieces = [0u8; MAX_PIECES];
+        let mut size = 0;
+        let mut lead_pawns = 0;
+        let mut file = 0;
+        if table.material.has_pawns {
+            let lead = table.get(0, 0).pieces[0] ^ flip_color;
+            lead_pawns = board.bits.get_pieces((lead >> 3) as usize, Pieces::PAWN).0;
+            iterate_bits(lead_pawns, |bit| {
+                squares[size] = bit.trailing_zeros() as usize ^ flip_squares;
+                size += 1;
+            });
+            let mut max = 0;
+            for i in 1..size {
+                if index.map_pawns[squares[i]] > index.map_pawns[squares[max]] {
+                    max = i;
+                }
+            }
+            squares.swap(0, max);
+            file = (squares[0] % 8).min(7 - squares[0] % 8);
+        }
+        let lead_count = size;
+
+        if kind == TableKind::Dtz && !table.check_dtz_stm(stm, file) {
+            return Some(Lookup::ChangeStm);
+        }
+
+        iterate_bits(occupied ^ lead_pawns, |bit| {
+            let square = bit.trailing_zeros() as usize;
+            squares[size] = square ^ flip_squares;
+            pieces[size] = piece_code(board, square) ^ flip_color;
+            size += 1;
+        });
+
+        // Put the pieces in the order the table encodes them
+        let d = table.get(stm, file);
+        for i in lead_count..size.saturating_sub(1) {
+            for j in i + 1..size {
+                if d.pieces[i] == pieces[j] {
+                    pieces.swap(i, j);
+                    squares.swap(i, j);
+                    break;
+                }
+            }
+        }
+
+        // Mirror so the first piece is on the queen side, then in the a1-d1-d4 triangle for pawnless tables
+        if squares[0] % 8 > 3 {
+            for square in squares.iter_mut().take(size) {
+                *square ^= 7;
+            }
+        }
+        let mut idx;
+        if table.material.has_pawns {
+            idx = index.lead_pawn_idx[lead_count][squares[0]];
+            squares[1..lead_count].sort_by_key(|square| index.map_pawns[*square]);
+            for (i, square) in squares.iter().enumerate().take(lead_count).skip(1) {
+                idx += index.binomial[i][index.map_pawns[*square] as usize];
+            }
+        } else {
+            if squares[0] / 8 > 3 {
+                for square in squares.iter_mut().take(size) {
+                    *square ^= 56;
+                }
+            }
+            for i in 0..d.group_len[0] {
+                let offset = diagonal_offset(squares[i]);
+                if offset == 0 {
+                    continue;
+                }
+                if offset > 0 {
+                    for square in squares.iter_mut().take(size).skip(i) {
+                        *square = ((*square >> 3) | (*square << 3)) & 63;
+                    }
+                }
+                break;
+            }
+
+            if table.material.has_unique_pieces {
+                let (s0, s1, s2) = (squares[0], squares[1], squares[2]);
+                let adjust1 = (s1 > s0) as usize;
+                let adjust2 = (s2 > s0) as usize + (s2 > s1) as usize;
+                idx = if diagonal_offset(s0) != 0 {
+                    (index.map_a1d1d4[s0] * 63 + (s1 - adjust1) as u64) * 62 + (s2 - adjust2) as u64
+                } else if diagonal_offset(s1) != 0 {
+                    (6 * 63 + (s0 / 8) as u64 * 28 + index.map_b1h1h7[s1]) * 62 + (s2 - adjust2) as u64
+                } else if diagonal_offset(s2) != 0 {
+                    6 * 63 * 62 + 4 * 28 * 62 + (s0 / 8) as u64 * 7 * 28 + (s1 / 8 - adjust1) as u64 * 28 + index.map_b1h1h7[s2]
+                } else {
+                    6 * 63 * 62 + 4 * 28 * 62 + 4 * 7 * 28 + (s0 / 8) as u64 * 7 * 6 + (s1 / 8 - adjust1) as u64 * 6
+                        + (s2 / 8 - adjust2) as u64
+                };
+            } else {
+                idx = index.map_kk[index.map_a1d1d4[squares[0]] as usize][squares[1]];
+            }
+        }
+        idx *= d.group_idx[0];
+
+        // Each remaining group is a combination of squares not taken by the previous groups
+        let mut group_start = d.group_len[0];
+        let mut remaining_pawns = table.material.has_pawns && table.material.pawn_count[1] > 0;
+        let mut next = 1;
+        while d.group_len[next] != 0 {
+            let length = d.group_len[next];
+            squares[group_start..group_start + length].sort();
+            let mut n = 0;
+            for i in 0..length {
+                let square = squares[group_start + i];
+                let adjust = squares[..group_start].iter().filter(|previous| square > **previous).count();
+                n += index.binomial[i + 1][square - adjust - if remaining_pawns { 8 } else { 0 }];
+            }
+            remaining_pawns = false;
+            idx += n * d.group_idx[next];
+            group_start += length;
+            next += 1;
+        }
+
+        let value = table.decompress(d, idx)?;
+        table.map_score(file, value, wdl).map(Lookup::Value)
+    }
+}
+
+// Table name of one side's material, e.g. KRP
+fn material_name(board: &ChessBoard, team: usize) -> String {
+    let mut name = String::new();
+    for (piece, letter) in NAME_ORDER {
+        for _ in 0..board.bits.get_pieces(team, piece).count_ones() {
+            name.push(letter);
+        }
+    }
+    name
+}
+
+fn piece_code(board: &ChessBoard, square: usize) -> u8 {
+    let index = Vector::from_bit_position_index(square).mail_box_index();
+    board.mailbox.get_piece_at(index).map_or(0, |piece| TB_PIECE[piece.get_piece()] + 8 * piece.get_team() as u8)
+}
+
+fn is_pawn_move(board: &ChessBoard, mv: &CompletedMove) -> bool {
+    board.bits.get_pieces(board.state.team_to_play, Pieces::PAWN).0 & (1 << mv.origin) != 0
+}
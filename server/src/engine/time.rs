@@ -0,0 +1,23 @@
+use std::time::Duration;
+
+use crate::board::{Team, Teams};
+use crate::game::clock::ClockReading;
+
+// Moves the rest of the game is assumed to last when the time control does not say
+const DEFAULT_MOVES_TO_GO: u32 = 30;
+// Kept back for the time between the search ending and the move reaching the clock
+const MOVE_OVERHEAD: Duration = Duration::from_millis(50);
+const MIN_MOVE_TIME: Duration = Duration::from_millis(10);
+
+// Time to spend on the next move: an even share of what is left until the next time control, plus most of
+// the increment, never more than a fraction of the remaining time so a single move cannot lose on time
+pub fn allocate(clock: &ClockReading, team: Team) -> Duration {
+    let remaining = Duration::from_millis(if team == Teams::WHITE { clock.white_ms } else { clock.black_ms });
+    let available = remaining.saturating_sub(MOVE_OVERHEAD);
+    let moves_to_go = clock.moves_to_go.unwrap_or(DEFAULT_MOVES_TO_GO).clamp(1, DEFAULT_MOVES_TO_GO);
+    let increment = Duration::from_millis(clock.increment_ms);
+    let share = available / moves_to_go + increment * 3 / 4;
+    // With the control only a move away, almost everything may go into it
+    let cap = if moves_to_go == 1 { available * 9 / 10 } else { available / 2 };
+    share.min(cap).max(MIN_MOVE_TIME)
+}
@@ -0,0 +1,141 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::engine::{MATE_SCORE, MAX_PLY};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bound {
+    Exact,
+    Lower,
+    Upper
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct TranspositionEntry {
+    pub key: u64,
+    pub depth: u8,
+    pub score: i32,
+    pub bound: Bound,
+    // Origin in the low 6 bits, target in the next 6, zero when there is no move
+    best_move: u16
+}
+
+impl TranspositionEntry {
+    pub fn best_move(&self) -> Option<(u8, u8)> {
+        if self.best_move == 0 {
+            None
+        } else {
+            Some(((self.best_move & 0x3F) as u8, (self.best_move >> 6) as u8))
+        }
+    }
+}
+
+// Entries are written as two atomic words, the key being stored xored with the data. A slot torn by
+// two threads writing at once then fails the key check instead of returning another position's data,
+// so the table can be shared between search threads without locks.
+#[derive(Default)]
+struct Slot {
+    checked_key: AtomicU64,
+    data: AtomicU64
+}
+
+pub struct TranspositionTable {
+    slots: Vec<Slot>,
+    mask: usize
+}
+
+// Data layout: score in the low 32 bits, then depth, bound, move, and a used flag in the top bit
+const USED: u64 = 1 << 63;
+
+fn pack(entry: &TranspositionEntry) -> u64 {
+    let bound = match entry.bound {
+        Bound::Exact => 0,
+        Bound::Lower => 1,
+        Bound::Upper => 2
+    };
+    entry.score as u32 as u64 | (entry.depth as u64) << 32 | bound << 40 | (entry.best_move as u64) << 42 | USED
+}
+
+fn unpack(key: u64, data: u64) -> TranspositionEntry {
+    let bound = match (data >> 40) & 3 {
+        0 => Bound::Exact,
+        1 => Bound::Lower,
+        _ => Bound::Upper
+    };
+    TranspositionEntry {
+        key,
+        depth: (data >> 32) as u8,
+        score: data as u32 as i32,
+        bound,
+        best_move: ((data >> 42) & 0xFFF) as u16
+    }
+}
+
+impl TranspositionTable {
+    pub const DEFAULT_SIZE_MB: usize = 16;
+
+    pub fn new(size_mb: usize) -> Self {
+        let capacity = (size_mb * 1024 * 1024 / std::mem::size_of::<Slot>()).max(1);
+        // Round down to a power of two so that indexing is a single mask
+        let capacity = 1 << (usize::BITS - 1 - capacity.leading_zeros());
+        TranspositionTable {
+            slots: (0..capacity).map(|_| Slot::default()).collect(),
+            mask: capacity - 1
+        }
+    }
+
+    pub fn probe(&self, key: u64) -> Option<TranspositionEntry> {
+        let slot = &self.slots[key as usize & self.mask];
+        let data = slot.data.load(Ordering::Relaxed);
+        let checked_key = slot.checked_key.load(Ordering::Relaxed);
+        (data & USED != 0 && checked_key ^ data == key).then(|| unpack(key, data))
+    }
+
+    pub fn store(&self, key: u64, depth: u8, score: i32, bound: Bound, best_move: Option<(u8, u8)>) {
+        let slot = &self.slots[key as usize & self.mask];
+        if let Some(existing) = self.probe(key) {
+            // Keep deeper results for the same position unless the new one is exact
+            if existing.depth > depth && bound != Bound::Exact {
+                return;
+            }
+        }
+        let best_move = best_move.map_or(0, |(origin, target)| origin as u16 | (target as u16) << 6);
+        let data = pack(&TranspositionEntry { key, depth, score, bound, best_move });
+        slot.checked_key.store(key ^ data, Ordering::Relaxed);
+        slot.data.store(data, Ordering::Relaxed);
+    }
+
+    pub fn clear(&self) {
+        for slot in &self.slots {
+            slot.checked_key.store(0, Ordering::Relaxed);
+            slot.data.store(0, Ordering::Relaxed);
+        }
+    }
+
+    // Permille of used entries, sampled from the start of the table like UCI engines do
+    pub fn hashfull(&self) -> u16 {
+        let sample = self.slots.len().min(1000);
+        let used = self.slots[..sample].iter().filter(|slot| slot.data.load(Ordering::Relaxed) & USED != 0).count();
+        (used * 1000 / sample) as u16
+    }
+}
+
+// Mate scores are stored relative to the node so they stay valid when reached through another path
+pub fn score_to_tt(score: i32, ply: usize) -> i32 {
+    if score >= MATE_SCORE - MAX_PLY as i32 {
+        score + ply as i32
+    } else if score <= -MATE_SCORE + MAX_PLY as i32 {
+        score - ply as i32
+    } else {
+        score
+    }
+}
+
+pub fn score_from_tt(score: i32, ply: usize) -> i32 {
+    if score >= MATE_SCORE - MAX_PLY as i32 {
+        score - ply as i32
+    } else if score <= -MATE_SCORE + MAX_PLY as i32 {
+        score + ply as i32
+    } else {
+        score
+    }
+}
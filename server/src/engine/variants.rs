@@ -0,0 +1,46 @@
+use crate::board::{get_opposite_team, Pieces, Team};
+use crate::board::board::ChessBoard;
+use crate::board::variant::{HILL, THREE_CHECKS, Variant};
+use crate::math::kings::mask_king_moves;
+
+// Bonus for a king by its distance to the nearest hill square, the hill itself being a win
+pub const HILL_DISTANCE_BONUS: [i32; 8] = [0, 120, 60, 30, 15, 5, 0, 0];
+// Bonus by the number of checks given, the third one being a win
+pub const CHECK_BONUS: [i32; THREE_CHECKS as usize] = [0, 150, 400];
+// Penalty for each own piece next to the king, any capture there blowing the king up with it
+pub const ATOMIC_NEIGHBOUR_PENALTY: i32 = -20;
+
+// Terms for what the variant adds to the usual goals, from the reference team's point of view. Wins by
+// the variant's rules are scored by the search itself.
+pub fn evaluate_variant(board: &ChessBoard, reference: Team) -> i32 {
+    let opponent = get_opposite_team(reference);
+    let term = match board.state.variant {
+        Variant::Standard | Variant::Horde => return 0,
+        Variant::KingOfTheHill => hill_distance_bonus,
+        Variant::ThreeCheck => |board: &ChessBoard, team: Team| CHECK_BONUS[(board.state.checks[team] as usize).min(CHECK_BONUS.len() - 1)],
+        Variant::Atomic => atomic_neighbours
+    };
+    term(board, reference) - term(board, opponent)
+}
+
+fn hill_distance_bonus(board: &ChessBoard, team: Team) -> i32 {
+    let king = board.bits.get_pieces(team, Pieces::KING).0;
+    if king == 0 {
+        return 0;
+    }
+    let square = king.trailing_zeros() as usize;
+    let mut distance = 7;
+    let mut hill = HILL;
+    while hill != 0 {
+        let center = hill.trailing_zeros() as usize;
+        distance = distance.min((square % 8).abs_diff(center % 8).max((square / 8).abs_diff(center / 8)));
+        hill &= hill - 1;
+    }
+    HILL_DISTANCE_BONUS[distance]
+}
+
+fn atomic_neighbours(board: &ChessBoard, team: Team) -> i32 {
+    let king = board.bits.get_pieces(team, Pieces::KING).0;
+    let neighbours = mask_king_moves(king, None).0 & board.bits.get_team_pieces(team).0;
+    neighbours.count_ones() as i32 * ATOMIC_NEIGHBOUR_PENALTY
+}
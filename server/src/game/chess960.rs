@@ -0,0 +1,45 @@
+use rand::Rng;
+
+use crate::board::{Piece, Pieces};
+
+pub const POSITIONS: u16 = 960;
+// Scharnagl number of the classical start, RNBQKBNR
+pub const CLASSICAL_INDEX: u16 = 518;
+
+// Where the two knights go among the five squares left once the bishops and queen are placed
+const KNIGHT_PLACEMENTS: [(usize, usize); 10] = [(0, 1), (0, 2), (0, 3), (0, 4), (1, 2), (1, 3), (1, 4), (2, 3), (2, 4), (3, 4)];
+
+// Back rank of a start position from its Scharnagl number: the bishops on opposite colours, then the queen
+// and the knights on the free squares, then rook, king and rook on the three left over
+pub fn back_rank(index: u16) -> Option<[Piece; 8]> {
+    if index >= POSITIONS {
+        return None;
+    }
+    let mut rank: [Option<Piece>; 8] = [None; 8];
+    let index = index as usize;
+    rank[index % 4 * 2 + 1] = Some(Pieces::BISHOP);
+    rank[index / 4 % 4 * 2] = Some(Pieces::BISHOP);
+    let index = index / 16;
+    let queen = index % 6;
+    let (first_knight, second_knight) = KNIGHT_PLACEMENTS[index / 6];
+
+    let free = |rank: &[Option<Piece>; 8]| (0..8).filter(|file| rank[*file].is_none()).collect::<Vec<usize>>();
+    rank[free(&rank)[queen]] = Some(Pieces::QUEEN);
+    let squares = free(&rank);
+    rank[squares[first_knight]] = Some(Pieces::KNIGHT);
+    rank[squares[second_knight]] = Some(Pieces::KNIGHT);
+    for (square, piece) in free(&rank).into_iter().zip([Pieces::ROOK, Pieces::KING, Pieces::ROOK]) {
+        rank[square] = Some(piece);
+    }
+    Some(rank.map(|piece| piece.unwrap()))
+}
+
+// FEN of the start position, castling rights written in X-FEN so classical FEN readers take position 518
+pub fn start_fen(index: u16) -> Option<String> {
+    let rank: String = back_rank(index)?.iter().map(|piece| ['p', 'b', 'n', 'r', 'q', 'k'][*piece]).collect();
+    Some(format!("{}/pppppppp/8/8/8/8/PPPPPPPP/{} w KQkq - 0 1", rank, rank.to_ascii_uppercase()))
+}
+
+pub fn random_index() -> u16 {
+    rand::thread_rng().gen_range(0..POSITIONS)
+}
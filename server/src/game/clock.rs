@@ -0,0 +1,184 @@
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::board::{get_opposite_team, Team};
+
+// A stretch of the game with its own time budget. Without a move count it lasts until the end.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Period {
+    pub moves: Option<u32>,
+    pub base: Duration,
+    pub increment: Duration
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Delay {
+    // The clock waits this long before it starts running down
+    Simple { ms: u64 },
+    // The time used is given back after the move, up to this much
+    Bronstein { ms: u64 }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimeControl {
+    pub periods: Vec<Period>,
+    pub delay: Option<Delay>
+}
+
+// PGN TimeControl syntax in seconds: periods separated by ':', each "moves/base" or "base", with an optional
+// "+increment". 40/5400+30:1800+30 is 90 minutes for 40 moves then 30 minutes, with 30 seconds a move.
+// A last period with a move count repeats.
+impl FromStr for TimeControl {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let seconds = |text: &str| {
+            text.parse::<f64>().ok()
+                .filter(|seconds| seconds.is_finite() && *seconds >= 0.0)
+                .map(Duration::from_secs_f64)
+                .ok_or(format!("Invalid time {} in time control {}", text, value))
+        };
+        let periods = value.split(':').map(|period| {
+            let (budget, increment) = match period.split_once('+') {
+                Some((budget, increment)) => (budget, seconds(increment)?),
+                None => (period, Duration::ZERO)
+            };
+            let (moves, base) = match budget.split_once('/') {
+                Some((moves, base)) => {
+                    let moves = moves.parse().ok().filter(|moves| *moves > 0).ok_or(format!("Invalid move count in time control {}", value))?;
+                    (Some(moves), seconds(base)?)
+                }
+                None => (None, seconds(budget)?)
+            };
+            Ok(Period { moves, base, increment })
+        }).collect::<Result<Vec<_>, String>>()?;
+        if periods.iter().all(|period| period.base.is_zero() && period.increment.is_zero()) {
+            return Err(format!("Time control {} gives no time", value));
+        }
+        Ok(TimeControl { periods, delay: None })
+    }
+}
+
+// Server-side game clock. The side to move's time runs from the moment its clock was started.
+#[derive(Debug, Clone)]
+pub struct Clock {
+    control: TimeControl,
+    remaining: [Duration; 2],
+    period: [usize; 2],
+    // Moves each side played in its current period
+    period_moves: [u32; 2],
+    // The running side and since when, None before the clock starts and after it stops
+    running: Option<(Team, Instant)>
+}
+
+// Snapshot of the clock for a client or the engine's time manager
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct ClockReading {
+    pub white_ms: u64,
+    pub black_ms: u64,
+    pub increment_ms: u64,
+    // Moves left before the side to move reaches its next time control, if its period has one
+    pub moves_to_go: Option<u32>
+}
+
+impl Clock {
+    pub fn new(control: TimeControl) -> Self {
+        let base = control.periods[0].base;
+        Clock { control, remaining: [base; 2], period: [0; 2], period_moves: [0; 2], running: None }
+    }
+
+    pub fn control(&self) -> &TimeControl {
+        &self.control
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running.is_some()
+    }
+
+    pub fn start(&mut self, team: Team, now: Instant) {
+        self.running = Some((team, now));
+    }
+
+    pub fn stop(&mut self, now: Instant) {
+        if let Some((team, _)) = self.running {
+            self.remaining[team] = self.remaining(team, now);
+            self.running = None;
+        }
+    }
+
+    // Time left to the team at this instant, taking the running move into account
+    pub fn remaining(&self, team: Team, now: Instant) -> Duration {
+        match self.running {
+            Some((running, since)) if running == team => {
+                let charged = now.saturating_duration_since(since).saturating_sub(self.free_time());
+                self.remaining[team].saturating_sub(charged)
+            }
+            _ => self.remaining[team]
+        }
+    }
+
+    // How long until the team's flag falls, counting what is left of a simple delay
+    pub fn time_to_flag(&self, team: Team, now: Instant) -> Duration {
+        match self.running {
+            Some((running, since)) if running == team => {
+                (self.remaining[team] + self.free_time()).saturating_sub(now.saturating_duration_since(since))
+            }
+            _ => self.remaining[team]
+        }
+    }
+
+    pub fn is_flagged(&self, team: Team, now: Instant) -> bool {
+        self.remaining(team, now).is_zero()
+    }
+
+    // Closes the running side's move: charges it, adds the increment and any new period, then starts the
+    // opponent. Returns false when the mover's time ran out before the move, leaving the clock untouched.
+    pub fn press(&mut self, now: Instant) -> bool {
+        let Some((team, since)) = self.running else { return true };
+        if self.is_flagged(team, now) {
+            return false;
+        }
+        let elapsed = now.saturating_duration_since(since);
+        let mut remaining = self.remaining(team, now);
+        if let Some(Delay::Bronstein { ms }) = self.control.delay {
+            remaining += elapsed.min(Duration::from_millis(ms));
+        }
+        let period = self.current_period(team);
+        remaining += period.increment;
+        self.period_moves[team] += 1;
+        if period.moves == Some(self.period_moves[team]) {
+            // Next period, or the last one again
+            self.period[team] = (self.period[team] + 1).min(self.control.periods.len() - 1);
+            self.period_moves[team] = 0;
+            remaining += self.current_period(team).base;
+        }
+        self.remaining[team] = remaining;
+        self.running = Some((get_opposite_team(team), now));
+        true
+    }
+
+    pub fn reading(&self, team_to_play: Team, now: Instant) -> ClockReading {
+        let period = self.current_period(team_to_play);
+        ClockReading {
+            white_ms: self.remaining(0, now).as_millis() as u64,
+            black_ms: self.remaining(1, now).as_millis() as u64,
+            increment_ms: period.increment.as_millis() as u64,
+            moves_to_go: period.moves.map(|moves| moves - self.period_moves[team_to_play])
+        }
+    }
+
+    fn current_period(&self, team: Team) -> Period {
+        self.control.periods[self.period[team]]
+    }
+
+    // Part of a move that is not charged under a simple delay
+    fn free_time(&self) -> Duration {
+        match self.control.delay {
+            Some(Delay::Simple { ms }) => Duration::from_millis(ms),
+            _ => Duration::ZERO
+        }
+    }
+}
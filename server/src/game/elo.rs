@@ -0,0 +1,133 @@
+use std::fmt::Display;
+
+// 95% confidence
+const Z_95: f64 = 1.959964;
+
+// Results from the first engine's point of view
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MatchScore {
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32
+}
+
+impl MatchScore {
+    pub fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+
+    // Points per game, between 0 and 1
+    pub fn score(&self) -> f64 {
+        (self.wins as f64 + self.draws as f64 / 2.0) / self.games().max(1) as f64
+    }
+
+    pub fn draw_ratio(&self) -> f64 {
+        self.draws as f64 / self.games().max(1) as f64
+    }
+
+    // Variance of a single game's points
+    fn variance(&self) -> f64 {
+        let score = self.score();
+        let games = self.games().max(1) as f64;
+        (self.wins as f64 * (1.0 - score).powi(2) + self.draws as f64 * (0.5 - score).powi(2) + self.losses as f64 * score.powi(2)) / games
+    }
+
+    // Elo difference with the half width of its 95% interval. None until both engines scored and lost points,
+    // when the difference is still infinite.
+    pub fn elo(&self) -> Option<(f64, f64)> {
+        let score = self.score();
+        if self.games() == 0 || score <= 0.0 || score >= 1.0 {
+            return None;
+        }
+        let deviation = (self.variance() / self.games() as f64).sqrt();
+        let low = score_to_elo((score - Z_95 * deviation).max(f64::EPSILON));
+        let high = score_to_elo((score + Z_95 * deviation).min(1.0 - f64::EPSILON));
+        Some((score_to_elo(score), (high - low) / 2.0))
+    }
+
+    // Likelihood of superiority: the chance the first engine is the stronger one, draws left out
+    pub fn los(&self) -> f64 {
+        let decisive = (self.wins + self.losses) as f64;
+        if decisive == 0.0 {
+            return 0.5;
+        }
+        0.5 * (1.0 + erf((self.wins as f64 - self.losses as f64) / (2.0 * decisive).sqrt()))
+    }
+}
+
+impl Display for MatchScore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} - {} - {} [{:.3}] {}", self.wins, self.losses, self.draws, self.score(), self.games())
+    }
+}
+
+pub fn score_to_elo(score: f64) -> f64 {
+    -400.0 * (1.0 / score - 1.0).log10()
+}
+
+pub fn elo_to_score(elo: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-elo / 400.0))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SprtStatus {
+    Continue,
+    // The first engine is no better than elo0
+    AcceptH0,
+    // The first engine is at least elo1 better
+    AcceptH1
+}
+
+// Sequential probability ratio test of elo0 against elo1, stopping the match as soon as the results tell
+// them apart with the error rates alpha (false positives) and beta (false negatives)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sprt {
+    pub elo0: f64,
+    pub elo1: f64,
+    pub alpha: f64,
+    pub beta: f64
+}
+
+impl Default for Sprt {
+    fn default() -> Self {
+        Sprt { elo0: 0.0, elo1: 5.0, alpha: 0.05, beta: 0.05 }
+    }
+}
+
+impl Sprt {
+    pub fn lower_bound(&self) -> f64 {
+        (self.beta / (1.0 - self.alpha)).ln()
+    }
+
+    pub fn upper_bound(&self) -> f64 {
+        ((1.0 - self.beta) / self.alpha).ln()
+    }
+
+    // Log-likelihood ratio in the normal approximation of the generalized SPRT, on logistic Elo
+    pub fn llr(&self, results: &MatchScore) -> f64 {
+        let variance = results.variance();
+        if results.games() == 0 || variance <= 0.0 {
+            return 0.0;
+        }
+        let (score0, score1) = (elo_to_score(self.elo0), elo_to_score(self.elo1));
+        results.games() as f64 * (score1 - score0) * (2.0 * results.score() - score0 - score1) / (2.0 * variance)
+    }
+
+    pub fn status(&self, results: &MatchScore) -> SprtStatus {
+        let llr = self.llr(results);
+        if llr >= self.upper_bound() {
+            SprtStatus::AcceptH1
+        } else if llr <= self.lower_bound() {
+            SprtStatus::AcceptH0
+        } else {
+            SprtStatus::Continue
+        }
+    }
+}
+
+// Abramowitz and Stegun 7.1.26, good to 1.5e-7
+fn erf(x: f64) -> f64 {
+    let t = 1.0 / (1.0 + 0.3275911 * x.abs());
+    let polynomial = t * (0.254829592 + t * (-0.284496736 + t * (1.421413741 + t * (-1.453152027 + t * 1.061405429))));
+    (1.0 - polynomial * (-x * x).exp()).copysign(x)
+}
@@ -0,0 +1,132 @@
+use crate::board::CompletedMove;
+use crate::board::board::ChessBoard;
+use crate::game::fen;
+use crate::game::play::Game;
+use crate::game::san::parse_san;
+use crate::game::square::parse_long_algebraic;
+
+// A position of an EPD file: the first four FEN fields, then operations such as bm Qxf7+; id "WAC.001";
+#[derive(Debug, Clone)]
+pub struct EpdRecord {
+    // Full FEN, with the move counters from the hmvc and fmvn operations when present
+    pub fen: String,
+    pub operations: Vec<(String, Vec<String>)>
+}
+
+impl EpdRecord {
+    // None for blank lines, comments and lines without four position fields
+    pub fn parse(line: &str) -> Option<EpdRecord> {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+        let mut rest = line;
+        let mut fields = Vec::new();
+        for _ in 0..4 {
+            rest = rest.trim_start();
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            fields.push(&rest[..end]);
+            rest = &rest[end..];
+        }
+        if fields.iter().any(|field| field.is_empty()) {
+            return None;
+        }
+        let operations = parse_operations(rest);
+        let mut record = EpdRecord { fen: String::new(), operations };
+        // Plain FEN lines carry the counters as two more fields rather than operations
+        let (halfmove, fullmove) = match &record.operations[..] {
+            [(counter, operands)] if operands.len() == 1 && counter.parse::<u16>().is_ok() && operands[0].parse::<u16>().is_ok() => {
+                let counters = (counter.clone(), operands[0].clone());
+                record.operations.clear();
+                counters
+            }
+            _ => (
+                record.operand("hmvc").unwrap_or("0").to_string(),
+                record.operand("fmvn").unwrap_or("1").to_string()
+            )
+        };
+        record.fen = format!("{} {} {}", fields.join(" "), halfmove, fullmove);
+        Some(record)
+    }
+
+    pub fn board(&self) -> Option<ChessBoard> {
+        fen::new_board(&self.fen)
+    }
+
+    pub fn operation(&self, opcode: &str) -> Option<&[String]> {
+        self.operations.iter().find(|(name, _)| name == opcode).map(|(_, operands)| operands.as_slice())
+    }
+
+    // First operand of the operation
+    pub fn operand(&self, opcode: &str) -> Option<&str> {
+        self.operation(opcode)?.first().map(String::as_str)
+    }
+
+    pub fn id(&self) -> Option<&str> {
+        self.operand("id")
+    }
+
+    // Moves of a bm or am operation, in SAN or long algebraic notation. None when the operation is missing or
+    // one of its moves is not legal here.
+    pub fn moves(&self, opcode: &str) -> Option<Vec<CompletedMove>> {
+        let game = Game::new(self.board()?);
+        self.operation(opcode)?.iter().map(|text| {
+            parse_san(game.board(), text).or_else(|| {
+                let (origin, target, promotion) = parse_long_algebraic(text)?;
+                game.legal_moves().into_iter().find(|legal| legal.origin == origin && legal.target == target && legal.get_promotion() == promotion)
+            })
+        }).collect()
+    }
+
+    // Moves to the mate of a dm operation
+    pub fn direct_mate(&self) -> Option<i32> {
+        self.operand("dm")?.parse().ok()
+    }
+}
+
+// Every record of an EPD file, skipping the lines that are not one
+pub fn parse_epd(text: &str) -> Vec<EpdRecord> {
+    text.lines().filter_map(EpdRecord::parse).collect()
+}
+
+// Operations end with a semicolon, their operands are separated by spaces unless quoted
+fn parse_operations(text: &str) -> Vec<(String, Vec<String>)> {
+    let mut operations = Vec::new();
+    let mut tokens = Vec::new();
+    let mut token = String::new();
+    let mut quoted = false;
+    let finish_token = |token: &mut String, tokens: &mut Vec<String>| {
+        if !token.is_empty() {
+            tokens.push(std::mem::take(token));
+        }
+    };
+    for c in text.chars() {
+        match c {
+            '"' => {
+                if quoted {
+                    tokens.push(std::mem::take(&mut token));
+                } else {
+                    finish_token(&mut token, &mut tokens);
+                }
+                quoted = !quoted;
+            }
+            _ if quoted => token.push(c),
+            ';' => {
+                finish_token(&mut token, &mut tokens);
+                if !tokens.is_empty() {
+                    let opcode = tokens.remove(0);
+                    operations.push((opcode, std::mem::take(&mut tokens)));
+                }
+            }
+            c if c.is_whitespace() => finish_token(&mut token, &mut tokens),
+            c => token.push(c)
+        }
+    }
+    // The last operation may lack its semicolon
+    finish_token(&mut token, &mut tokens);
+    if !tokens.is_empty() {
+        let opcode = tokens.remove(0);
+        operations.push((opcode, tokens));
+    }
+    operations
+}
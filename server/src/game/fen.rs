@@ -1,10 +1,23 @@
-use crate::board::{BitBoard, BitPosition, GamePiece, MailBox, Pieces, Teams};
+use crate::board::{BitBoard, BitPosition, GamePiece, MailBox, Pieces, Team, Teams};
 use crate::board::board::ChessBoard;
 use crate::board::state::{CastlingRights, CastlingSides, ChessState};
-use crate::game::square::square_to_vector;
+use crate::board::variant::{THREE_CHECKS, Variant};
+use crate::game::square::{index_to_square, square_to_vector};
 
 pub fn new_board(fen: &str) -> Option<ChessBoard> {
-    let parts: Vec<&str> = fen.split_whitespace().collect();
+    new_variant_board(fen, Variant::Standard)
+}
+
+// Three-check FENs carry the checks each side has left before the move counters, as in 3+3
+pub fn new_variant_board(fen: &str, variant: Variant) -> Option<ChessBoard> {
+    let mut parts: Vec<&str> = fen.split_whitespace().collect();
+    let mut checks = [0, 0];
+    if parts.len() == 7 {
+        let (white, black) = parts.remove(4).split_once('+')?;
+        for (team, remaining) in [(Teams::WHITE, white), (Teams::BLACK, black)] {
+            checks[team] = THREE_CHECKS.checked_sub(remaining.parse().ok()?)?;
+        }
+    }
     if parts.len() != 6 {
         return None;
     }
@@ -48,27 +61,116 @@ pub fn new_board(fen: &str) -> Option<ChessBoard> {
         _ => return None,
     };
 
-    let castling_rights_part = parts[2];
+    // KQkq name the outermost rook of each side (X-FEN), file letters name the rook directly (Shredder-FEN)
     let mut castling_rights = CastlingRights::none();
-    for c in castling_rights_part.chars() {
-        match c {
-            'K' => castling_rights.allow(Teams::WHITE, CastlingSides::KINGSIDE),
-            'Q' => castling_rights.allow(Teams::WHITE, CastlingSides::QUEENSIDE),
-            'k' => castling_rights.allow(Teams::BLACK, CastlingSides::KINGSIDE),
-            'q' => castling_rights.allow(Teams::BLACK, CastlingSides::QUEENSIDE),
+    let mut castling_files = ChessState::STANDARD_CASTLING_FILES;
+    for c in parts[2].chars() {
+        let team = if c.is_ascii_uppercase() { Teams::WHITE } else { Teams::BLACK };
+        let king_file = back_rank_king_file(&mail_box, team);
+        let (side, rook_file) = match c.to_ascii_lowercase() {
+            'k' => (CastlingSides::KINGSIDE, (king_file + 1..8).rev().find(|file| is_back_rank_rook(&mail_box, team, *file)).unwrap_or(7)),
+            'q' => (CastlingSides::QUEENSIDE, (0..king_file).find(|file| is_back_rank_rook(&mail_box, team, *file)).unwrap_or(0)),
+            file @ 'a'..='h' => {
+                let file = file as u8 - b'a';
+                (if file > king_file { CastlingSides::KINGSIDE } else { CastlingSides::QUEENSIDE }, file)
+            }
             '-' => break,
             _ => return None,
-        }
+        };
+        castling_rights.allow(team, side);
+        castling_files[team][side] = rook_file;
     }
+    // Chess960 as soon as a castling king or rook is away from its classical square
+    let chess960 = [Teams::WHITE, Teams::BLACK].into_iter().any(|team| {
+        [CastlingSides::KINGSIDE, CastlingSides::QUEENSIDE].into_iter().any(|side| {
+            castling_rights.is_allowed(team, side)
+                && (castling_files[team][side] != ChessState::STANDARD_CASTLING_FILES[team][side] || back_rank_king_file(&mail_box, team) != 4)
+        })
+    });
     let en_passant_square = match parts[3] {
         "-" => None,
         square => Some(square_to_vector(square).bit_position_index()),
     };
+    let fullmove_number: u16 = parts[5].parse().unwrap_or(1);
+    let ply = fullmove_number.max(1).saturating_sub(1) * 2 + team_to_play as u16;
     let state = ChessState {
         castling_rights,
         en_passant_square,
-        team_to_play
+        team_to_play,
+        ply,
+        castling_files,
+        chess960,
+        variant,
+        checks
     };
 
     Some(ChessBoard::new(bit_position, mail_box, state))
-}
\ No newline at end of file
+}
+// The board does not track the halfmove clock, callers that do pass it in
+pub fn to_fen(board: &ChessBoard, halfmove_clock: u16) -> String {
+    let mut placement = String::new();
+    for rank in (0..8).rev() {
+        let mut empty = 0;
+        for file in 0..8 {
+            match board.mailbox.get_piece_at(rank * 16 + file) {
+                Some(piece) => {
+                    if empty > 0 {
+                        placement.push_str(&empty.to_string());
+                        empty = 0;
+                    }
+                    let c = ['p', 'b', 'n', 'r', 'q', 'k'][piece.get_piece()];
+                    placement.push(if piece.get_team() == Teams::WHITE { c.to_ascii_uppercase() } else { c });
+                }
+                None => empty += 1
+            }
+        }
+        if empty > 0 {
+            placement.push_str(&empty.to_string());
+        }
+        if rank > 0 {
+            placement.push('/');
+        }
+    }
+
+    let state = &board.state;
+    let team_to_play = if state.team_to_play == Teams::WHITE { "w" } else { "b" };
+    // X-FEN: KQkq unless another rook stands between the castling rook and the corner, then the rook's file
+    let mut castling = String::new();
+    for (team, side) in [
+        (Teams::WHITE, CastlingSides::KINGSIDE),
+        (Teams::WHITE, CastlingSides::QUEENSIDE),
+        (Teams::BLACK, CastlingSides::KINGSIDE),
+        (Teams::BLACK, CastlingSides::QUEENSIDE)
+    ] {
+        if !state.castling_rights.is_allowed(team, side) {
+            continue;
+        }
+        let rook_file = state.castling_files[team][side];
+        let outer_files = if side == CastlingSides::KINGSIDE { rook_file + 1..8 } else { 0..rook_file };
+        let c = if outer_files.into_iter().any(|file| is_back_rank_rook(&board.mailbox, team, file)) {
+            (b'a' + rook_file) as char
+        } else if side == CastlingSides::KINGSIDE {
+            'k'
+        } else {
+            'q'
+        };
+        castling.push(if team == Teams::WHITE { c.to_ascii_uppercase() } else { c });
+    }
+    if castling.is_empty() {
+        castling.push('-');
+    }
+    let mut en_passant = state.en_passant_square.map_or("-".to_string(), |square| index_to_square(square as u8));
+    if state.variant == Variant::ThreeCheck {
+        en_passant += &format!(" {}+{}", THREE_CHECKS - state.checks[Teams::WHITE], THREE_CHECKS - state.checks[Teams::BLACK]);
+    }
+    format!("{} {} {} {} {} {}", placement, team_to_play, castling, en_passant, halfmove_clock, state.ply / 2 + 1)
+}
+
+// File of the team's king on its back rank, the e-file when it is elsewhere
+fn back_rank_king_file(mail_box: &MailBox, team: Team) -> u8 {
+    (0..8).find(|file| *mail_box.get_piece_at(team * 7 * 16 + *file as usize) == Some(GamePiece::King(team))).unwrap_or(4)
+}
+
+fn is_back_rank_rook(mail_box: &MailBox, team: Team, file: u8) -> bool {
+    *mail_box.get_piece_at(team * 7 * 16 + file as usize) == Some(GamePiece::Rook(team))
+}
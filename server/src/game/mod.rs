@@ -2,8 +2,15 @@ use std::fmt::Display;
 use serde::{Deserialize, Serialize};
 use crate::board::Piece;
 
+pub mod chess960;
+pub mod clock;
+pub mod elo;
+pub mod epd;
 pub mod fen;
-mod square;
+pub mod pgn;
+pub mod play;
+pub mod san;
+pub mod square;
 
 #[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Eq, Ord, Hash, Serialize, Deserialize)]
 // Goes from 0 to 7
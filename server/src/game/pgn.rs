@@ -0,0 +1,183 @@
+use serde::Serialize;
+
+use crate::board::{CompletedMove, Teams};
+use crate::board::board::ChessBoard;
+use crate::game::san::to_san;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GameResult {
+    WhiteWins,
+    BlackWins,
+    Draw,
+    Unknown
+}
+
+impl GameResult {
+    pub fn parse(value: &str) -> Option<GameResult> {
+        match value {
+            "1-0" => Some(GameResult::WhiteWins),
+            "0-1" => Some(GameResult::BlackWins),
+            "1/2-1/2" => Some(GameResult::Draw),
+            "*" => Some(GameResult::Unknown),
+            _ => None
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GameResult::WhiteWins => "1-0",
+            GameResult::BlackWins => "0-1",
+            GameResult::Draw => "1/2-1/2",
+            GameResult::Unknown => "*"
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PgnGame {
+    pub tags: Vec<(String, String)>,
+    // Main line moves in SAN, without move numbers, comments, annotations or variations
+    pub moves: Vec<String>,
+    pub result: GameResult
+}
+
+impl PgnGame {
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags.iter().find(|(tag, _)| tag == name).map(|(_, value)| value.as_str())
+    }
+}
+
+// Reads every game of a PGN collection. A game ends at its result token, the Result tag wins when both exist.
+pub fn parse_pgn(text: &str) -> Vec<PgnGame> {
+    let mut games = Vec::new();
+    let mut tags = Vec::new();
+    let mut moves = Vec::new();
+    let mut chars = text.chars().peekable();
+    let mut token = String::new();
+
+    let mut finish = |tags: &mut Vec<(String, String)>, moves: &mut Vec<String>, terminator: GameResult| {
+        let result = tags.iter()
+            .find(|(tag, _): &&(String, String)| tag == "Result")
+            .and_then(|(_, value)| GameResult::parse(value))
+            .unwrap_or(terminator);
+        games.push(PgnGame { tags: std::mem::take(tags), moves: std::mem::take(moves), result });
+    };
+
+    while let Some(c) = chars.next() {
+        match c {
+            '[' => {
+                let tag: String = chars.by_ref().take_while(|c| *c != ']').collect();
+                // A tag section after movetext without a result starts a new game
+                if !moves.is_empty() {
+                    finish(&mut tags, &mut moves, GameResult::Unknown);
+                }
+                if let Some((name, value)) = tag.trim().split_once(' ') {
+                    tags.push((name.to_string(), value.trim().trim_matches('"').to_string()));
+                }
+            }
+            '{' => {
+                chars.by_ref().find(|c| *c == '}');
+            }
+            ';' => {
+                chars.by_ref().find(|c| *c == '\n');
+            }
+            '(' => {
+                let mut depth = 1;
+                for c in chars.by_ref() {
+                    match c {
+                        '(' => depth += 1,
+                        ')' => depth -= 1,
+                        _ => {}
+                    }
+                    if depth == 0 {
+                        break;
+                    }
+                }
+            }
+            c if c.is_whitespace() => {}
+            c => {
+                token.clear();
+                token.push(c);
+                while let Some(next) = chars.peek() {
+                    if next.is_whitespace() || matches!(next, '{' | '(' | ';' | '[') {
+                        break;
+                    }
+                    token.push(chars.next().unwrap());
+                }
+                if let Some(result) = GameResult::parse(&token) {
+                    finish(&mut tags, &mut moves, result);
+                    continue;
+                }
+                if token.starts_with('$') {
+                    continue;
+                }
+                // Strip move numbers, which may be glued to the move (1.e4 or 12...Nf6)
+                let san = token.rsplit('.').next().unwrap_or_default();
+                if !san.is_empty() {
+                    moves.push(san.to_string());
+                }
+            }
+        }
+    }
+    if !moves.is_empty() {
+        finish(&mut tags, &mut moves, GameResult::Unknown);
+    }
+    games
+}
+
+// YYYY.MM.DD in UTC
+pub fn pgn_date(unix_seconds: u64) -> String {
+    // Howard Hinnant's civil_from_days
+    let days = (unix_seconds / 86_400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!("{:04}.{:02}.{:02}", year, month, day)
+}
+
+// Writes a game as PGN: the tags in order, then the moves in SAN played from the start position, wrapped
+// at 80 columns. The Result tag and the termination come from the result.
+pub fn write_pgn(tags: &[(String, String)], start: &ChessBoard, moves: &[CompletedMove], result: GameResult) -> String {
+    let mut pgn = String::new();
+    for (name, value) in tags.iter().filter(|(name, _)| name != "Result") {
+        pgn.push_str(&format!("[{} \"{}\"]\n", name, value.replace('\\', "\\\\").replace('"', "\\\"")));
+    }
+    pgn.push_str(&format!("[Result \"{}\"]\n\n", result.as_str()));
+
+    let mut board = start.clone();
+    let mut tokens = Vec::new();
+    for (index, mv) in moves.iter().enumerate() {
+        let white = board.state.team_to_play == Teams::WHITE;
+        let number = board.state.ply / 2 + 1;
+        if white {
+            tokens.push(format!("{}.", number));
+        } else if index == 0 {
+            tokens.push(format!("{}...", number));
+        }
+        tokens.push(to_san(&board, mv));
+        board.play_move(mv.origin, mv.target, mv.get_promotion(), true);
+    }
+    tokens.push(result.as_str().to_string());
+
+    let mut line = String::new();
+    for token in tokens {
+        if !line.is_empty() && line.len() + 1 + token.len() > 80 {
+            pgn.push_str(&line);
+            pgn.push('\n');
+            line.clear();
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(&token);
+    }
+    pgn.push_str(&line);
+    pgn.push('\n');
+    pgn
+}
@@ -0,0 +1,268 @@
+use serde::Serialize;
+
+use crate::board::{CompletedMove, get_opposite_team, Piece, Pieces, Team, Teams};
+use crate::board::board::ChessBoard;
+use crate::board::variant::Variant;
+use crate::book::polyglot::polyglot_key;
+use crate::game::fen;
+use crate::game::pgn::GameResult;
+
+const DARK_SQUARES: u64 = 0xAA55AA55AA55AA55;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EndReason {
+    Checkmate,
+    Stalemate,
+    Repetition,
+    FiftyMoves,
+    InsufficientMaterial,
+    Resignation,
+    Agreement,
+    Timeout,
+    // Out of time, but the opponent could never have mated
+    TimeoutVsInsufficientMaterial,
+    // Wins by the variant's own rules
+    KingOfTheHill,
+    ThreeChecks,
+    KingExploded,
+    HordeCaptured
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Outcome {
+    pub result: GameResult,
+    pub reason: EndReason
+}
+
+impl Outcome {
+    pub fn win(winner: Team, reason: EndReason) -> Self {
+        let result = if winner == Teams::WHITE { GameResult::WhiteWins } else { GameResult::BlackWins };
+        Outcome { result, reason }
+    }
+
+    pub fn draw(reason: EndReason) -> Self {
+        Outcome { result: GameResult::Draw, reason }
+    }
+}
+
+// A game played from a starting position, checking every move and ending it when the rules say so
+#[derive(Debug, Clone)]
+pub struct Game {
+    start_fen: String,
+    board: ChessBoard,
+    moves: Vec<CompletedMove>,
+    // Half-moves since the last capture or pawn move
+    halfmove_clock: u16,
+    // Positions since the last capture or pawn move, the current one last
+    keys: Vec<RepetitionKey>,
+    outcome: Option<Outcome>
+}
+
+impl Game {
+    pub fn new(board: ChessBoard) -> Self {
+        let start_fen = fen::to_fen(&board, 0);
+        let keys = vec![repetition_key(&board)];
+        let mut game = Game { start_fen, board, moves: Vec::new(), halfmove_clock: 0, keys, outcome: None };
+        game.outcome = game.rules_outcome();
+        game
+    }
+
+    pub fn start_fen(&self) -> &str {
+        &self.start_fen
+    }
+
+    pub fn fen(&self) -> String {
+        fen::to_fen(&self.board, self.halfmove_clock)
+    }
+
+    pub fn board(&self) -> &ChessBoard {
+        &self.board
+    }
+
+    pub fn moves(&self) -> &[CompletedMove] {
+        &self.moves
+    }
+
+    pub fn team_to_play(&self) -> Team {
+        self.board.state.team_to_play
+    }
+
+    pub fn outcome(&self) -> Option<Outcome> {
+        self.outcome
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.outcome.is_some()
+    }
+
+    pub fn variant(&self) -> Variant {
+        self.board.state.variant
+    }
+
+    pub fn legal_moves(&self) -> Vec<CompletedMove> {
+        legal_moves(&self.board)
+    }
+
+    // None when the game is over or the move is not legal. A pawn reaching the last rank must name the piece
+    // it becomes, any other move must not.
+    pub fn play(&mut self, origin: u8, target: u8, promotion: Option<Piece>) -> Option<CompletedMove> {
+        if self.is_over() || !self.legal_moves().iter().any(|mv| mv.origin == origin && mv.target == target && mv.get_promotion() == promotion) {
+            return None;
+        }
+        let index = (origin / 8 * 16 + origin % 8) as usize;
+        let is_pawn = self.board.mailbox.get_piece_at(index).is_some_and(|piece| piece.get_piece() == Pieces::PAWN);
+        let mv = self.board.play_move(origin, target, promotion, true)?;
+        if is_pawn || mv.is_capture() || mv.is_en_passant() {
+            self.halfmove_clock = 0;
+            self.keys.clear();
+        } else {
+            self.halfmove_clock += 1;
+        }
+        self.keys.push(repetition_key(&self.board));
+        self.moves.push(mv.clone());
+        self.outcome = self.rules_outcome();
+        Some(mv)
+    }
+
+    // Ends the game for a reason outside the board, such as a resignation
+    pub fn end(&mut self, outcome: Outcome) {
+        if self.outcome.is_none() {
+            self.outcome = Some(outcome);
+        }
+    }
+
+    // The team ran out of time: it loses unless the opponent cannot win by any series of legal moves
+    pub fn flag(&mut self, team: Team) {
+        let opponent = get_opposite_team(team);
+        self.end(if can_win(&self.board, opponent) {
+            Outcome::win(opponent, EndReason::Timeout)
+        } else {
+            Outcome::draw(EndReason::TimeoutVsInsufficientMaterial)
+        });
+    }
+
+    fn rules_outcome(&self) -> Option<Outcome> {
+        let team = self.board.state.team_to_play;
+        let variant = self.board.state.variant;
+        if let Some(winner) = variant.winner(&self.board) {
+            let reason = match variant {
+                Variant::KingOfTheHill => EndReason::KingOfTheHill,
+                Variant::ThreeCheck => EndReason::ThreeChecks,
+                Variant::Atomic => EndReason::KingExploded,
+                _ => EndReason::HordeCaptured
+            };
+            return Some(Outcome::win(winner, reason));
+        }
+        if self.board.generate_moves(team).is_empty() {
+            return Some(if self.board.is_in_check(team) {
+                Outcome::win(get_opposite_team(team), EndReason::Checkmate)
+            } else {
+                Outcome::draw(EndReason::Stalemate)
+            });
+        }
+        let current = self.keys[self.keys.len() - 1];
+        if self.keys.iter().filter(|key| **key == current).count() >= 3 {
+            return Some(Outcome::draw(EndReason::Repetition));
+        }
+        if self.halfmove_clock >= 100 {
+            return Some(Outcome::draw(EndReason::FiftyMoves));
+        }
+        if is_insufficient_material(&self.board) {
+            return Some(Outcome::draw(EndReason::InsufficientMaterial));
+        }
+        None
+    }
+}
+
+// The position and, in Three-check, the checks given so far, which a repetition must match too
+type RepetitionKey = (u64, [u8; 2]);
+
+fn repetition_key(board: &ChessBoard) -> RepetitionKey {
+    (polyglot_key(board), board.state.checks)
+}
+
+// Every legal move of the side to play
+pub fn legal_moves(board: &ChessBoard) -> Vec<CompletedMove> {
+    board.generate_moves(board.state.team_to_play)
+}
+
+// Leaf positions of the legal move tree, the usual check of move generation against known counts
+pub fn perft(board: &mut ChessBoard, depth: u8) -> u64 {
+    let moves = legal_moves(board);
+    if depth <= 1 {
+        return if depth == 0 { 1 } else { moves.len() as u64 };
+    }
+    let mut nodes = 0;
+    for mv in moves {
+        let state = board.state;
+        if let Some(played) = board.play_move(mv.origin, mv.target, mv.get_promotion(), true) {
+            nodes += perft(board, depth - 1);
+            board.undo_move(&played);
+        }
+        board.state = state;
+    }
+    nodes
+}
+
+// Neither side can win any more. In standard chess that is bare kings or a single minor piece left on the board.
+fn is_insufficient_material(board: &ChessBoard) -> bool {
+    match board.state.variant {
+        // A lone king still wins by reaching the hill, and the horde's last pieces can always be taken
+        Variant::KingOfTheHill | Variant::Horde => return false,
+        // Any piece but a king can give checks, or capture and blow up the king next to its target
+        Variant::ThreeCheck | Variant::Atomic => return [Teams::WHITE, Teams::BLACK].into_iter().all(|team| !has_pieces(board, team)),
+        Variant::Standard => {}
+    }
+    let mut minors = 0;
+    for team in [Teams::WHITE, Teams::BLACK] {
+        for piece in [Pieces::PAWN, Pieces::ROOK, Pieces::QUEEN] {
+            if board.bits.get_pieces(team, piece).0 != 0 {
+                return false;
+            }
+        }
+        minors += board.bits.get_pieces(team, Pieces::BISHOP).0.count_ones() + board.bits.get_pieces(team, Pieces::KNIGHT).0.count_ones();
+    }
+    minors <= 1
+}
+
+// Whether some series of legal moves, however unlikely, ends with the team winning. In standard chess the
+// opponent's own pieces count since they can block their king in.
+fn can_win(board: &ChessBoard, team: Team) -> bool {
+    match board.state.variant {
+        Variant::KingOfTheHill => return board.bits.get_pieces(team, Pieces::KING).0 != 0,
+        Variant::ThreeCheck | Variant::Atomic => return has_pieces(board, team),
+        Variant::Horde if team == Teams::BLACK => return true,
+        _ => {}
+    }
+    let opponent = get_opposite_team(team);
+    let pieces = |team: Team, piece: Piece| board.bits.get_pieces(team, piece).0;
+    if [Pieces::PAWN, Pieces::ROOK, Pieces::QUEEN].into_iter().any(|piece| pieces(team, piece) != 0) {
+        return true;
+    }
+    let knights = pieces(team, Pieces::KNIGHT).count_ones();
+    let bishops = pieces(team, Pieces::BISHOP);
+    let opponent_pieces = [Pieces::PAWN, Pieces::BISHOP, Pieces::KNIGHT, Pieces::ROOK, Pieces::QUEEN]
+        .into_iter()
+        .any(|piece| pieces(opponent, piece) != 0);
+    match (knights, bishops.count_ones()) {
+        (0, 0) => false,
+        // A lone knight mates only with the defending king boxed in by its own pieces
+        (1, 0) => opponent_pieces,
+        // Bishops on one colour need a piece that can stand on the other colour, or a pawn that may become one
+        (0, _) if bishops & DARK_SQUARES == 0 || bishops & !DARK_SQUARES == 0 => {
+            let own_colour = if bishops & DARK_SQUARES != 0 { DARK_SQUARES } else { !DARK_SQUARES };
+            pieces(opponent, Pieces::PAWN) != 0
+                || pieces(opponent, Pieces::KNIGHT) != 0
+                || pieces(opponent, Pieces::BISHOP) & !own_colour != 0
+                || pieces(opponent, Pieces::ROOK) != 0
+                || pieces(opponent, Pieces::QUEEN) != 0
+        }
+        _ => true
+    }
+}
+
+// Pieces besides the king
+fn has_pieces(board: &ChessBoard, team: Team) -> bool {
+    board.bits.get_team_pieces(team).0 & !board.bits.get_pieces(team, Pieces::KING).0 != 0
+}
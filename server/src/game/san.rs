@@ -0,0 +1,158 @@
+use crate::board::{CompletedMove, get_opposite_team, Piece, Pieces};
+use crate::board::board::ChessBoard;
+use crate::board::state::CastlingSides;
+use crate::game::square::{index_to_square, promotion_piece};
+use crate::game::Vector;
+use crate::math::kings::{CASTLED_KING_FILES, castling_blocks, castling_king_path};
+
+// Resolves a move in standard algebraic notation (e.g. Nbd7, exd6, O-O+) against the legal moves of the position
+pub fn parse_san(board: &ChessBoard, san: &str) -> Option<CompletedMove> {
+    let san = san.trim_end_matches(['+', '#', '!', '?']);
+    match san {
+        "O-O" | "0-0" => return castling_move(board, CastlingSides::KINGSIDE),
+        "O-O-O" | "0-0-0" => return castling_move(board, CastlingSides::QUEENSIDE),
+        _ => {}
+    }
+    // The promotion piece comes last, written e8=Q or e8Q
+    let (san, promotion) = match san.char_indices().last()? {
+        (index, letter) if letter.is_ascii_uppercase() && index > 0 => (san[..index].trim_end_matches('='), Some(promotion_piece(letter)?)),
+        _ => (san, None)
+    };
+
+    let (piece, rest) = match san.chars().next()? {
+        'N' => (Pieces::KNIGHT, &san[1..]),
+        'B' => (Pieces::BISHOP, &san[1..]),
+        'R' => (Pieces::ROOK, &san[1..]),
+        'Q' => (Pieces::QUEEN, &san[1..]),
+        'K' => (Pieces::KING, &san[1..]),
+        _ => (Pieces::PAWN, san)
+    };
+    let rest: Vec<char> = rest.chars().filter(|c| *c != 'x' && *c != '-').collect();
+    if rest.len() < 2 {
+        return None;
+    }
+    let target = parse_square(rest[rest.len() - 2], rest[rest.len() - 1])?;
+    let mut origin_file = None;
+    let mut origin_rank = None;
+    for c in &rest[..rest.len() - 2] {
+        match c {
+            'a'..='h' => origin_file = Some(*c as u8 - b'a'),
+            '1'..='8' => origin_rank = Some(*c as u8 - b'1'),
+            _ => return None
+        }
+    }
+
+    let mut candidates = board.generate_moves(board.state.team_to_play).into_iter().filter(|mv| {
+        mv.target == target
+            && mv.get_promotion() == promotion
+            && piece_at(board, mv.origin) == Some(piece)
+            && origin_file.is_none_or(|file| mv.origin % 8 == file)
+            && origin_rank.is_none_or(|rank| mv.origin / 8 == rank)
+    });
+    let mv = candidates.next()?;
+    // Ambiguous notation is rejected rather than guessed
+    if candidates.next().is_some() {
+        return None;
+    }
+    Some(mv)
+}
+
+// Standard algebraic notation of a legal move in this position, with the check or mate suffix
+pub fn to_san(board: &ChessBoard, mv: &CompletedMove) -> String {
+    let team = board.state.team_to_play;
+    let piece = piece_at(board, mv.origin).unwrap_or(Pieces::PAWN);
+    let castling = mv.is_castling() || (piece == Pieces::KING && !board.state.chess960 && mv.origin.abs_diff(mv.target) == 2);
+    let mut san = if castling {
+        if mv.target > mv.origin { "O-O".to_string() } else { "O-O-O".to_string() }
+    } else {
+        let capture = piece_at(board, mv.target).is_some() || (piece == Pieces::PAWN && mv.origin % 8 != mv.target % 8);
+        let mut san = String::new();
+        if piece == Pieces::PAWN {
+            if capture {
+                san.push((b'a' + mv.origin % 8) as char);
+            }
+        } else {
+            san.push(['P', 'B', 'N', 'R', 'Q', 'K'][piece]);
+            // Disambiguate by file, then rank, then both, among the same pieces reaching the same square
+            let rivals: Vec<u8> = board.generate_moves(team).iter()
+                .filter(|other| other.target == mv.target && other.origin != mv.origin && piece_at(board, other.origin) == Some(piece))
+                .map(|other| other.origin)
+                .collect();
+            if !rivals.is_empty() {
+                let file_unique = rivals.iter().all(|origin| origin % 8 != mv.origin % 8);
+                let rank_unique = rivals.iter().all(|origin| origin / 8 != mv.origin / 8);
+                if file_unique || !rank_unique {
+                    san.push((b'a' + mv.origin % 8) as char);
+                }
+                if !file_unique {
+                    san.push((b'1' + mv.origin / 8) as char);
+                }
+            }
+        }
+        if capture {
+            san.push('x');
+        }
+        san.push_str(&index_to_square(mv.target));
+        if let Some(promotion) = mv.get_promotion() {
+            san.push('=');
+            san.push(['P', 'B', 'N', 'R', 'Q', 'K'][promotion]);
+        }
+        san
+    };
+
+    let mut after = board.clone();
+    after.play_move(mv.origin, mv.target, mv.get_promotion(), true);
+    let opponent = get_opposite_team(team);
+    if after.is_in_check(opponent) {
+        san.push(if after.generate_moves(opponent).is_empty() { '#' } else { '+' });
+    }
+    san
+}
+
+// Castling for the side to play when allowed: rights, empty path and no attacked square. Chess960 writes it
+// as the king taking its own rook, classical chess as the king's two-square step.
+pub fn castling_move(board: &ChessBoard, side: usize) -> Option<CompletedMove> {
+    let team = board.state.team_to_play;
+    if !board.state.castling_rights.is_allowed(team, side) {
+        return None;
+    }
+    let rank = team as u8 * 7;
+    let king_bits = board.bits.get_pieces(team, Pieces::KING).0 & (0xFF << (rank * 8));
+    if king_bits == 0 {
+        return None;
+    }
+    let king = king_bits.trailing_zeros() as u8;
+    let rook_file = board.state.castling_files[team][side];
+    let rook = rank * 8 + rook_file;
+    if piece_at(board, rook) != Some(Pieces::ROOK) {
+        return None;
+    }
+    let occupied = !board.bits.empty_squares().0;
+    if occupied & castling_blocks(team, king % 8, rook_file, side) != 0 {
+        return None;
+    }
+    let attacked = board.attacks(get_opposite_team(team)).0;
+    if attacked & castling_king_path(team, king % 8, side) != 0 {
+        return None;
+    }
+    let target = if board.state.chess960 { rook } else { rank * 8 + CASTLED_KING_FILES[side] };
+    // The castling rook may have been shielding the king's destination from a rook or queen along the rank
+    let mut after = board.clone();
+    let mv = after.play_move(king, target, None, true)?;
+    if !mv.is_castling() || after.is_in_check(team) {
+        return None;
+    }
+    Some(mv)
+}
+
+fn parse_square(file: char, rank: char) -> Option<u8> {
+    if !('a'..='h').contains(&file) || !('1'..='8').contains(&rank) {
+        return None;
+    }
+    Some((rank as u8 - b'1') * 8 + (file as u8 - b'a'))
+}
+
+fn piece_at(board: &ChessBoard, square: u8) -> Option<Piece> {
+    let index = Vector::from_bit_position_index(square as usize).mail_box_index();
+    board.mailbox.get_piece_at(index).map(|piece| piece.get_piece())
+}
@@ -1,15 +1,15 @@
 use std::fmt::Display;
 
-use crate::board::PossibleMove;
+use crate::board::{CompletedMove, Piece, Pieces, PossibleMove};
 use crate::game::Vector;
 
 pub fn square_to_vector(square: &str) -> Vector {
     let mut chars = square.chars();
     let file = chars.next().unwrap();
     let rank = chars.next().unwrap();
-    let file = file as u8 - 'a' as u8;
-    let rank = rank as u8 - '1' as u8;
-    Vector::new(rank, file)
+    let file = file as u8 - b'a';
+    let rank = rank as u8 - b'1';
+    Vector::new(file, rank)
 }
 
 impl Display for PossibleMove {
@@ -21,4 +21,47 @@ impl Display for PossibleMove {
         result.push_str(&to);
         write!(f, "{}", result)
     }
-}
\ No newline at end of file
+}
+
+// Bit position index of a square such as e4, None when it is not one
+pub fn square_to_index(square: &str) -> Option<u8> {
+    let &[file, rank] = square.as_bytes() else { return None };
+    ((b'a'..=b'h').contains(&file) && (b'1'..=b'8').contains(&rank)).then(|| (rank - b'1') * 8 + file - b'a')
+}
+
+// Origin, target and promotion piece of a move in long algebraic notation, such as e7e8q
+pub fn parse_long_algebraic(text: &str) -> Option<(u8, u8, Option<Piece>)> {
+    let origin = square_to_index(text.get(0..2)?)?;
+    let target = square_to_index(text.get(2..4)?)?;
+    let promotion = match text[4..].chars().next() {
+        Some(letter) => Some(promotion_piece(letter)?),
+        None => None
+    };
+    Some((origin, target, promotion))
+}
+
+// Piece a promotion letter stands for, in either case
+pub fn promotion_piece(letter: char) -> Option<Piece> {
+    match letter.to_ascii_lowercase() {
+        'n' => Some(Pieces::KNIGHT),
+        'b' => Some(Pieces::BISHOP),
+        'r' => Some(Pieces::ROOK),
+        'q' => Some(Pieces::QUEEN),
+        _ => None
+    }
+}
+
+pub fn index_to_square(index: u8) -> String {
+    format!("{}{}", (b'a' + index % 8) as char, (b'1' + index / 8) as char)
+}
+
+// Long algebraic notation, as used by UCI (e.g. e2e4 or e7e8q)
+impl Display for CompletedMove {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}{}", index_to_square(self.origin), index_to_square(self.target))?;
+        if let Some(promotion) = self.get_promotion() {
+            write!(f, "{}", ['p', 'b', 'n', 'r', 'q', 'k'][promotion])?;
+        }
+        Ok(())
+    }
+}
@@ -1,7 +1,13 @@
-use rand::Rng;
-use crate::board::{Pieces, Team, Teams};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use crate::board::{Pieces, Teams};
 use crate::board::board::ChessBoard;
 use crate::board::state::CastlingSides;
+use crate::board::variant::{THREE_CHECKS, Variant};
+
+// Keys come from a fixed seed so searches, and the bench node count, are the same from run to run. ChaCha
+// promises the same numbers for a seed in every release, which StdRng does not.
+const SEED: u64 = 0x0C41_55A0;
 
 #[derive(Clone)]
 pub struct ZobristHash {
@@ -9,17 +15,22 @@ pub struct ZobristHash {
     turn_key: u64,
     castling_keys: [u64; 4],
     en_passant_keys: [u64; 8],
+    #[allow(dead_code)]
     fifty_move_rule_key: u64,
+    // Positions of different variants share the transposition table, standard chess hashes as before
+    variant_keys: [u64; Variant::ALL.len()],
+    // By team and checks given, none given hashing as before
+    check_keys: [[u64; THREE_CHECKS as usize + 1]; 2],
 }
 
 impl ZobristHash {
     pub fn new() -> Self {
-        let mut rng = rand::thread_rng();
+        let mut rng = ChaCha8Rng::seed_from_u64(SEED);
         let mut piece_keys = [[[0; 64]; 6]; 2];
-        for team in 0..2 {
-            for piece in 0..6 {
-                for square in 0..64 {
-                    piece_keys[team][piece][square] = rng.gen();
+        for team_keys in &mut piece_keys {
+            for square_keys in team_keys.iter_mut() {
+                for key in square_keys.iter_mut() {
+                    *key = rng.gen();
                 }
             }
         }
@@ -33,7 +44,18 @@ impl ZobristHash {
             *key = rng.gen();
         }
         let fifty_move_rule_key = rng.gen();
-        ZobristHash { piece_keys, turn_key, castling_keys, en_passant_keys, fifty_move_rule_key }
+        // Drawn after the others so the keys above stay the same
+        let mut variant_keys = [0; Variant::ALL.len()];
+        for key in variant_keys.iter_mut().skip(1) {
+            *key = rng.gen();
+        }
+        let mut check_keys = [[0; THREE_CHECKS as usize + 1]; 2];
+        for team_keys in &mut check_keys {
+            for key in team_keys.iter_mut().skip(1) {
+                *key = rng.gen();
+            }
+        }
+        ZobristHash { piece_keys, turn_key, castling_keys, en_passant_keys, fifty_move_rule_key, variant_keys, check_keys }
     }
 
     pub fn hash(&self, board: &ChessBoard) -> u64 {
@@ -69,7 +91,12 @@ impl ZobristHash {
         }
 
         if let Some(en_passant_square) = board.state.en_passant_square {
-            hash ^= self.en_passant_keys[(en_passant_square % 8) as usize];
+            hash ^= self.en_passant_keys[en_passant_square % 8];
+        }
+
+        hash ^= self.variant_keys[board.state.variant as usize];
+        for team in [Teams::WHITE, Teams::BLACK] {
+            hash ^= self.check_keys[team][(board.state.checks[team] as usize).min(THREE_CHECKS as usize)];
         }
 
         // Uncomment if fifty_move_rule_key is needed
@@ -79,4 +106,24 @@ impl ZobristHash {
 
         hash
     }
+
+    // Only the pawn placement, used to key the evaluation's pawn structure cache
+    pub fn pawn_hash(&self, board: &ChessBoard) -> u64 {
+        let mut hash = 0;
+        for team in [Teams::WHITE, Teams::BLACK] {
+            let mut bits = board.bits.get_pieces(team, Pieces::PAWN).0;
+            while bits != 0 {
+                let square = bits.trailing_zeros() as usize;
+                hash ^= self.piece_keys[team][Pieces::PAWN][square];
+                bits &= bits - 1;
+            }
+        }
+        hash
+    }
+}
+
+impl Default for ZobristHash {
+    fn default() -> Self {
+        Self::new()
+    }
 }
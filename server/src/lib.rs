@@ -0,0 +1,66 @@
+use crate::board::{BitBoard, BitPosition, GamePiece, MailBox};
+
+// The chess engine and everything around it, shared by the server binary and the benchmarks
+pub mod board;
+pub mod book;
+pub mod game;
+pub mod math;
+pub mod engine;
+pub mod hash;
+pub mod storage;
+
+pub fn print(board: &BitBoard) {
+    for rank in (0..8).rev() {
+        for file in 0..8 {
+            let index = rank * 8 + file;
+            if board.0 & (1 << index) != 0 {
+                print!("1 ");
+            } else {
+                print!("0 ");
+            }
+        }
+        println!();
+    }
+}
+
+pub fn printm(mail_box: &MailBox) {
+    for rank in (0..8).rev() {
+        for file in 0..8 {
+            let index = rank * 16 + file;
+            if let Some(piece) = mail_box.get_piece_at(index) {
+                print!("{} ", piece.get_piece());
+            } else {
+                print!("- ");
+            }
+        }
+        println!();
+    }
+}
+
+// This method will print a BitPosition in teh same way printm prints a MailBox
+// Instead of print (BitBoard), it will display the actual number of the pieces
+pub fn printbp(bit_position: &BitPosition) {
+    for rank in (0..8).rev() {
+        for file in 0..8 {
+            let index = rank * 8 + file;
+            let mut piece = None;
+            for team in 0..2 {
+                for piece_type in 0..6 {
+                    if bit_position.bb_pieces[team][piece_type].0 & (1 << index) != 0 {
+                        piece = Some(GamePiece::from(piece_type, team));
+                        break;
+                    }
+                }
+                if piece.is_some() {
+                    break;
+                }
+            }
+            if let Some(piece) = piece {
+                print!("{} ", piece.get_piece());
+            } else {
+                print!("- ");
+            }
+        }
+        println!();
+    }
+}
\ No newline at end of file
@@ -1,33 +1,123 @@
-use std::sync::{Arc, RwLock};
+use std::sync::Arc;
+use std::time::Duration;
 
 use axum::Router;
-use axum::routing::post;
+use axum::routing::{get, post};
 use tokio::net::TcpListener;
 use tower::ServiceBuilder;
 use tower_http::add_extension::AddExtensionLayer;
 use tower_http::cors::CorsLayer;
 use tower_http::trace::TraceLayer;
 
-use crate::board::{BitBoard, BitPosition, GamePiece, MailBox, Pieces, Teams};
-use crate::engine::ChessEngine;
+use crate::book::BookSettings;
+use crate::book::polyglot::PolyglotBook;
+use crate::engine::evaluator::{AnyEvaluator, EvaluatorKind, EvaluatorRegistry};
 use crate::engine::minimax::MinimaxEngine;
-use crate::server::{get_best_move, get_piece_moves, get_team_moves, get_threatened_squares};
+use crate::engine::nnue::Network;
+use crate::engine::transposition::TranspositionTable;
+use crate::server::archive;
+use crate::server::games::{self, Lobby};
+use crate::storage::Storage;
+use crate::server::{get_analysis, get_best_move, get_piece_moves, get_team_moves, get_threatened_squares};
+
+pub use caissa::{board, book, engine, game, hash, math, storage};
 
-pub mod board;
-pub mod game;
-pub mod math;
-pub mod engine;
-pub mod hash;
 mod server;
+mod tools;
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let result = match args.get(1).map(String::as_str) {
+        Some("tune") => tools::tune::run(&args[2..]),
+        Some("bench") => tools::bench::run(&args[2..]),
+        Some("book") => tools::book::run(&args[2..]),
+        Some("match") => tools::matches::run(&args[2..]),
+        Some("perft") => tools::perft::run(&args[2..]),
+        Some("suite") => tools::suite::run(&args[2..]),
+        Some("uci") => tools::uci::run(&args[2..]),
+        _ => {
+            serve();
+            Ok(())
+        }
+    };
+    if let Err(error) = result {
+        eprintln!("{}", error);
+        std::process::exit(1);
+    }
+}
 
 #[tokio::main]
-async fn main() {
-    let state = AppState {
-        engine: MinimaxEngine::new(),
-        depth: 6
+async fn serve() {
+    // CAISSA_NNUE points at a network weights file, CAISSA_EVALUATOR picks the default evaluator
+    let network = std::env::var("CAISSA_NNUE").ok().map(|path| {
+        Network::load(&path).unwrap_or_else(|error| panic!("Could not load network {}: {}", path, error))
+    });
+    let default_evaluator = match std::env::var("CAISSA_EVALUATOR") {
+        Ok(name) => name.parse().unwrap(),
+        Err(_) if network.is_some() => EvaluatorKind::Nnue,
+        Err(_) => EvaluatorKind::default()
+    };
+    let evaluators = EvaluatorRegistry::new(default_evaluator, network);
+    let evaluator = evaluators.create(default_evaluator).expect("CAISSA_EVALUATOR=nnue requires CAISSA_NNUE");
+    let mut engine = MinimaxEngine::with_evaluator(evaluator);
+    engine.set_info_callback(|info| tracing::info!("{}", info));
+    // CAISSA_THREADS sets the threads of each search. Requests already search side by side, each on a fork of
+    // the engine, so one thread apiece keeps a busy server from running more threads than it has cores.
+    let threads = match std::env::var("CAISSA_THREADS") {
+        Ok(threads) => threads.parse().unwrap(),
+        Err(_) => 1
     };
+    engine.set_threads(threads);
+    // CAISSA_BOOK points at a Polyglot .bin book, CAISSA_BOOK_ENABLED, _DEPTH and _SELECTION tune how it is used
+    let book = std::env::var("CAISSA_BOOK").ok().map(|path| {
+        PolyglotBook::load(&path).unwrap_or_else(|error| panic!("Could not load book {}: {}", path, error))
+    });
+    let mut book_settings = BookSettings::default();
+    if let Ok(depth) = std::env::var("CAISSA_BOOK_DEPTH") {
+        book_settings.max_ply = depth.parse().unwrap();
+    }
+    if let Ok(selection) = std::env::var("CAISSA_BOOK_SELECTION") {
+        book_settings.selection = selection.parse().unwrap();
+    }
+    book_settings.enabled = std::env::var("CAISSA_BOOK_ENABLED").map_or(true, |enabled| enabled != "false");
+    engine.set_book(book.map(Arc::new));
+    engine.set_book_settings(book_settings);
+    // CAISSA_SYZYGY lists directories of Syzygy tables, separated by ':'
+    #[cfg(feature = "syzygy")]
+    if let Ok(path) = std::env::var("CAISSA_SYZYGY") {
+        let tablebase = engine::syzygy::Tablebase::open(&path)
+            .unwrap_or_else(|error| panic!("Could not open tablebases {}: {}", path, error));
+        tracing::info!("Probing Syzygy tables up to {} pieces", tablebase.max_pieces());
+        engine.set_tablebase(Some(Arc::new(tablebase)));
+    }
+    // CAISSA_SEARCH_TIMEOUT_MS bounds how long a best move request may search
+    let search_timeout = std::env::var("CAISSA_SEARCH_TIMEOUT_MS").map_or(ServerConfig::DEFAULT_SEARCH_TIMEOUT, |timeout| {
+        Duration::from_millis(timeout.parse().unwrap())
+    });
+    // CAISSA_DEPTH is the depth searched when a request sets no limit, CAISSA_MAX_* cap what requests may ask for
+    let env_or = |name: &str, default: u64| std::env::var(name).map_or(default, |value| value.parse().unwrap());
+    let config = ServerConfig {
+        search_timeout,
+        default_depth: env_or("CAISSA_DEPTH", 6) as u8,
+        max_depth: env_or("CAISSA_MAX_DEPTH", 20) as u8,
+        max_movetime: Duration::from_millis(env_or("CAISSA_MAX_MOVETIME_MS", search_timeout.as_millis() as u64)),
+        max_nodes: env_or("CAISSA_MAX_NODES", 50_000_000),
+        max_multipv: env_or("CAISSA_MAX_MULTIPV", 8) as usize
+    };
+    // CAISSA_DATABASE is the SQLite file finished games and analyses are kept in, :memory: keeps nothing
+    let database = std::env::var("CAISSA_DATABASE").unwrap_or("caissa.db".to_string());
+    let storage = Storage::open(&database).unwrap_or_else(|error| panic!("Could not open database {}: {}", database, error));
+    let state = AppState::new(engine, &evaluators, storage);
     let app = Router::new()
         .route("/api/playground/moves/best", post(get_best_move))
+        .route("/api/analysis", post(get_analysis))
+        .route("/api/analysis/live", get(server::socket::analysis_socket))
+        .route("/api/games", get(games::list_games).post(games::create_game))
+        .route("/api/games/:id", get(games::get_game))
+        .route("/api/games/:id/join", post(games::join_game))
+        .route("/api/games/:id/socket", get(games::game_socket))
+        .route("/api/archive/games", get(archive::search_games))
+        .route("/api/archive/games/:id", get(archive::get_game))
         .route("/api/playground/moves/team", post(get_team_moves))
         .route("/api/playground/moves/piece", post(get_piece_moves))
         .route("/api/playground/moves/threats", post(get_threatened_squares))
@@ -35,78 +125,64 @@ async fn main() {
         .layer(
             ServiceBuilder::new()
                 .layer(TraceLayer::new_for_http())
-                .layer(AddExtensionLayer::new(Arc::new(RwLock::new(state))))
+                .layer(AddExtensionLayer::new(Arc::new(state)))
+                .layer(AddExtensionLayer::new(Arc::new(config)))
                 .into_inner()
         );
     tracing_subscriber::fmt()
-        .with_max_level(tracing::Level::ERROR)
+        .with_max_level(tracing::Level::INFO)
         .init();
     let listener = TcpListener::bind("127.0.0.1:3000").await.unwrap();
     axum::serve(listener, app).await.unwrap();
 }
 
+// Requests search with forks of these engines, so they run side by side without locking
 pub struct AppState {
-    pub engine: MinimaxEngine,
-    pub depth: u8
+    // One engine per evaluator the server can run, each with its own transposition table since
+    // scores from different evaluators do not mix
+    engines: Vec<MinimaxEngine<AnyEvaluator>>,
+    pub default_evaluator: EvaluatorKind,
+    pub lobby: Lobby,
+    pub storage: Arc<Storage>
 }
 
-type SharedState = Arc<RwLock<AppState>>;
-
-unsafe impl Send for AppState {}
-unsafe impl Sync for AppState {}
-
-pub fn print(board: &BitBoard) {
-    for rank in (0..8).rev() {
-        for file in 0..8 {
-            let index = rank * 8 + file;
-            if board.0 & (1 << index) != 0 {
-                print!("1 ");
-            } else {
-                print!("0 ");
+impl AppState {
+    fn new(engine: MinimaxEngine<AnyEvaluator>, evaluators: &EvaluatorRegistry, storage: Storage) -> Self {
+        let default_evaluator = engine.evaluator().kind();
+        let mut engines = Vec::new();
+        for kind in [EvaluatorKind::Material, EvaluatorKind::HandCrafted, EvaluatorKind::Nnue] {
+            if kind == default_evaluator {
+                continue;
+            }
+            if let Some(evaluator) = evaluators.create(kind) {
+                let mut other = engine.fork();
+                other.set_hash_size(TranspositionTable::DEFAULT_SIZE_MB);
+                other.set_evaluator(evaluator);
+                engines.push(other);
             }
         }
-        println!();
+        engines.push(engine);
+        AppState { engines, default_evaluator, lobby: Lobby::default(), storage: Arc::new(storage) }
     }
-}
 
-pub fn printm(mail_box: &MailBox) {
-    for rank in (0..8).rev() {
-        for file in 0..8 {
-            let index = rank * 16 + file;
-            if let Some(piece) = mail_box.get_piece_at(index) {
-                print!("{} ", piece.get_piece());
-            } else {
-                print!("- ");
-            }
-        }
-        println!();
+    // None when the evaluator is not available on this server
+    pub fn engine(&self, kind: EvaluatorKind) -> Option<&MinimaxEngine<AnyEvaluator>> {
+        self.engines.iter().find(|engine| engine.evaluator().kind() == kind)
     }
 }
 
-// This method will print a BitPosition in teh same way printm prints a MailBox
-// Instead of print (BitBoard), it will display the actual number of the pieces
-pub fn printbp(bit_position: &BitPosition) {
-    for rank in (0..8).rev() {
-        for file in 0..8 {
-            let index = rank * 8 + file;
-            let mut piece = None;
-            for team in 0..2 {
-                for piece_type in 0..6 {
-                    if bit_position.bb_pieces[team][piece_type].0 & (1 << index) != 0 {
-                        piece = Some(GamePiece::from(piece_type, team));
-                        break;
-                    }
-                }
-                if piece.is_some() {
-                    break;
-                }
-            }
-            if let Some(piece) = piece {
-                print!("{} ", piece.get_piece());
-            } else {
-                print!("- ");
-            }
-        }
-        println!();
-    }
-}
\ No newline at end of file
+type SharedState = Arc<AppState>;
+
+// Settings fixed at startup, shared by every request
+pub struct ServerConfig {
+    pub search_timeout: Duration,
+    pub default_depth: u8,
+    pub max_depth: u8,
+    pub max_movetime: Duration,
+    pub max_nodes: u64,
+    pub max_multipv: usize
+}
+
+impl ServerConfig {
+    pub const DEFAULT_SEARCH_TIMEOUT: Duration = Duration::from_secs(30);
+}
@@ -1,19 +1,13 @@
-use crate::board::{BitBoard, BitPosition, Pieces, Team, Teams};
+use crate::board::{BitBoard, BitPosition, Pieces, Team};
 use crate::board::state::{CastlingRights, CastlingSides};
 
-pub const WHITE_QUEENSIDE_KING_CASTLE: u64 = 0x4;
-pub const WHITE_KINGSIDE_KING_CASTLE: u64 = 0x40;
-pub const BLACK_QUEENSIDE_KING_CASTLE: u64 = 0x400000000000000;
-pub const BLACK_KINGSIDE_KING_CASTLE: u64 = 0x4000000000000000;
-pub const WHITE_QUEENSIDE_CASTLE_BLOCKS: u64 = 0xE;
-pub const WHITE_KINGSIDE_CASTLE_BLOCKS: u64 = 0x60;
-pub const BLACK_QUEENSIDE_CASTLE_BLOCKS: u64 = 0xE00000000000000;
-pub const BLACK_KINGSIDE_CASTLE_BLOCKS: u64 = 0x6000000000000000;
+// Files the king and rook end on after castling, by side
+pub const CASTLED_KING_FILES: [u8; 2] = [6, 2];
+pub const CASTLED_ROOK_FILES: [u8; 2] = [5, 3];
 
 pub fn calculate_king_moves(position: &BitPosition, team: Team) -> BitBoard {
     let king = position.get_pieces(team, Pieces::KING);
-    let king_moves = mask_king_moves(king.0, None);
-    king_moves
+    mask_king_moves(king.0, None)
 }
 
 pub fn mask_king_moves(king_bit: u64, opponent_threats: Option<&BitBoard>) -> BitBoard {
@@ -26,9 +20,9 @@ pub fn mask_king_moves(king_bit: u64, opponent_threats: Option<&BitBoard>) -> Bi
     if king_bit >> 8 != 0 { moves |= king_bit >> 8; }
     if king_bit << 8 != 0 { moves |= king_bit << 8; }
     if (king_bit >> 9) & not_h_file != 0 { moves |= king_bit >> 9; }
-    if (king_bit << 7) & not_a_file != 0 { moves |= king_bit << 7; }
+    if (king_bit << 7) & not_h_file != 0 { moves |= king_bit << 7; }
     if (king_bit >> 7) & not_a_file != 0 { moves |= king_bit >> 7; }
-    if (king_bit << 9) & not_h_file != 0 { moves |= king_bit << 9; }
+    if (king_bit << 9) & not_a_file != 0 { moves |= king_bit << 9; }
 
     BitBoard(if let Some(threats) = opponent_threats {
         moves & !threats.0
@@ -37,36 +31,39 @@ pub fn mask_king_moves(king_bit: u64, opponent_threats: Option<&BitBoard>) -> Bi
     })
 }
 
-pub fn calculate_king_castling_moves(castling_rights: &CastlingRights, team: Team, occupied_spaces: &BitBoard) -> BitBoard {
-    let mut castling_moves = BitBoard(0);
+// Squares of the team's back rank between two files, both included
+fn back_rank_span(team: Team, a: u8, b: u8) -> u64 {
+    let (low, high) = (a.min(b), a.max(b));
+    let span = (0xFFu64 >> (7 - high)) & (0xFFu64 << low);
+    span << (team * 56)
+}
 
-    if team == Teams::WHITE && castling_rights.is_allowed(Teams::WHITE, CastlingSides::KINGSIDE) && (occupied_spaces.0 & WHITE_KINGSIDE_CASTLE_BLOCKS) == 0 {
-        castling_moves.0 |= WHITE_KINGSIDE_KING_CASTLE;
-    } else if team == Teams::BLACK && castling_rights.is_allowed(Teams::BLACK, CastlingSides::KINGSIDE) && (occupied_spaces.0 & BLACK_KINGSIDE_CASTLE_BLOCKS) == 0 {
-        castling_moves.0 |= BLACK_KINGSIDE_KING_CASTLE;
-    }
-    if team == Teams::WHITE && castling_rights.is_allowed(Teams::WHITE, CastlingSides::QUEENSIDE) && (occupied_spaces.0 & WHITE_QUEENSIDE_CASTLE_BLOCKS) == 0 {
-        castling_moves.0 |= WHITE_QUEENSIDE_KING_CASTLE;
-    } else if team == Teams::BLACK && castling_rights.is_allowed(Teams::BLACK, CastlingSides::QUEENSIDE) && (occupied_spaces.0 & BLACK_QUEENSIDE_CASTLE_BLOCKS) == 0 {
-        castling_moves.0 |= BLACK_QUEENSIDE_KING_CASTLE;
-    }
+// Squares that must be empty for the king and rook to castle, the two of them aside: every square either
+// crosses or lands on. In classical chess this is f1 and g1, or b1, c1 and d1.
+pub fn castling_blocks(team: Team, king_file: u8, rook_file: u8, side: usize) -> u64 {
+    let path = back_rank_span(team, king_file, CASTLED_KING_FILES[side]) | back_rank_span(team, rook_file, CASTLED_ROOK_FILES[side]);
+    path & !back_rank_span(team, king_file, king_file) & !back_rank_span(team, rook_file, rook_file)
+}
 
-    castling_moves
+// Squares the king stands on, crosses and lands on while castling, none of which may be attacked
+pub fn castling_king_path(team: Team, king_file: u8, side: usize) -> u64 {
+    back_rank_span(team, king_file, CASTLED_KING_FILES[side])
 }
 
-pub fn mask_king_castling_moves(team: Team, occupied_spaces: &BitBoard) -> BitBoard {
+// Squares the king can castle to with the rights left and nothing in the way, attacks aside
+pub fn calculate_king_castling_moves(castling_rights: &CastlingRights, castling_files: &[[u8; 2]; 2], team: Team, king: u64, occupied_spaces: &BitBoard) -> BitBoard {
     let mut castling_moves = BitBoard(0);
-
-    if team == Teams::WHITE && (occupied_spaces.0 & WHITE_KINGSIDE_CASTLE_BLOCKS) == 0 {
-        castling_moves.0 |= WHITE_KINGSIDE_KING_CASTLE;
-    } else if team == Teams::BLACK && (occupied_spaces.0 & BLACK_KINGSIDE_CASTLE_BLOCKS) == 0 {
-        castling_moves.0 |= BLACK_KINGSIDE_KING_CASTLE;
+    let back_rank = back_rank_span(team, 0, 7);
+    if king & back_rank == 0 {
+        return castling_moves;
     }
-    if team == Teams::WHITE && (occupied_spaces.0 & WHITE_QUEENSIDE_CASTLE_BLOCKS) == 0 {
-        castling_moves.0 |= WHITE_QUEENSIDE_KING_CASTLE;
-    } else if team == Teams::BLACK && (occupied_spaces.0 & BLACK_QUEENSIDE_CASTLE_BLOCKS) == 0 {
-        castling_moves.0 |= BLACK_QUEENSIDE_KING_CASTLE;
+    let king_file = (king.trailing_zeros() % 8) as u8;
+    for side in [CastlingSides::KINGSIDE, CastlingSides::QUEENSIDE] {
+        let rook_file = castling_files[team][side];
+        if castling_rights.is_allowed(team, side) && occupied_spaces.0 & castling_blocks(team, king_file, rook_file, side) == 0 {
+            castling_moves.0 |= back_rank_span(team, CASTLED_KING_FILES[side], CASTLED_KING_FILES[side]);
+        }
     }
 
     castling_moves
-}
\ No newline at end of file
+}
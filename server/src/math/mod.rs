@@ -8,6 +8,7 @@ pub mod kings;
 const NOT_A_FILE: u64 = 0xfefefefefefefefe;
 const NOT_H_FILE: u64 = 0x7f7f7f7f7f7f7f7f;
 
+#[allow(clippy::too_many_arguments)]
 pub fn individually_mask_piece_moves(
     bit: u64,
     piece: Piece,
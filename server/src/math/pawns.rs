@@ -18,6 +18,13 @@ pub fn mask_pawn_march_moves(pawn_bit: u64, empty: &BitBoard, team: Team) -> Bit
     BitBoard(pawn_one_forward_moves | pawn_two_forward_moves)
 }
 
+// Horde: a pawn on its own first rank steps two squares when both are empty
+pub fn mask_first_rank_double_step(pawn_bit: u64, empty: &BitBoard, team: Team) -> BitBoard {
+    let first_rank_mask = if team == Teams::WHITE { 0xFF } else { 0xFF << 56 };
+    let one_forward = shift_bit_up(pawn_bit & first_rank_mask, 8, &team) & empty.0;
+    BitBoard(shift_bit_up(one_forward, 8, &team) & empty.0)
+}
+
 fn shift_bit_up(bit: u64, shift: u8, team: &Team) -> u64 {
     match *team {
         Teams::WHITE => bit << shift,
@@ -33,7 +40,7 @@ pub fn mask_pawn_capture_moves(pawn_bit: u64, enemy_pieces: &BitBoard, en_passan
     let right_pawn = BitBoard(pawn_bit & right_edge_mask).shift_up(7, &team);
     match en_passant_square {
         None => BitBoard((left_pawn.0 | right_pawn.0) & enemy_pieces.0),
-        Some(square) => (left_pawn | right_pawn) & BitBoard(1u64 << square)
+        Some(square) => (left_pawn | right_pawn) & BitBoard(enemy_pieces.0 | 1u64 << square)
     }
 }
 
@@ -61,6 +68,6 @@ pub fn mask_all_pawn_capture_moves(pawns: &BitBoard, enemy_pieces: &BitBoard, en
     let right_pawns = BitBoard(pawns.0 & right_edge_mask).shift_up(7, &team);
     match en_passant_square {
         None => BitBoard((left_pawns | right_pawns).0 & enemy_pieces.0),
-        Some(square) => (left_pawns | right_pawns) & BitBoard(1u64 << square)
+        Some(square) => (left_pawns | right_pawns) & BitBoard(enemy_pieces.0 | 1u64 << square)
     }
 }
\ No newline at end of file
@@ -0,0 +1,37 @@
+use axum::{Extension, Json};
+use axum::extract::{Path, Query};
+use axum::http::StatusCode;
+
+use crate::storage::{GameQuery, GameRecord, GameSummary};
+use crate::SharedState;
+
+// Finished games, newest first
+pub async fn search_games(
+    Extension(state): Extension<SharedState>,
+    Query(query): Query<GameQuery>
+) -> Result<Json<Vec<GameSummary>>, StatusCode> {
+    let storage = state.storage.clone();
+    tokio::task::spawn_blocking(move || storage.search_games(&query))
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .map(Json)
+        .map_err(|error| {
+            tracing::warn!("Could not search games: {}", error);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })
+}
+
+pub async fn get_game(
+    Extension(state): Extension<SharedState>,
+    Path(id): Path<i64>
+) -> Result<Json<GameRecord>, StatusCode> {
+    let storage = state.storage.clone();
+    let game = tokio::task::spawn_blocking(move || storage.game(id))
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .map_err(|error| {
+            tracing::warn!("Could not read game {}: {}", id, error);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    game.map(Json).ok_or(StatusCode::NOT_FOUND)
+}
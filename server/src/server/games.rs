@@ -0,0 +1,660 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use axum::{Extension, Json};
+use axum::extract::{Path, Query};
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::http::StatusCode;
+use axum::response::Response;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use tokio::runtime::Handle;
+use tokio::sync::broadcast;
+
+use crate::board::{CompletedMove, get_opposite_team, Piece, Team, Teams};
+use crate::board::variant::Variant;
+use crate::engine::{ChessEngine, SearchLimits, StopHandle, time};
+use crate::engine::evaluator::AnyEvaluator;
+use crate::engine::minimax::MinimaxEngine;
+use crate::game::clock::{Clock, ClockReading, Delay, TimeControl};
+use crate::game::{chess960, fen};
+use crate::game::pgn::{pgn_date, write_pgn};
+use crate::game::play::{EndReason, Game, Outcome};
+use crate::game::square::promotion_piece;
+use crate::storage::{GameRecord, GameSummary, Storage, unix_time};
+use crate::{ServerConfig, SharedState};
+
+// Events a slow client may fall behind by before it misses some and gets a fresh snapshot
+const EVENT_BUFFER: usize = 64;
+// Slack given to the flag check so it runs after the time is really out
+const FLAG_SLACK: Duration = Duration::from_millis(5);
+// Unfinished games nobody has been connected to for this long are dropped
+const ABANDONED_AFTER: Duration = Duration::from_secs(60 * 60);
+const ANONYMOUS: &str = "Anonymous";
+const ENGINE_NAME: &str = "Caissa";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Side {
+    White,
+    Black
+}
+
+impl Side {
+    fn team(self) -> Team {
+        match self {
+            Side::White => Teams::WHITE,
+            Side::Black => Teams::BLACK
+        }
+    }
+
+    fn of(team: Team) -> Side {
+        if team == Teams::WHITE { Side::White } else { Side::Black }
+    }
+}
+
+// Games hosted by the server, only kept in memory while someone may still play or watch them
+#[derive(Default)]
+pub struct Lobby {
+    rooms: Mutex<HashMap<u64, Arc<Room>>>,
+    next_id: AtomicU64
+}
+
+impl Lobby {
+    fn room(&self, id: u64) -> Option<Arc<Room>> {
+        self.rooms.lock().unwrap().get(&id).cloned()
+    }
+
+    // Drops the rooms no socket is connected to any more once their game is over, the archive keeping
+    // finished games, as well as the games abandoned for ABANDONED_AFTER
+    fn sweep(&self) {
+        let now = Instant::now();
+        self.rooms.lock().unwrap().retain(|_, room| {
+            let state = room.state.lock().unwrap();
+            let idle = state.game.is_over() || now.duration_since(state.last_disconnect) >= ABANDONED_AFTER;
+            room.events.receiver_count() > 0 || !idle
+        });
+    }
+}
+
+struct Room {
+    id: u64,
+    state: Mutex<RoomState>,
+    events: broadcast::Sender<GameEvent>,
+    engine: Option<EnginePlayer>,
+    // Flag watches are also started from the engine's thread, outside the runtime
+    runtime: Handle,
+    // Where the game is archived once it ends
+    storage: Arc<Storage>
+}
+
+// The engine holding one of the seats
+struct EnginePlayer {
+    team: Team,
+    engine: MinimaxEngine<AnyEvaluator>,
+    // Limits for games without a clock
+    untimed: SearchLimits,
+    // Depth bound when the clock decides how long to search
+    max_depth: u8
+}
+
+struct RoomState {
+    game: Game,
+    // Secret each player identifies with, indexed by team. None until someone takes the seat.
+    tokens: [Option<String>; 2],
+    draw_offer: Option<Team>,
+    // Starts with the first move, so nobody loses time before the game gets going
+    clock: Option<Clock>,
+    // Stops the engine's search when the game ends under it
+    engine_stop: Option<StopHandle>,
+    // Player names for the archive, indexed by team
+    names: [String; 2],
+    // Unix seconds
+    started_at: u64,
+    // As the creator gave it, for the PGN
+    time_control: Option<String>,
+    // When the last socket closed, or the room was created
+    last_disconnect: Instant
+}
+
+impl Room {
+    fn snapshot(&self) -> GameSnapshot {
+        let state = self.state.lock().unwrap();
+        state.snapshot(self.id, self.engine.as_ref().map(|player| Side::of(player.team)))
+    }
+}
+
+impl RoomState {
+    fn snapshot(&self, id: u64, engine: Option<Side>) -> GameSnapshot {
+        GameSnapshot {
+            id,
+            engine,
+            clock: self.clock_reading(),
+            variant: self.game.variant(),
+            start_fen: self.game.start_fen().to_string(),
+            fen: self.game.fen(),
+            moves: self.game.moves().to_vec(),
+            turn: Side::of(self.game.team_to_play()),
+            white_joined: self.tokens[Teams::WHITE].is_some(),
+            black_joined: self.tokens[Teams::BLACK].is_some(),
+            draw_offer: self.draw_offer.map(Side::of),
+            outcome: self.game.outcome()
+        }
+    }
+
+    fn seat(&self, token: &str) -> Option<Team> {
+        [Teams::WHITE, Teams::BLACK].into_iter().find(|team| self.tokens[*team].as_deref() == Some(token))
+    }
+
+    fn clock_reading(&self) -> Option<ClockReading> {
+        self.clock.as_ref().map(|clock| clock.reading(self.game.team_to_play(), Instant::now()))
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GameSnapshot {
+    id: u64,
+    // Side played by the engine, if any
+    engine: Option<Side>,
+    clock: Option<ClockReading>,
+    variant: Variant,
+    start_fen: String,
+    fen: String,
+    moves: Vec<CompletedMove>,
+    turn: Side,
+    white_joined: bool,
+    black_joined: bool,
+    draw_offer: Option<Side>,
+    outcome: Option<Outcome>
+}
+
+// Broadcast to everyone watching a game
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum GameEvent {
+    Joined { side: Side },
+    Move { side: Side, #[serde(rename = "move")] mv: CompletedMove, fen: String, clock: Option<ClockReading> },
+    DrawOffered { side: Side },
+    DrawDeclined { side: Side },
+    GameOver { outcome: Outcome }
+}
+
+// Sent to a single socket
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum DirectMessage {
+    State(GameSnapshot),
+    Error { message: String }
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum PlayerMessage {
+    // The promotion piece is a letter, q, r, b or n, and is only sent for a pawn reaching the last rank
+    Move { origin: u8, target: u8, promotion: Option<char> },
+    Resign,
+    OfferDraw,
+    AcceptDraw,
+    DeclineDraw
+}
+
+#[derive(Deserialize)]
+pub struct CreateGameRequest {
+    fen: Option<String>,
+    // Standard chess when not given, the FEN being read under its rules
+    variant: Option<Variant>,
+    // Chess960 start instead of a FEN
+    chess960: Option<Chess960Start>,
+    // Random when not given
+    side: Option<Side>,
+    // PGN TimeControl syntax such as 300+3 or 40/5400+30:1800+30, untimed when not given
+    time_control: Option<String>,
+    delay: Option<Delay>,
+    // Another human by default
+    opponent: Option<Opponent>,
+    // Name the creator plays under
+    name: Option<String>
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(untagged)]
+pub enum Chess960Start {
+    // Scharnagl number of the start position, 0 to 959
+    Index(u16),
+    // A random start when true
+    Random(bool)
+}
+
+#[derive(Deserialize)]
+pub struct JoinGameRequest {
+    name: Option<String>
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Opponent {
+    Human,
+    Engine
+}
+
+#[derive(Serialize)]
+pub struct SeatResponse {
+    id: u64,
+    side: Side,
+    // Identifies the player on the game's socket
+    token: String
+}
+
+#[derive(Deserialize)]
+pub struct SocketQuery {
+    // Players pass their token, spectators connect without one
+    token: Option<String>
+}
+
+fn new_token() -> String {
+    format!("{:032x}", rand::thread_rng().gen::<u128>())
+}
+
+fn player_name(name: Option<String>) -> String {
+    name.map(|name| name.trim().to_string()).filter(|name| !name.is_empty()).unwrap_or(ANONYMOUS.to_string())
+}
+
+// Games still being played, finished ones are found in the archive
+pub async fn list_games(Extension(state): Extension<SharedState>) -> Json<Vec<GameSnapshot>> {
+    state.lobby.sweep();
+    let rooms: Vec<Arc<Room>> = state.lobby.rooms.lock().unwrap().values().cloned().collect();
+    let mut games: Vec<GameSnapshot> = rooms.iter().map(|room| room.snapshot()).filter(|game| game.outcome.is_none()).collect();
+    games.sort_by_key(|game| game.id);
+    Json(games)
+}
+
+pub async fn create_game(
+    Extension(state): Extension<SharedState>,
+    Extension(config): Extension<Arc<ServerConfig>>,
+    Json(payload): Json<CreateGameRequest>
+) -> Result<Json<SeatResponse>, StatusCode> {
+    let variant = payload.variant.unwrap_or_default();
+    // The horde has no back rank to shuffle
+    if variant == Variant::Horde && payload.chess960.is_some() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let start_fen = match (payload.fen, payload.chess960) {
+        (Some(_), Some(_)) => return Err(StatusCode::BAD_REQUEST),
+        (Some(fen), None) => fen,
+        (None, Some(Chess960Start::Index(index))) => chess960::start_fen(index).ok_or(StatusCode::BAD_REQUEST)?,
+        (None, Some(Chess960Start::Random(true))) => chess960::start_fen(chess960::random_index()).unwrap(),
+        (None, _) => variant.start_fen().to_string()
+    };
+    let board = fen::new_variant_board(&start_fen, variant).ok_or(StatusCode::BAD_REQUEST)?;
+    let game = Game::new(board);
+    if game.is_over() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let clock = match &payload.time_control {
+        Some(control) => {
+            let mut control: TimeControl = control.parse().map_err(|_| StatusCode::BAD_REQUEST)?;
+            control.delay = payload.delay;
+            Some(Clock::new(control))
+        }
+        None if payload.delay.is_some() => return Err(StatusCode::BAD_REQUEST),
+        None => None
+    };
+    let side = payload.side.unwrap_or_else(|| if rand::random() { Side::White } else { Side::Black });
+    let token = new_token();
+    let mut tokens = [None, None];
+    tokens[side.team()] = Some(token.clone());
+    let mut names = [ANONYMOUS.to_string(), ANONYMOUS.to_string()];
+    names[side.team()] = player_name(payload.name);
+    let engine = match payload.opponent.unwrap_or(Opponent::Human) {
+        Opponent::Human => None,
+        Opponent::Engine => {
+            let team = get_opposite_team(side.team());
+            // The engine's seat is taken by a token nobody knows
+            tokens[team] = Some(new_token());
+            names[team] = ENGINE_NAME.to_string();
+            let mut engine = state.engine(state.default_evaluator).ok_or(StatusCode::INTERNAL_SERVER_ERROR)?.fork();
+            engine.clear_info_callback();
+            let untimed = SearchLimits {
+                movetime: Some(config.search_timeout),
+                ..SearchLimits::depth(config.default_depth)
+            };
+            Some(EnginePlayer { team, engine, untimed, max_depth: config.max_depth })
+        }
+    };
+    state.lobby.sweep();
+    let id = state.lobby.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+    let room = Arc::new(Room {
+        id,
+        state: Mutex::new(RoomState {
+            game,
+            tokens,
+            draw_offer: None,
+            clock,
+            engine_stop: None,
+            names,
+            started_at: unix_time(),
+            time_control: payload.time_control,
+            last_disconnect: Instant::now()
+        }),
+        events: broadcast::channel(EVENT_BUFFER).0,
+        engine,
+        runtime: Handle::current(),
+        storage: state.storage.clone()
+    });
+    state.lobby.rooms.lock().unwrap().insert(id, room.clone());
+    // The engine may have the first move
+    let mut room_state = room.state.lock().unwrap();
+    publish(&room, &mut room_state, Vec::new());
+    drop(room_state);
+    Ok(Json(SeatResponse { id, side, token }))
+}
+
+pub async fn join_game(
+    Extension(state): Extension<SharedState>,
+    Path(id): Path<u64>,
+    payload: Option<Json<JoinGameRequest>>
+) -> Result<Json<SeatResponse>, StatusCode> {
+    let room = state.lobby.room(id).ok_or(StatusCode::NOT_FOUND)?;
+    let mut room_state = room.state.lock().unwrap();
+    let team = [Teams::WHITE, Teams::BLACK].into_iter().find(|team| room_state.tokens[*team].is_none()).ok_or(StatusCode::CONFLICT)?;
+    let token = new_token();
+    room_state.tokens[team] = Some(token.clone());
+    room_state.names[team] = player_name(payload.and_then(|Json(payload)| payload.name));
+    let _ = room.events.send(GameEvent::Joined { side: Side::of(team) });
+    Ok(Json(SeatResponse { id, side: Side::of(team), token }))
+}
+
+pub async fn get_game(
+    Extension(state): Extension<SharedState>,
+    Path(id): Path<u64>
+) -> Result<Json<GameSnapshot>, StatusCode> {
+    let room = state.lobby.room(id).ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Json(room.snapshot()))
+}
+
+pub async fn game_socket(
+    Extension(state): Extension<SharedState>,
+    Path(id): Path<u64>,
+    Query(query): Query<SocketQuery>,
+    upgrade: WebSocketUpgrade
+) -> Result<Response, StatusCode> {
+    let room = state.lobby.room(id).ok_or(StatusCode::NOT_FOUND)?;
+    let seat = match &query.token {
+        Some(token) => Some(room.state.lock().unwrap().seat(token).ok_or(StatusCode::FORBIDDEN)?),
+        None => None
+    };
+    Ok(upgrade.on_upgrade(move |socket| async move {
+        handle_socket(socket, &room, seat).await;
+        room.state.lock().unwrap().last_disconnect = Instant::now();
+        state.lobby.sweep();
+    }))
+}
+
+async fn send<T: Serialize>(socket: &mut WebSocket, message: &T) -> bool {
+    let text = serde_json::to_string(message).expect("Messages serialize to JSON");
+    socket.send(Message::Text(text)).await.is_ok()
+}
+
+async fn handle_socket(mut socket: WebSocket, room: &Arc<Room>, seat: Option<Team>) {
+    // Subscribed before the snapshot is taken so no event is missed, though one may repeat what the snapshot shows
+    let mut events = room.events.subscribe();
+    if !send(&mut socket, &DirectMessage::State(room.snapshot())).await {
+        return;
+    }
+    loop {
+        tokio::select! {
+            message = socket.recv() => {
+                let Some(Ok(message)) = message else { break };
+                let Message::Text(text) = message else { continue };
+                let result = match (seat, serde_json::from_str::<PlayerMessage>(&text)) {
+                    (None, _) => Err("Spectators cannot play".to_string()),
+                    (Some(team), Ok(message)) => handle_message(room, team, message),
+                    (_, Err(error)) => Err(error.to_string())
+                };
+                if let Err(message) = result {
+                    if !send(&mut socket, &DirectMessage::Error { message }).await {
+                        break;
+                    }
+                }
+            }
+            event = events.recv() => {
+                let sent = match event {
+                    Ok(event) => send(&mut socket, &event).await,
+                    // Fell too far behind: start over from the current state
+                    Err(broadcast::error::RecvError::Lagged(_)) => send(&mut socket, &DirectMessage::State(room.snapshot())).await,
+                    Err(broadcast::error::RecvError::Closed) => false
+                };
+                if !sent {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+// Applies a player's message under the room lock, broadcasting what changed
+fn handle_message(room: &Arc<Room>, team: Team, message: PlayerMessage) -> Result<(), String> {
+    let mut state = room.state.lock().unwrap();
+    if state.game.is_over() {
+        return Err("The game is over".to_string());
+    }
+    let side = Side::of(team);
+    let mut events = Vec::new();
+    match message {
+        PlayerMessage::Move { origin, target, promotion } => {
+            if state.game.team_to_play() != team {
+                return Err("Not your turn".to_string());
+            }
+            let promotion = promotion.map(|letter| promotion_piece(letter).ok_or("Unknown promotion piece")).transpose()?;
+            play_move(&mut state, origin, target, promotion, &mut events)?;
+        }
+        PlayerMessage::Resign => state.game.end(Outcome::win(get_opposite_team(team), EndReason::Resignation)),
+        PlayerMessage::OfferDraw => {
+            if state.draw_offer.is_none() {
+                state.draw_offer = Some(team);
+                events.push(GameEvent::DrawOffered { side });
+                // The engine plays on
+                if room.engine.is_some() {
+                    state.draw_offer = None;
+                    events.push(GameEvent::DrawDeclined { side: Side::of(get_opposite_team(team)) });
+                }
+            }
+        }
+        PlayerMessage::AcceptDraw => {
+            if state.draw_offer != Some(get_opposite_team(team)) {
+                return Err("No draw offer to accept".to_string());
+            }
+            state.game.end(Outcome::draw(EndReason::Agreement));
+        }
+        PlayerMessage::DeclineDraw => {
+            if state.draw_offer != Some(get_opposite_team(team)) {
+                return Err("No draw offer to decline".to_string());
+            }
+            state.draw_offer = None;
+            events.push(GameEvent::DrawDeclined { side });
+        }
+    }
+    publish(room, &mut state, events);
+    Ok(())
+}
+
+// Plays the side to move's move, unless its time ran out first
+fn play_move(state: &mut RoomState, origin: u8, target: u8, promotion: Option<Piece>, events: &mut Vec<GameEvent>) -> Result<(), String> {
+    let team = state.game.team_to_play();
+    let now = Instant::now();
+    if state.clock.as_ref().is_some_and(|clock| clock.is_flagged(team, now)) {
+        state.game.flag(team);
+        return Ok(());
+    }
+    let mv = state.game.play(origin, target, promotion).ok_or("Illegal move")?;
+    if let Some(clock) = &mut state.clock {
+        // The first move takes no time but still counts towards the time control
+        if !clock.is_running() {
+            clock.start(team, now);
+        }
+        clock.press(now);
+    }
+    // Playing a move declines a pending offer
+    state.draw_offer = None;
+    events.push(GameEvent::Move { side: Side::of(team), mv, fen: state.game.fen(), clock: state.clock_reading() });
+    Ok(())
+}
+
+// Broadcasts the events, then either wraps up a finished game or gets the next move going: the engine
+// starts thinking if it is its turn, and the flag is watched for the side to move
+fn publish(room: &Arc<Room>, state: &mut RoomState, mut events: Vec<GameEvent>) {
+    let now = Instant::now();
+    if let Some(outcome) = state.game.outcome() {
+        state.draw_offer = None;
+        if let Some(clock) = &mut state.clock {
+            clock.stop(now);
+        }
+        if let Some(stop) = state.engine_stop.take() {
+            stop.stop();
+        }
+        events.push(GameEvent::GameOver { outcome });
+        archive(room, state, outcome);
+    }
+    for event in events {
+        let _ = room.events.send(event);
+    }
+    if state.game.is_over() {
+        return;
+    }
+    let team = state.game.team_to_play();
+    if let Some(clock) = state.clock.as_ref().filter(|clock| clock.is_running()) {
+        watch_flag(room.clone(), state.game.moves().len(), clock.time_to_flag(team, now) + FLAG_SLACK);
+    }
+    if room.engine.as_ref().is_some_and(|player| player.team == team) && state.engine_stop.is_none() {
+        start_engine(room, state);
+    }
+}
+
+// Saves the finished game off the runtime's threads, a failure only costs the archive its copy
+fn archive(room: &Arc<Room>, state: &RoomState, outcome: Outcome) {
+    let game = &state.game;
+    let variant = game.variant();
+    let Some(start) = fen::new_variant_board(game.start_fen(), variant) else { return };
+    let mut tags = vec![
+        ("Event".to_string(), "Casual game".to_string()),
+        ("Site".to_string(), "Caissa".to_string()),
+        ("Date".to_string(), pgn_date(state.started_at)),
+        ("White".to_string(), state.names[Teams::WHITE].clone()),
+        ("Black".to_string(), state.names[Teams::BLACK].clone()),
+        ("Termination".to_string(), end_reason(outcome.reason)),
+        ("TimeControl".to_string(), state.time_control.clone().unwrap_or("-".to_string()))
+    ];
+    if variant != Variant::Standard {
+        tags.push(("Variant".to_string(), variant.pgn_name().to_string()));
+    } else if start.state.chess960 {
+        tags.push(("Variant".to_string(), "Chess960".to_string()));
+    }
+    if game.start_fen() != fen::to_fen(&fen::new_variant_board(variant.start_fen(), variant).unwrap(), 0) {
+        tags.push(("SetUp".to_string(), "1".to_string()));
+        tags.push(("FEN".to_string(), game.start_fen().to_string()));
+    }
+    let clock = state.clock_reading();
+    let record = GameRecord {
+        summary: GameSummary {
+            id: 0,
+            white: state.names[Teams::WHITE].clone(),
+            black: state.names[Teams::BLACK].clone(),
+            result: outcome.result,
+            reason: end_reason(outcome.reason),
+            time_control: state.time_control.clone(),
+            white_ms: clock.map(|clock| clock.white_ms),
+            black_ms: clock.map(|clock| clock.black_ms),
+            started_at: state.started_at,
+            ended_at: unix_time(),
+            start_fen: game.start_fen().to_string(),
+            moves: game.moves().len() as u32
+        },
+        pgn: write_pgn(&tags, &start, game.moves(), outcome.result)
+    };
+    let storage = room.storage.clone();
+    let id = room.id;
+    room.runtime.spawn_blocking(move || {
+        if let Err(error) = storage.save_game(&record) {
+            tracing::warn!("Could not archive game {}: {}", id, error);
+        }
+    });
+}
+
+fn end_reason(reason: EndReason) -> String {
+    serde_json::to_value(reason).ok().and_then(|value| value.as_str().map(String::from)).unwrap_or_default()
+}
+
+// Ends the game on time if the side to move has not moved once its time is up
+fn watch_flag(room: Arc<Room>, ply: usize, delay: Duration) {
+    let runtime = room.runtime.clone();
+    runtime.spawn(async move {
+        let mut delay = delay;
+        loop {
+            tokio::time::sleep(delay).await;
+            // A timer waking a little early goes back to sleep for what is left
+            match check_flag(&room, ply) {
+                Some(left) => delay = left + FLAG_SLACK,
+                None => break
+            }
+        }
+    });
+}
+
+// Flags the side to move if its time is up and it has not moved since the watch began. Returns the time
+// left when it is not up yet, None once there is nothing more to watch.
+fn check_flag(room: &Arc<Room>, ply: usize) -> Option<Duration> {
+    let mut state = room.state.lock().unwrap();
+    if state.game.is_over() || state.game.moves().len() != ply {
+        return None;
+    }
+    let team = state.game.team_to_play();
+    let now = Instant::now();
+    let clock = state.clock.as_ref().filter(|clock| clock.is_running())?;
+    if !clock.is_flagged(team, now) {
+        return Some(clock.time_to_flag(team, now));
+    }
+    state.game.flag(team);
+    publish(room, &mut state, Vec::new());
+    None
+}
+
+// Searches the engine's move on its own thread, with the time manager's share of its clock in timed games
+fn start_engine(room: &Arc<Room>, state: &mut RoomState) {
+    let Some(player) = &room.engine else { return };
+    let limits = match &state.clock {
+        Some(clock) => SearchLimits {
+            movetime: Some(time::allocate(&clock.reading(player.team, Instant::now()), player.team)),
+            ..SearchLimits::depth(player.max_depth)
+        },
+        None => player.untimed
+    };
+    let stop = StopHandle::default();
+    state.engine_stop = Some(stop.clone());
+    let mut engine = player.engine.fork();
+    engine.set_stop_handle(stop);
+    let board = state.game.board().clone();
+    let ply = state.game.moves().len();
+    let room = room.clone();
+    thread::Builder::new()
+        .name("negamax".to_string())
+        .stack_size(32 * 1024 * 1024)
+        .spawn(move || {
+            let result = engine.search(&board, &limits);
+            let mut state = room.state.lock().unwrap();
+            if state.game.is_over() || state.game.moves().len() != ply {
+                return;
+            }
+            state.engine_stop = None;
+            let mut events = Vec::new();
+            let played = result.is_some_and(|result| play_move(&mut state, result.best_move.origin, result.best_move.target, result.best_move.get_promotion(), &mut events).is_ok());
+            if !played {
+                // Only an engine bug gets here, the game cannot go on without its moves
+                state.game.end(Outcome::win(get_opposite_team(room.engine.as_ref().unwrap().team), EndReason::Resignation));
+            }
+            publish(&room, &mut state, events);
+        })
+        .expect("Could not spawn a search thread");
+}
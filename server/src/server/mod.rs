@@ -1,14 +1,24 @@
+use std::sync::Arc;
 use std::thread;
+use std::time::Duration;
 
 use axum::{Extension, Json};
 use axum::http::StatusCode;
 use serde::{Deserialize, Serialize};
+use tokio::sync::oneshot;
 
-use crate::board::{CompletedMove, get_opposite_team, PossibleMove};
-use crate::engine::ChessEngine;
+use crate::board::CompletedMove;
+use crate::board::variant::Variant;
+use crate::engine::{ChessEngine, SearchLimits, StopHandle};
+use crate::engine::evaluator::{AnyEvaluator, EvaluatorKind};
+use crate::engine::info::{Analysis, SearchResult};
+use crate::engine::minimax::MinimaxEngine;
 use crate::game::{fen, Vector};
-use crate::math::map_bits;
-use crate::SharedState;
+use crate::{ServerConfig, SharedState};
+
+pub mod archive;
+pub mod games;
+pub mod socket;
 
 pub async fn get_team_moves(
     Json(payload): Json<TeamMovesRequest>
@@ -30,7 +40,7 @@ pub async fn get_piece_moves(
         return (StatusCode::BAD_REQUEST, Json(Moves { moves: Vec::new() }))
     }
     let board = board.unwrap();
-    let moves = board.calculate_move_for_piece(payload.coordinates);
+    let _moves = board.calculate_move_for_piece(payload.coordinates);
     (StatusCode::BAD_REQUEST, Json(Moves { moves: Vec::new() }))
 }
 
@@ -50,19 +60,99 @@ pub async fn get_threatened_squares(
 
 pub async fn get_best_move(
     Extension(state): Extension<SharedState>,
+    Extension(config): Extension<Arc<ServerConfig>>,
     Json(payload): Json<BestMoveRequest>,
-) -> Result<Json<CompletedMove>, StatusCode> {
-    let board = fen::new_board(&payload.fen);
-    let thread = thread::Builder::new()
+) -> Result<Json<SearchResult>, StatusCode> {
+    let board = fen::new_variant_board(&payload.fen, payload.variant.unwrap_or_default()).ok_or(StatusCode::BAD_REQUEST)?;
+    let multipv = payload.multipv.unwrap_or(1);
+    let skill = payload.skill.unwrap_or(SearchLimits::MAX_SKILL);
+    let limits = payload.limits.resolve(&config, multipv, skill).ok_or(StatusCode::BAD_REQUEST)?;
+    let engine = fork_engine(&state, payload.evaluator)?;
+    // A mated or stalemated position has no move to give
+    run_search(&config, engine, move |engine| engine.search(&board, &limits)).await?.map(Json).ok_or(StatusCode::BAD_REQUEST)
+}
+
+pub async fn get_analysis(
+    Extension(state): Extension<SharedState>,
+    Extension(config): Extension<Arc<ServerConfig>>,
+    Json(payload): Json<AnalysisRequest>,
+) -> Result<Json<Analysis>, StatusCode> {
+    let board = fen::new_variant_board(&payload.fen, payload.variant.unwrap_or_default()).ok_or(StatusCode::BAD_REQUEST)?;
+    let lines = payload.lines.unwrap_or(AnalysisRequest::DEFAULT_LINES);
+    let limits = payload.limits.resolve(&config, lines, SearchLimits::MAX_SKILL).ok_or(StatusCode::BAD_REQUEST)?;
+    let evaluator = payload.evaluator.unwrap_or(state.default_evaluator);
+    let engine = fork_engine(&state, Some(evaluator))?;
+    // Only depth-bound analyses are cached, a time or node budget gives no depth to match against
+    let cacheable = limits.movetime.is_none() && limits.nodes.is_none();
+    // The cache is keyed by position alone, so it only holds standard chess
+    let standard = board.state.variant == Variant::Standard;
+    let storage = state.storage.clone();
+    if cacheable && standard {
+        let (storage, cached_board) = (storage.clone(), board.clone());
+        let cached = tokio::task::spawn_blocking(move || storage.cached_analysis(&cached_board, evaluator, limits.depth, lines)).await;
+        match cached {
+            Ok(Ok(Some(analysis))) => return Ok(Json(analysis)),
+            Ok(Err(error)) => tracing::warn!("Could not read the analysis cache: {}", error),
+            _ => ()
+        }
+    }
+    let analysis = run_search(&config, engine, {
+        let board = board.clone();
+        move |engine| engine.analyse(&board, &limits)
+    }).await?;
+    // A search cut short by the timeout is kept too, at the depth it reached
+    if standard && analysis.depth > 0 && !analysis.lines.is_empty() {
+        let stored = analysis.clone();
+        tokio::task::spawn_blocking(move || {
+            if let Err(error) = storage.store_analysis(&board, evaluator, lines, &stored) {
+                tracing::warn!("Could not cache the analysis: {}", error);
+            }
+        });
+    }
+    Ok(Json(analysis))
+}
+
+fn fork_engine(state: &SharedState, evaluator: Option<EvaluatorKind>) -> Result<MinimaxEngine<AnyEvaluator>, StatusCode> {
+    let kind = evaluator.unwrap_or(state.default_evaluator);
+    Ok(state.engine(kind).ok_or(StatusCode::BAD_REQUEST)?.fork())
+}
+
+// Runs the search on its own thread, stopping it when the client goes away or the server timeout passes
+async fn run_search<T, F>(config: &ServerConfig, mut engine: MinimaxEngine<AnyEvaluator>, search: F) -> Result<T, StatusCode>
+where
+    T: Send + 'static,
+    F: FnOnce(&mut MinimaxEngine<AnyEvaluator>) -> T + Send + 'static
+{
+    let stop = StopHandle::default();
+    // Axum drops this future when the client goes away, which stops the search with it
+    let _guard = StopOnDrop(stop.clone());
+    engine.set_stop_handle(stop.clone());
+    let (sender, receiver) = oneshot::channel();
+    thread::Builder::new()
         .name("negamax".to_string())
         .stack_size(32 * 1024 * 1024)
         .spawn(move || {
-            let mut state = &mut state.write().unwrap();
-            let depth = state.depth.clone();
-            state.engine.get_best_move(&board.unwrap(), depth)
+            let _ = sender.send(search(&mut engine));
         }).unwrap();
-    let best_move = thread.join().unwrap();
-    Ok(Json(best_move))
+
+    let mut receiver = receiver;
+    let result = match tokio::time::timeout(config.search_timeout, &mut receiver).await {
+        Ok(result) => result,
+        Err(_) => {
+            // Out of time: the search returns the best move it has found so far
+            stop.stop();
+            receiver.await
+        }
+    };
+    result.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+struct StopOnDrop(StopHandle);
+
+impl Drop for StopOnDrop {
+    fn drop(&mut self) {
+        self.0.stop();
+    }
 }
 
 #[derive(Deserialize)]
@@ -73,7 +163,55 @@ pub struct TeamMovesRequest {
 
 #[derive(Deserialize)]
 pub struct BestMoveRequest {
-    fen: String
+    fen: String,
+    variant: Option<Variant>,
+    evaluator: Option<EvaluatorKind>,
+    #[serde(flatten)]
+    limits: LimitsRequest,
+    multipv: Option<usize>,
+    skill: Option<u8>
+}
+
+#[derive(Deserialize)]
+pub struct AnalysisRequest {
+    fen: String,
+    variant: Option<Variant>,
+    evaluator: Option<EvaluatorKind>,
+    #[serde(flatten)]
+    limits: LimitsRequest,
+    // Number of best moves to return
+    lines: Option<usize>
+}
+
+impl AnalysisRequest {
+    const DEFAULT_LINES: usize = 3;
+}
+
+#[derive(Deserialize)]
+pub struct LimitsRequest {
+    depth: Option<u8>,
+    // Milliseconds
+    movetime: Option<u64>,
+    nodes: Option<u64>
+}
+
+impl LimitsRequest {
+    // None when a limit is out of the server's bounds
+    fn resolve(&self, config: &ServerConfig, multipv: usize, skill: u8) -> Option<SearchLimits> {
+        let movetime = self.movetime.map(Duration::from_millis);
+        // A time or node budget without a depth searches as deep as the server allows
+        let depth = match self.depth {
+            Some(depth) => depth,
+            None if movetime.is_some() || self.nodes.is_some() => config.max_depth,
+            None => config.default_depth
+        };
+        let valid = (1..=config.max_depth).contains(&depth)
+            && movetime.is_none_or(|movetime| !movetime.is_zero() && movetime <= config.max_movetime)
+            && self.nodes.is_none_or(|nodes| nodes > 0 && nodes <= config.max_nodes)
+            && (1..=config.max_multipv).contains(&multipv)
+            && skill <= SearchLimits::MAX_SKILL;
+        valid.then_some(SearchLimits { depth, movetime, nodes: self.nodes, multipv, skill })
+    }
 }
 
 #[derive(Deserialize)]
@@ -0,0 +1,153 @@
+use std::sync::Arc;
+use std::thread;
+
+use axum::Extension;
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::response::Response;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+
+use crate::board::variant::Variant;
+use crate::engine::{ChessEngine, SearchLimits, StopHandle};
+use crate::engine::evaluator::EvaluatorKind;
+use crate::engine::info::{SearchInfo, SearchResult};
+use crate::game::fen;
+use crate::server::{fork_engine, LimitsRequest};
+use crate::{ServerConfig, SharedState};
+
+// Live analysis: the client sends positions to search and receives an info message after every
+// iteration, then a bestmove message once the search ends
+pub async fn analysis_socket(
+    Extension(state): Extension<SharedState>,
+    Extension(config): Extension<Arc<ServerConfig>>,
+    upgrade: WebSocketUpgrade
+) -> Response {
+    upgrade.on_upgrade(move |socket| handle_socket(socket, state, config))
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum ClientMessage {
+    // Starts a search, stopping the one running if any
+    Go(GoRequest),
+    // Ends the running search, which still answers with its best move
+    Stop
+}
+
+#[derive(Deserialize)]
+struct GoRequest {
+    fen: String,
+    variant: Option<Variant>,
+    evaluator: Option<EvaluatorKind>,
+    #[serde(flatten)]
+    limits: LimitsRequest,
+    multipv: Option<usize>
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum ServerMessage {
+    Info(SearchInfo),
+    BestMove(SearchResult),
+    Error { message: String }
+}
+
+// Search progress, tagged with the search it comes from
+type Event = (u64, ServerMessage);
+
+async fn handle_socket(mut socket: WebSocket, state: SharedState, config: Arc<ServerConfig>) {
+    let (events, mut receiver) = mpsc::unbounded_channel::<Event>();
+    // Id and stop handle of the running search. Messages from searches replaced by a newer one are dropped.
+    let mut current: Option<(u64, StopHandle)> = None;
+    let mut next_id = 0;
+    loop {
+        let outgoing = tokio::select! {
+            message = socket.recv() => {
+                let Some(Ok(message)) = message else { break };
+                let Message::Text(text) = message else { continue };
+                match serde_json::from_str::<ClientMessage>(&text) {
+                    Ok(ClientMessage::Go(request)) => {
+                        if let Some((_, stop)) = current.take() {
+                            stop.stop();
+                        }
+                        next_id += 1;
+                        match start_search(&state, &config, request, next_id, events.clone()) {
+                            Ok(stop) => {
+                                current = Some((next_id, stop));
+                                continue;
+                            }
+                            Err(message) => ServerMessage::Error { message }
+                        }
+                    }
+                    Ok(ClientMessage::Stop) => {
+                        if let Some((_, stop)) = &current {
+                            stop.stop();
+                        }
+                        continue;
+                    }
+                    Err(error) => ServerMessage::Error { message: error.to_string() }
+                }
+            }
+            Some((id, message)) = receiver.recv() => {
+                if current.as_ref().is_none_or(|(current_id, _)| *current_id != id) {
+                    continue;
+                }
+                if matches!(message, ServerMessage::BestMove(_)) {
+                    current = None;
+                }
+                message
+            }
+        };
+        let text = serde_json::to_string(&outgoing).expect("Messages serialize to JSON");
+        if socket.send(Message::Text(text)).await.is_err() {
+            break;
+        }
+    }
+    // The client is gone, there is no one left to search for
+    if let Some((_, stop)) = current {
+        stop.stop();
+    }
+}
+
+fn start_search(
+    state: &SharedState,
+    config: &ServerConfig,
+    request: GoRequest,
+    id: u64,
+    events: mpsc::UnboundedSender<Event>
+) -> Result<StopHandle, String> {
+    let board = fen::new_variant_board(&request.fen, request.variant.unwrap_or_default()).ok_or("Invalid FEN")?;
+    if board.generate_moves(board.state.team_to_play).is_empty() {
+        return Err("No legal moves in this position".to_string());
+    }
+    let multipv = request.multipv.unwrap_or(1);
+    let limits = request.limits.resolve(config, multipv, SearchLimits::MAX_SKILL)
+        .ok_or("Search limits out of the server's bounds")?;
+    let mut engine = fork_engine(state, request.evaluator).map_err(|_| "Evaluator not available")?;
+    let stop = StopHandle::default();
+    engine.set_stop_handle(stop.clone());
+    let info_events = events.clone();
+    engine.set_info_callback(move |info| {
+        let _ = info_events.send((id, ServerMessage::Info(info.clone())));
+    });
+
+    // Bounded by the server timeout like any other search
+    let timeout_stop = stop.clone();
+    let timeout = config.search_timeout;
+    tokio::spawn(async move {
+        tokio::time::sleep(timeout).await;
+        timeout_stop.stop();
+    });
+    thread::Builder::new()
+        .name("negamax".to_string())
+        .stack_size(32 * 1024 * 1024)
+        .spawn(move || {
+            let message = match engine.search(&board, &limits) {
+                Some(result) => ServerMessage::BestMove(result),
+                None => ServerMessage::Error { message: "No legal moves in this position".to_string() }
+            };
+            let _ = events.send((id, message));
+        })
+        .map_err(|error| error.to_string())?;
+    Ok(stop)
+}
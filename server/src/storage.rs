@@ -0,0 +1,203 @@
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rusqlite::{Connection, OptionalExtension, params, Row};
+use serde::{Deserialize, Serialize};
+
+use crate::board::board::ChessBoard;
+use crate::book::polyglot::polyglot_key;
+use crate::engine::evaluator::EvaluatorKind;
+use crate::engine::info::Analysis;
+use crate::game::fen;
+use crate::game::pgn::GameResult;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS games (
+        id INTEGER PRIMARY KEY,
+        white TEXT NOT NULL,
+        black TEXT NOT NULL,
+        result TEXT NOT NULL,
+        reason TEXT NOT NULL,
+        time_control TEXT,
+        white_ms INTEGER,
+        black_ms INTEGER,
+        started_at INTEGER NOT NULL,
+        ended_at INTEGER NOT NULL,
+        start_fen TEXT NOT NULL,
+        moves INTEGER NOT NULL,
+        pgn TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS games_white ON games (white COLLATE NOCASE);
+    CREATE INDEX IF NOT EXISTS games_black ON games (black COLLATE NOCASE);
+    CREATE TABLE IF NOT EXISTS analyses (
+        key INTEGER NOT NULL,
+        fen TEXT NOT NULL,
+        evaluator TEXT NOT NULL,
+        lines INTEGER NOT NULL,
+        depth INTEGER NOT NULL,
+        analysis TEXT NOT NULL,
+        created_at INTEGER NOT NULL,
+        PRIMARY KEY (key, fen, evaluator, lines)
+    );
+";
+
+const SUMMARY_COLUMNS: &str = "id, white, black, result, reason, time_control, white_ms, black_ms, started_at, ended_at, start_fen, moves";
+pub const MAX_PAGE: u32 = 200;
+
+pub fn unix_time() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_secs())
+}
+
+// A finished game without its moves
+#[derive(Debug, Clone, Serialize)]
+pub struct GameSummary {
+    pub id: i64,
+    pub white: String,
+    pub black: String,
+    pub result: GameResult,
+    pub reason: String,
+    pub time_control: Option<String>,
+    // Clock times left at the end, for timed games
+    pub white_ms: Option<u64>,
+    pub black_ms: Option<u64>,
+    // Seconds since the Unix epoch
+    pub started_at: u64,
+    pub ended_at: u64,
+    pub start_fen: String,
+    // Half-moves played
+    pub moves: u32
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GameRecord {
+    #[serde(flatten)]
+    pub summary: GameSummary,
+    pub pgn: String
+}
+
+// Filters for listing stored games, newest first
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct GameQuery {
+    // Either side's name, ignoring case
+    pub player: Option<String>,
+    // In PGN form: 1-0, 0-1 or 1/2-1/2
+    pub result: Option<String>,
+    pub reason: Option<String>,
+    // Unix seconds bounding when the game ended
+    pub since: Option<u64>,
+    pub until: Option<u64>,
+    pub limit: Option<u32>,
+    pub offset: Option<u32>
+}
+
+// Finished games and analysis results, kept in SQLite so they outlive the process
+pub struct Storage {
+    connection: Mutex<Connection>
+}
+
+impl Storage {
+    // A path of :memory: keeps everything in memory
+    pub fn open(path: &str) -> rusqlite::Result<Self> {
+        let connection = Connection::open(path)?;
+        connection.execute_batch(SCHEMA)?;
+        Ok(Storage { connection: Mutex::new(connection) })
+    }
+
+    // The record's id is ignored, the new one is returned
+    pub fn save_game(&self, game: &GameRecord) -> rusqlite::Result<i64> {
+        let connection = self.connection.lock().unwrap();
+        let summary = &game.summary;
+        connection.execute(
+            "INSERT INTO games (white, black, result, reason, time_control, white_ms, black_ms, started_at, ended_at, start_fen, moves, pgn)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+            params![
+                summary.white, summary.black, summary.result.as_str(), summary.reason, summary.time_control,
+                summary.white_ms, summary.black_ms, summary.started_at, summary.ended_at, summary.start_fen,
+                summary.moves, game.pgn
+            ]
+        )?;
+        Ok(connection.last_insert_rowid())
+    }
+
+    pub fn game(&self, id: i64) -> rusqlite::Result<Option<GameRecord>> {
+        let connection = self.connection.lock().unwrap();
+        connection.query_row(
+            &format!("SELECT {}, pgn FROM games WHERE id = ?1", SUMMARY_COLUMNS),
+            [id],
+            |row| Ok(GameRecord { summary: read_summary(row)?, pgn: row.get(12)? })
+        ).optional()
+    }
+
+    pub fn search_games(&self, query: &GameQuery) -> rusqlite::Result<Vec<GameSummary>> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection.prepare(&format!(
+            "SELECT {} FROM games
+             WHERE (?1 IS NULL OR white = ?1 COLLATE NOCASE OR black = ?1 COLLATE NOCASE)
+               AND (?2 IS NULL OR result = ?2)
+               AND (?3 IS NULL OR reason = ?3)
+               AND (?4 IS NULL OR ended_at >= ?4)
+               AND (?5 IS NULL OR ended_at <= ?5)
+             ORDER BY ended_at DESC, id DESC
+             LIMIT ?6 OFFSET ?7",
+            SUMMARY_COLUMNS
+        ))?;
+        let limit = query.limit.unwrap_or(50).min(MAX_PAGE);
+        let rows = statement.query_map(
+            params![query.player, query.result, query.reason, query.since, query.until, limit, query.offset.unwrap_or(0)],
+            read_summary
+        )?;
+        rows.collect()
+    }
+
+    // A stored analysis at least as deep and with at least as many lines, cut down to the lines asked for
+    pub fn cached_analysis(&self, board: &ChessBoard, evaluator: EvaluatorKind, depth: u8, lines: usize) -> rusqlite::Result<Option<Analysis>> {
+        let connection = self.connection.lock().unwrap();
+        let stored: Option<String> = connection.query_row(
+            "SELECT analysis FROM analyses
+             WHERE key = ?1 AND fen = ?2 AND evaluator = ?3 AND lines >= ?4 AND depth >= ?5
+             ORDER BY depth DESC, lines ASC LIMIT 1",
+            params![polyglot_key(board) as i64, position_fen(board), evaluator.name(), lines, depth],
+            |row| row.get(0)
+        ).optional()?;
+        Ok(stored.and_then(|json| serde_json::from_str::<Analysis>(&json).ok()).map(|mut analysis| {
+            analysis.lines.truncate(lines);
+            analysis
+        }))
+    }
+
+    // Keeps the deepest analysis for each position, evaluator and line count
+    pub fn store_analysis(&self, board: &ChessBoard, evaluator: EvaluatorKind, lines: usize, analysis: &Analysis) -> rusqlite::Result<()> {
+        let connection = self.connection.lock().unwrap();
+        let json = serde_json::to_string(analysis).expect("Analyses serialize to JSON");
+        connection.execute(
+            "INSERT INTO analyses (key, fen, evaluator, lines, depth, analysis, created_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+             ON CONFLICT (key, fen, evaluator, lines) DO UPDATE SET depth = excluded.depth, analysis = excluded.analysis, created_at = excluded.created_at
+             WHERE excluded.depth > analyses.depth",
+            params![polyglot_key(board) as i64, position_fen(board), evaluator.name(), lines, analysis.depth, json, unix_time()]
+        )?;
+        Ok(())
+    }
+}
+
+// The FEN without its move counters, which do not change the analysis
+fn position_fen(board: &ChessBoard) -> String {
+    fen::to_fen(board, 0).split(' ').take(4).collect::<Vec<_>>().join(" ")
+}
+
+fn read_summary(row: &Row) -> rusqlite::Result<GameSummary> {
+    let result: String = row.get(3)?;
+    Ok(GameSummary {
+        id: row.get(0)?,
+        white: row.get(1)?,
+        black: row.get(2)?,
+        result: GameResult::parse(&result).unwrap_or(GameResult::Unknown),
+        reason: row.get(4)?,
+        time_control: row.get(5)?,
+        white_ms: row.get(6)?,
+        black_ms: row.get(7)?,
+        started_at: row.get(8)?,
+        ended_at: row.get(9)?,
+        start_fen: row.get(10)?,
+        moves: row.get(11)?
+    })
+}
@@ -0,0 +1,56 @@
+use std::time::Instant;
+
+use crate::engine::{ChessEngine, SearchLimits};
+use crate::engine::evaluator::HandCraftedEvaluator;
+use crate::engine::minimax::MinimaxEngine;
+use crate::game::fen;
+
+const DEFAULT_DEPTH: u8 = 5;
+
+// Openings, middlegames with tactics for both sides, and endgames where the search runs deep
+const POSITIONS: [&str; 16] = [
+    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+    "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 10",
+    "r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 4 4",
+    "rnbqkb1r/pp1p1ppp/4pn2/2p5/2PP4/2N5/PP2PPPP/R1BQKBNR w KQkq - 0 4",
+    "r1bq1rk1/pp2bppp/2n1pn2/3p4/2PP4/2N1PN2/PP1B1PPP/R2QKB1R w KQ - 0 8",
+    "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - 0 1",
+    "r1b2rk1/2q1b1pp/p2ppn2/1p6/3QP3/1BN1B3/PPP3PP/R4RK1 w - - 0 14",
+    "r2q1rk1/ppp2ppp/2n1bn2/2b1p3/3pP3/3P1NPP/PPP1NPB1/R1BQ1RK1 b - - 0 9",
+    "4rrk1/pp1n3p/3q2pQ/2p1pb2/2PP4/2P3N1/P2B2PP/4RRK1 b - - 7 19",
+    "r3r1k1/2p2ppp/p1p1bn2/8/1q2P3/2NPQN2/PPP3PP/R4RK1 b - - 2 15",
+    "6k1/6p1/6Pp/ppp5/3pn2P/1P3K2/1PP2P2/3N4 b - - 0 1",
+    "3b4/5kp1/1p1p1p1p/pP1PpP1P/P1P1P3/3KN3/8/8 w - - 0 1",
+    "8/8/8/8/5kp1/P7/8/1K1N4 w - - 0 1",
+    "8/5k2/3p4/1p1Pp2p/pP2Pp1P/P4P1K/8/8 b - - 99 50",
+    "8/3k4/8/8/8/4B3/4KB2/2B5 w - - 0 1",
+    "8/8/1P6/5pr1/8/4R3/7k/2K5 w - - 0 1"
+];
+
+// Usage: bench [depth]
+// Searches a fixed set of positions to a fixed depth on one thread with a fresh hash table each. The total node
+// count only changes when the search or evaluation does, so it works as a signature of the engine's behaviour,
+// while the speed tracks its performance. At the default depth the signature is 2087887 nodes.
+pub fn run(args: &[String]) -> Result<(), String> {
+    let depth = match args.first() {
+        Some(depth) => depth.parse().ok().filter(|depth| *depth > 0).ok_or(format!("Invalid depth {}", depth))?,
+        None => DEFAULT_DEPTH
+    };
+    let mut engine = MinimaxEngine::with_evaluator(HandCraftedEvaluator::default());
+    let limits = SearchLimits::depth(depth);
+    let mut nodes = 0;
+    let started = Instant::now();
+    for (index, position) in POSITIONS.iter().enumerate() {
+        let board = fen::new_board(position).ok_or(format!("Invalid bench position {}", position))?;
+        engine.clear_hash();
+        let result = engine.search(&board, &limits).ok_or(format!("No legal moves in bench position {}", position))?;
+        println!("Position {:>2}/{}: {:>10} nodes  {}", index + 1, POSITIONS.len(), result.nodes, result.best_move);
+        nodes += result.nodes;
+    }
+    let elapsed = started.elapsed();
+    println!();
+    println!("Total time (ms) : {}", elapsed.as_millis());
+    println!("Nodes searched  : {}", nodes);
+    println!("Nodes/second    : {}", (nodes as f64 / elapsed.as_secs_f64().max(f64::EPSILON)) as u64);
+    Ok(())
+}
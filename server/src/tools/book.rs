@@ -0,0 +1,66 @@
+use std::fs;
+
+use crate::book::builder::OpeningTree;
+use crate::game::pgn::parse_pgn;
+
+const USAGE: &str = "Usage: book <output> [--format polyglot|native] [--depth plies] [--min-games n] <games.pgn>...";
+const DEFAULT_DEPTH: usize = 20;
+
+#[derive(PartialEq)]
+enum BookFormat {
+    Polyglot,
+    Native
+}
+
+// Usage: book <output> [--format polyglot|native] [--depth plies] [--min-games n] <games.pgn>...
+// Replays every game of the PGN files and writes the resulting opening tree. Polyglot books can be
+// loaded by the engine through CAISSA_BOOK, the native format keeps the full win/draw/loss counts.
+pub fn run(args: &[String]) -> Result<(), String> {
+    let output = args.first().ok_or(USAGE)?;
+    let mut format = if output.ends_with(".bin") { BookFormat::Polyglot } else { BookFormat::Native };
+    let mut depth = DEFAULT_DEPTH;
+    let mut min_games = 1;
+    let mut inputs = Vec::new();
+
+    let mut args = args[1..].iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("Missing value for {}", arg));
+        match arg.as_str() {
+            "--format" => format = match value()?.as_str() {
+                "polyglot" => BookFormat::Polyglot,
+                "native" => BookFormat::Native,
+                other => return Err(format!("Unknown book format {}", other))
+            },
+            "--depth" => depth = value()?.parse().map_err(|_| "Invalid depth".to_string())?,
+            "--min-games" => min_games = value()?.parse().map_err(|_| "Invalid game count".to_string())?,
+            path => inputs.push(path.to_string())
+        }
+    }
+    if inputs.is_empty() {
+        return Err(USAGE.to_string());
+    }
+
+    let mut tree = OpeningTree::default();
+    let mut skipped = 0;
+    for path in &inputs {
+        let text = fs::read_to_string(path).map_err(|error| format!("Could not read {}: {}", path, error))?;
+        for game in parse_pgn(&text) {
+            if tree.add_game(&game, depth) == 0 {
+                skipped += 1;
+            }
+        }
+    }
+    println!("Read {} games ({} without a playable move), {} positions", tree.games(), skipped, tree.positions());
+
+    let result = match format {
+        BookFormat::Polyglot => {
+            let book = tree.to_polyglot(min_games);
+            println!("Writing {} Polyglot entries", book.len());
+            book.save(output)
+        }
+        BookFormat::Native => tree.save(output)
+    };
+    result.map_err(|error| format!("Could not write {}: {}", output, error))?;
+    println!("Wrote {}", output);
+    Ok(())
+}
@@ -0,0 +1,456 @@
+use std::fs::{self, File};
+use std::io::Write;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Instant;
+
+use rand::seq::SliceRandom;
+
+use crate::board::{get_opposite_team, Piece, Team, Teams};
+use crate::game::clock::{Clock, TimeControl};
+use crate::game::elo::{MatchScore, Sprt, SprtStatus};
+use crate::game::epd::parse_epd;
+use crate::game::fen;
+use crate::game::pgn::{GameResult, parse_pgn, pgn_date, write_pgn};
+use crate::game::play::{EndReason, Game, Outcome};
+use crate::game::san::parse_san;
+use crate::storage::unix_time;
+use crate::tools::players::{EngineSpec, Player};
+
+const USAGE: &str = "Usage: match --engine <key=value>... --engine <key=value>... [--openings file.epd|file.pgn] [--plies n] \
+[--shuffle] [--games n] [--tc 40/60+0.5] [--concurrency n] [--pgn out.pgn] [--sprt elo0=0 elo1=5 alpha=0.05 beta=0.05] \
+[--draw movenumber=40 movecount=8 score=10] [--resign movecount=3 score=600] [--maxmoves n]";
+const STARTING_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+const RATING_INTERVAL: u32 = 10;
+// Searches recurse deeply, so games are played on threads with large stacks
+const WORKER_STACK_SIZE: usize = 32 * 1024 * 1024;
+
+// A position to start games from, with the moves that led there when it comes from a PGN
+#[derive(Debug, Clone)]
+struct Opening {
+    fen: String,
+    moves: Vec<(u8, u8, Option<Piece>)>
+}
+
+// Draw once both engines have called the game even for a while
+#[derive(Debug, Clone, Copy)]
+struct DrawAdjudication {
+    move_number: usize,
+    move_count: usize,
+    score: i32
+}
+
+// Loss for an engine that has seen itself lost for a while
+#[derive(Debug, Clone, Copy)]
+struct ResignAdjudication {
+    move_count: usize,
+    score: i32
+}
+
+struct MatchSettings {
+    engines: [EngineSpec; 2],
+    time_control: Option<(String, TimeControl)>,
+    draw: Option<DrawAdjudication>,
+    resign: Option<ResignAdjudication>,
+    max_moves: Option<usize>
+}
+
+struct PlayedGame {
+    game: Game,
+    result: GameResult,
+    // PGN Termination tag value
+    termination: &'static str,
+    // Seats whose engine failed and should be started again
+    failed: [bool; 2]
+}
+
+struct Finished {
+    round: usize,
+    // Whether the first engine had white
+    first_white: bool,
+    result: GameResult,
+    termination: &'static str,
+    pgn: String
+}
+
+// Usage: see USAGE
+// Plays two engine configurations against each other, each opening once with either colour, and reports the
+// Elo difference. With --sprt the match ends as soon as the test accepts one of its hypotheses.
+pub fn run(args: &[String]) -> Result<(), String> {
+    let mut engines = Vec::new();
+    let mut openings_path = None;
+    let mut plies = None;
+    let mut shuffle = false;
+    let mut games = None;
+    let mut time_control = None;
+    let mut concurrency = 1;
+    let mut pgn_path = None;
+    let mut sprt = None;
+    let mut draw = None;
+    let mut resign = None;
+    let mut max_moves = None;
+
+    let mut index = 0;
+    // Values of an option run until the next --flag
+    let take_values = |index: &mut usize| -> Vec<String> {
+        let start = *index;
+        while *index < args.len() && !args[*index].starts_with("--") {
+            *index += 1;
+        }
+        args[start..*index].to_vec()
+    };
+    while index < args.len() {
+        let flag = args[index].as_str();
+        index += 1;
+        let values = take_values(&mut index);
+        let single = || values.first().cloned().ok_or(format!("Missing value for {}", flag));
+        let invalid = || format!("Invalid value for {}", flag);
+        match flag {
+            "--engine" => engines.push(EngineSpec::parse(&values)?),
+            "--openings" => openings_path = Some(single()?),
+            "--plies" => plies = Some(single()?.parse::<usize>().map_err(|_| invalid())?),
+            "--shuffle" => shuffle = true,
+            "--games" => games = Some(single()?.parse::<usize>().ok().filter(|games| *games > 0).ok_or_else(invalid)?),
+            "--tc" => {
+                let text = single()?;
+                time_control = Some((text.clone(), text.parse::<TimeControl>()?));
+            }
+            "--concurrency" => concurrency = single()?.parse::<usize>().ok().filter(|threads| *threads > 0).ok_or_else(invalid)?,
+            "--pgn" => pgn_path = Some(single()?),
+            "--sprt" => {
+                let mut test = Sprt::default();
+                for (key, value) in key_values(&values)? {
+                    let value: f64 = value.parse().map_err(|_| invalid())?;
+                    match key {
+                        "elo0" => test.elo0 = value,
+                        "elo1" => test.elo1 = value,
+                        "alpha" => test.alpha = value,
+                        "beta" => test.beta = value,
+                        _ => return Err(format!("Unknown SPRT setting {}", key))
+                    }
+                }
+                if test.elo0 >= test.elo1 || !(0.0..0.5).contains(&test.alpha) || !(0.0..0.5).contains(&test.beta) || test.alpha == 0.0 || test.beta == 0.0 {
+                    return Err(invalid());
+                }
+                sprt = Some(test);
+            }
+            "--draw" => {
+                let mut rule = DrawAdjudication { move_number: 40, move_count: 8, score: 10 };
+                for (key, value) in key_values(&values)? {
+                    match key {
+                        "movenumber" => rule.move_number = value.parse().map_err(|_| invalid())?,
+                        "movecount" => rule.move_count = value.parse().ok().filter(|count| *count > 0).ok_or_else(invalid)?,
+                        "score" => rule.score = value.parse().map_err(|_| invalid())?,
+                        _ => return Err(format!("Unknown draw setting {}", key))
+                    }
+                }
+                draw = Some(rule);
+            }
+            "--resign" => {
+                let mut rule = ResignAdjudication { move_count: 3, score: 600 };
+                for (key, value) in key_values(&values)? {
+                    match key {
+                        "movecount" => rule.move_count = value.parse().ok().filter(|count| *count > 0).ok_or_else(invalid)?,
+                        "score" => rule.score = value.parse().map_err(|_| invalid())?,
+                        _ => return Err(format!("Unknown resign setting {}", key))
+                    }
+                }
+                resign = Some(rule);
+            }
+            "--maxmoves" => max_moves = Some(single()?.parse::<usize>().ok().filter(|moves| *moves > 0).ok_or_else(invalid)?),
+            _ => return Err(format!("Unknown option {}\n{}", flag, USAGE))
+        }
+    }
+    let engines: [EngineSpec; 2] = engines.try_into().map_err(|_| USAGE.to_string())?;
+    if time_control.is_none() && engines.iter().any(|engine| engine.limits.is_empty()) {
+        return Err("Every engine needs depth, nodes or movetime when there is no --tc".to_string());
+    }
+
+    let mut openings = match &openings_path {
+        Some(path) => load_openings(path, plies)?,
+        None => vec![Opening { fen: STARTING_FEN.to_string(), moves: Vec::new() }]
+    };
+    if openings.is_empty() {
+        return Err(format!("No usable openings in {}", openings_path.unwrap_or_default()));
+    }
+    if shuffle {
+        openings.shuffle(&mut rand::thread_rng());
+    }
+    let total = games.unwrap_or(openings.len() * 2);
+    let names = [engines[0].name.clone(), engines[1].name.clone()];
+    println!("{} vs {}: {} games from {} openings on {} threads", names[0], names[1], total, openings.len(), concurrency);
+
+    let mut pgn_file = match &pgn_path {
+        Some(path) => Some(File::create(path).map_err(|error| format!("Could not create {}: {}", path, error))?),
+        None => None
+    };
+    let settings = Arc::new(MatchSettings { engines, time_control, draw, resign, max_moves });
+    let openings = Arc::new(openings);
+    let next = Arc::new(AtomicUsize::new(0));
+    let stop = Arc::new(AtomicBool::new(false));
+    let (sender, receiver) = mpsc::channel::<Result<Finished, String>>();
+    let mut workers = Vec::new();
+    for _ in 0..concurrency.min(total) {
+        let (settings, openings, next, stop, sender) = (settings.clone(), openings.clone(), next.clone(), stop.clone(), sender.clone());
+        let worker = thread::Builder::new()
+            .name("match".to_string())
+            .stack_size(WORKER_STACK_SIZE)
+            .spawn(move || play_games(&settings, &openings, total, &next, &stop, &sender))
+            .map_err(|error| error.to_string())?;
+        workers.push(worker);
+    }
+    drop(sender);
+
+    let started = Instant::now();
+    let mut score = MatchScore::default();
+    let mut error = None;
+    let mut decision = SprtStatus::Continue;
+    for finished in receiver {
+        let finished = match finished {
+            Ok(finished) => finished,
+            Err(message) => {
+                stop.store(true, Ordering::Relaxed);
+                error.get_or_insert(message);
+                continue;
+            }
+        };
+        let (white, black) = if finished.first_white { (&names[0], &names[1]) } else { (&names[1], &names[0]) };
+        println!("Finished game {} ({} vs {}): {} {{{}}}", finished.round, white, black, finished.result.as_str(), finished.termination);
+        let first_won = match finished.result {
+            GameResult::WhiteWins => Some(finished.first_white),
+            GameResult::BlackWins => Some(!finished.first_white),
+            GameResult::Draw => None,
+            // Unfinished games count for nobody
+            GameResult::Unknown => continue
+        };
+        match first_won {
+            Some(true) => score.wins += 1,
+            Some(false) => score.losses += 1,
+            None => score.draws += 1
+        }
+        if let Some(file) = &mut pgn_file {
+            writeln!(file, "{}", finished.pgn).map_err(|error| format!("Could not write the PGN: {}", error))?;
+        }
+        println!("Score of {} vs {}: {}", names[0], names[1], score);
+        if score.games() % RATING_INTERVAL == 0 {
+            report(&score, sprt.as_ref());
+        }
+        if let Some(test) = &sprt {
+            if decision == SprtStatus::Continue {
+                decision = test.status(&score);
+                if decision != SprtStatus::Continue {
+                    stop.store(true, Ordering::Relaxed);
+                }
+            }
+        }
+    }
+    for worker in workers {
+        let _ = worker.join();
+    }
+    if let Some(message) = error {
+        return Err(message);
+    }
+    println!("Finished match in {:.1}s", started.elapsed().as_secs_f64());
+    report(&score, sprt.as_ref());
+    match decision {
+        SprtStatus::AcceptH0 => println!("SPRT: H0 accepted, {} is not stronger by elo1", names[0]),
+        SprtStatus::AcceptH1 => println!("SPRT: H1 accepted, {} is stronger", names[0]),
+        SprtStatus::Continue => {}
+    }
+    Ok(())
+}
+
+fn report(score: &MatchScore, sprt: Option<&Sprt>) {
+    let elo = match score.elo() {
+        Some((elo, margin)) => format!("{:.1} +/- {:.1}", elo, margin),
+        None => "unknown".to_string()
+    };
+    println!("Elo difference: {}, LOS: {:.1} %, DrawRatio: {:.1} %", elo, score.los() * 100.0, score.draw_ratio() * 100.0);
+    if let Some(test) = sprt {
+        println!(
+            "SPRT: llr {:.2}, lbound {:.2}, ubound {:.2} (elo0 {}, elo1 {}, alpha {}, beta {})",
+            test.llr(score), test.lower_bound(), test.upper_bound(), test.elo0, test.elo1, test.alpha, test.beta
+        );
+    }
+}
+
+fn key_values(values: &[String]) -> Result<Vec<(&str, &str)>, String> {
+    values.iter().map(|value| value.split_once('=').ok_or(format!("Expected key=value, got {}", value))).collect()
+}
+
+// The positions of an EPD or FEN file, one per line, or the games of a PGN file cut to their first plies
+fn load_openings(path: &str, plies: Option<usize>) -> Result<Vec<Opening>, String> {
+    let text = fs::read_to_string(path).map_err(|error| format!("Could not read {}: {}", path, error))?;
+    if path.ends_with(".pgn") {
+        let openings = parse_pgn(&text).iter().filter_map(|pgn| {
+            let fen = pgn.tag("FEN").unwrap_or(STARTING_FEN).to_string();
+            let mut game = Game::new(fen::new_board(&fen)?);
+            let mut moves = Vec::new();
+            for san in pgn.moves.iter().take(plies.unwrap_or(usize::MAX)) {
+                let mv = parse_san(game.board(), san)?;
+                game.play(mv.origin, mv.target, mv.get_promotion())?;
+                moves.push((mv.origin, mv.target, mv.get_promotion()));
+            }
+            (!game.is_over()).then_some(Opening { fen, moves })
+        }).collect();
+        return Ok(openings);
+    }
+    Ok(parse_epd(&text).into_iter().filter_map(|record| {
+        let game = Game::new(record.board()?);
+        (!game.is_over()).then_some(Opening { fen: record.fen, moves: Vec::new() })
+    }).collect())
+}
+
+// Takes games off the shared counter until there are none left or the match is stopped. Game 2n and 2n + 1
+// play the same opening with colours reversed.
+fn play_games(
+    settings: &MatchSettings,
+    openings: &[Opening],
+    total: usize,
+    next: &AtomicUsize,
+    stop: &AtomicBool,
+    results: &mpsc::Sender<Result<Finished, String>>
+) {
+    let mut players = Vec::new();
+    for engine in &settings.engines {
+        match engine.create() {
+            Ok(player) => players.push(player),
+            Err(error) => {
+                let _ = results.send(Err(format!("{}: {}", engine.name, error)));
+                return;
+            }
+        }
+    }
+    while !stop.load(Ordering::Relaxed) {
+        let round = next.fetch_add(1, Ordering::Relaxed);
+        if round >= total {
+            break;
+        }
+        let opening = &openings[(round / 2) % openings.len()];
+        let first_white = round.is_multiple_of(2);
+        let [first, second] = &mut players[..] else { unreachable!() };
+        let seats: [&mut dyn Player; 2] = if first_white { [first.as_mut(), second.as_mut()] } else { [second.as_mut(), first.as_mut()] };
+        let played = play_game(seats, opening, settings);
+
+        let engine_at = |team: Team| if (team == Teams::WHITE) == first_white { 0 } else { 1 };
+        let mut tags = vec![
+            ("Event".to_string(), "Caissa match".to_string()),
+            ("Site".to_string(), "local".to_string()),
+            ("Date".to_string(), pgn_date(unix_time())),
+            ("Round".to_string(), (round + 1).to_string()),
+            ("White".to_string(), settings.engines[engine_at(Teams::WHITE)].name.clone()),
+            ("Black".to_string(), settings.engines[engine_at(Teams::BLACK)].name.clone()),
+            ("TimeControl".to_string(), settings.time_control.as_ref().map_or("-".to_string(), |(text, _)| text.clone())),
+            ("Termination".to_string(), played.termination.to_string())
+        ];
+        if opening.fen != STARTING_FEN {
+            tags.push(("SetUp".to_string(), "1".to_string()));
+            tags.push(("FEN".to_string(), opening.fen.clone()));
+        }
+        let start = fen::new_board(&opening.fen).expect("Openings are checked when loaded");
+        let pgn = write_pgn(&tags, &start, played.game.moves(), played.result);
+        let finished = Finished { round: round + 1, first_white, result: played.result, termination: played.termination, pgn };
+        if results.send(Ok(finished)).is_err() {
+            return;
+        }
+        for team in [Teams::WHITE, Teams::BLACK] {
+            if played.failed[team] {
+                let engine = &settings.engines[engine_at(team)];
+                match engine.create() {
+                    Ok(player) => players[engine_at(team)] = player,
+                    Err(error) => {
+                        let _ = results.send(Err(format!("{}: {}", engine.name, error)));
+                        return;
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Plays a game with the players seated by team, until the rules, the clock or an adjudication end it
+fn play_game(seats: [&mut dyn Player; 2], opening: &Opening, settings: &MatchSettings) -> PlayedGame {
+    let mut game = Game::new(fen::new_board(&opening.fen).expect("Openings are checked when loaded"));
+    for (origin, target, promotion) in &opening.moves {
+        game.play(*origin, *target, *promotion);
+    }
+    let mut failed = [false; 2];
+    for team in [Teams::WHITE, Teams::BLACK] {
+        if seats[team].new_game().is_err() {
+            failed[team] = true;
+        }
+    }
+    if let Some(team) = [Teams::WHITE, Teams::BLACK].into_iter().find(|team| failed[*team]) {
+        return forfeit(game, team, "abandoned", failed);
+    }
+    let mut clock = settings.time_control.as_ref().map(|(_, control)| Clock::new(control.clone()));
+    // Each side's evaluations, from its own point of view
+    let mut scores: [Vec<Option<i32>>; 2] = [Vec::new(), Vec::new()];
+    loop {
+        if let Some(outcome) = game.outcome() {
+            let termination = match outcome.reason {
+                EndReason::Timeout | EndReason::TimeoutVsInsufficientMaterial => "time forfeit",
+                _ => "normal"
+            };
+            return PlayedGame { game, result: outcome.result, termination, failed };
+        }
+        let team = game.team_to_play();
+        let now = Instant::now();
+        if let Some(clock) = &mut clock {
+            if !clock.is_running() {
+                clock.start(team, now);
+            }
+        }
+        let reading = clock.as_ref().map(|clock| clock.reading(team, now));
+        let mv = match seats[team].play(&game, reading.as_ref()) {
+            Ok(mv) => mv,
+            Err(_) => {
+                failed[team] = true;
+                return forfeit(game, team, "abandoned", failed);
+            }
+        };
+        if let Some(clock) = &mut clock {
+            if !clock.press(Instant::now()) {
+                game.flag(team);
+                continue;
+            }
+        }
+        if game.play(mv.origin, mv.target, mv.promotion).is_none() {
+            return forfeit(game, team, "rules infraction", failed);
+        }
+        scores[team].push(mv.centipawns());
+
+        // Opening moves included
+        let full_moves = game.moves().len() / 2;
+        let recent = |team: Team, count: usize| {
+            let scores = &scores[team];
+            (scores.len() >= count).then(|| &scores[scores.len() - count..])
+        };
+        if let Some(rule) = settings.resign {
+            if recent(team, rule.move_count).is_some_and(|scores| scores.iter().all(|score| score.is_some_and(|score| score <= -rule.score))) {
+                return adjudicate(game, Outcome::win(get_opposite_team(team), EndReason::Resignation), failed);
+            }
+        }
+        if let Some(rule) = settings.draw {
+            let even = |team: Team| recent(team, rule.move_count).is_some_and(|scores| scores.iter().all(|score| score.is_some_and(|score| score.abs() <= rule.score)));
+            if full_moves >= rule.move_number && even(Teams::WHITE) && even(Teams::BLACK) {
+                return adjudicate(game, Outcome::draw(EndReason::Agreement), failed);
+            }
+        }
+        if settings.max_moves.is_some_and(|max_moves| full_moves >= max_moves) {
+            return adjudicate(game, Outcome::draw(EndReason::Agreement), failed);
+        }
+    }
+}
+
+fn forfeit(game: Game, team: Team, termination: &'static str, failed: [bool; 2]) -> PlayedGame {
+    let result = if team == Teams::WHITE { GameResult::BlackWins } else { GameResult::WhiteWins };
+    PlayedGame { game, result, termination, failed }
+}
+
+fn adjudicate(mut game: Game, outcome: Outcome, failed: [bool; 2]) -> PlayedGame {
+    game.end(outcome);
+    PlayedGame { game, result: outcome.result, termination: "adjudication", failed }
+}
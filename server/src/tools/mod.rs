@@ -0,0 +1,9 @@
+// Offline commands run through the server binary, e.g. `server tune positions.epd`
+pub mod bench;
+pub mod book;
+pub mod matches;
+pub mod perft;
+pub mod players;
+pub mod suite;
+pub mod tune;
+pub mod uci;
@@ -0,0 +1,44 @@
+use std::time::Instant;
+
+use crate::board::variant::Variant;
+use crate::game::fen;
+use crate::game::play::{legal_moves, perft};
+
+// Usage: perft <depth> [--variant <name>] [--fen <fen>]
+// Counts the leaves of the legal move tree below each move of a position, the variant's start by default.
+// The known counts of every variant are checked by the perft tests.
+pub fn run(args: &[String]) -> Result<(), String> {
+    let mut depth = None;
+    let mut variant = Variant::Standard;
+    let mut fen = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("Missing value for {}", arg));
+        match arg.as_str() {
+            "--variant" => variant = value()?.parse()?,
+            "--fen" => fen = Some(value()?.clone()),
+            depth_arg => depth = Some(depth_arg.parse::<u8>().ok().filter(|depth| *depth > 0).ok_or(format!("Invalid depth {}", depth_arg))?)
+        }
+    }
+    let depth = depth.ok_or("Missing depth")?;
+    divide(variant, fen.as_deref().unwrap_or(variant.start_fen()), depth)
+}
+
+fn divide(variant: Variant, fen: &str, depth: u8) -> Result<(), String> {
+    let mut board = fen::new_variant_board(fen, variant).ok_or(format!("Invalid FEN {}", fen))?;
+    let started = Instant::now();
+    let mut total = 0;
+    for mv in legal_moves(&board) {
+        let state = board.state;
+        let Some(played) = board.play_move(mv.origin, mv.target, mv.get_promotion(), true) else { continue };
+        let nodes = perft(&mut board, depth - 1);
+        board.undo_move(&played);
+        board.state = state;
+        println!("{}: {}", mv, nodes);
+        total += nodes;
+    }
+    println!();
+    println!("Nodes searched  : {}", total);
+    println!("Total time (ms) : {}", started.elapsed().as_millis());
+    Ok(())
+}
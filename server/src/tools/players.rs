@@ -0,0 +1,358 @@
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::board::{Piece, Teams};
+use crate::engine::{ChessEngine, MATE_SCORE, SearchLimits, time};
+use crate::engine::evaluator::{AnyEvaluator, EvaluatorKind, EvaluatorRegistry};
+use crate::engine::info::Score;
+use crate::engine::minimax::MinimaxEngine;
+use crate::engine::nnue::Network;
+use crate::engine::transposition::TranspositionTable;
+use crate::game::clock::ClockReading;
+use crate::game::play::Game;
+use crate::game::square::parse_long_algebraic;
+
+// Depth bound of searches limited by time or nodes only
+pub const UNBOUNDED_DEPTH: u8 = 64;
+// Extra time an external engine gets past its clock before it is told to stop, then before it is given up on
+const HANG_MARGIN: Duration = Duration::from_secs(5);
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+const STANDARD_START: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+// A move in long algebraic notation with what the engine thought of the position, from its own side
+#[derive(Debug, Clone)]
+pub struct PlayerMove {
+    pub origin: u8,
+    pub target: u8,
+    pub promotion: Option<Piece>,
+    pub score: Option<Score>
+}
+
+impl PlayerMove {
+    pub fn parse(text: &str) -> Option<PlayerMove> {
+        let (origin, target, promotion) = parse_long_algebraic(text)?;
+        Some(PlayerMove { origin, target, promotion, score: None })
+    }
+
+    // The score in centipawns, mates counting as the largest scores there are
+    pub fn centipawns(&self) -> Option<i32> {
+        self.score.map(|score| match score {
+            Score::Cp(cp) => cp,
+            Score::Mate(moves) => if moves > 0 { MATE_SCORE } else { -MATE_SCORE }
+        })
+    }
+}
+
+// One side of a match
+pub trait Player: Send {
+    fn new_game(&mut self) -> Result<(), String>;
+    // Searches the side to move's move, under the clock when the game has one
+    fn play(&mut self, game: &Game, clock: Option<&ClockReading>) -> Result<PlayerMove, String>;
+}
+
+// Per-move limits given on the command line, on top of the clock if any
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MoveLimits {
+    pub depth: Option<u8>,
+    pub nodes: Option<u64>,
+    pub movetime: Option<Duration>
+}
+
+impl MoveLimits {
+    pub fn is_empty(&self) -> bool {
+        self.depth.is_none() && self.nodes.is_none() && self.movetime.is_none()
+    }
+}
+
+#[derive(Clone)]
+pub enum EngineKind {
+    Internal { evaluators: EvaluatorRegistry, evaluator: EvaluatorKind, threads: usize, hash: usize, skill: u8 },
+    Uci { command: String, args: Vec<String>, options: Vec<(String, String)> }
+}
+
+// An engine configuration as given to the match command, e.g. name=base evaluator=material depth=5
+#[derive(Clone)]
+pub struct EngineSpec {
+    pub name: String,
+    pub kind: EngineKind,
+    pub limits: MoveLimits
+}
+
+impl EngineSpec {
+    // Keys: name, cmd, arg, option.<name> for external engines, evaluator, nnue, threads, hash and skill for
+    // this one, and depth, nodes and movetime (milliseconds) for both
+    pub fn parse(args: &[String]) -> Result<EngineSpec, String> {
+        let mut name = None;
+        let mut command = None;
+        let mut engine_args = Vec::new();
+        let mut options = Vec::new();
+        let mut evaluator = None;
+        let mut network = None;
+        let mut threads = 1;
+        let mut hash = TranspositionTable::DEFAULT_SIZE_MB;
+        let mut skill = SearchLimits::MAX_SKILL;
+        let mut limits = MoveLimits::default();
+        for arg in args {
+            let (key, value) = arg.split_once('=').ok_or(format!("Expected key=value, got {}", arg))?;
+            let invalid = || format!("Invalid value for {}: {}", key, value);
+            match key {
+                "name" => name = Some(value.to_string()),
+                "cmd" => command = Some(value.to_string()),
+                "arg" => engine_args.push(value.to_string()),
+                "evaluator" => evaluator = Some(value.parse::<EvaluatorKind>().map_err(|_| invalid())?),
+                "nnue" => network = Some(Network::load(value).map_err(|error| format!("Could not load network {}: {}", value, error))?),
+                "threads" => threads = value.parse().map_err(|_| invalid())?,
+                "hash" => hash = value.parse().map_err(|_| invalid())?,
+                "skill" => skill = value.parse().ok().filter(|skill| *skill <= SearchLimits::MAX_SKILL).ok_or_else(invalid)?,
+                "depth" => limits.depth = Some(value.parse().ok().filter(|depth| *depth > 0).ok_or_else(invalid)?),
+                "nodes" => limits.nodes = Some(value.parse().ok().filter(|nodes| *nodes > 0).ok_or_else(invalid)?),
+                "movetime" => limits.movetime = Some(Duration::from_millis(value.parse().ok().filter(|ms| *ms > 0).ok_or_else(invalid)?)),
+                _ => match key.strip_prefix("option.") {
+                    Some(option) => options.push((option.to_string(), value.to_string())),
+                    None => return Err(format!("Unknown engine setting {}", key))
+                }
+            }
+        }
+        let kind = match command {
+            Some(command) => EngineKind::Uci { command, args: engine_args, options },
+            None => {
+                let default = evaluator.unwrap_or(if network.is_some() { EvaluatorKind::Nnue } else { EvaluatorKind::default() });
+                let evaluators = EvaluatorRegistry::new(default, network);
+                if evaluators.create(default).is_none() {
+                    return Err("evaluator=nnue requires nnue=<network>".to_string());
+                }
+                EngineKind::Internal { evaluators, evaluator: default, threads, hash, skill }
+            }
+        };
+        let name = name.unwrap_or_else(|| match &kind {
+            EngineKind::Internal { evaluator, .. } => format!("Caissa ({})", evaluator.name()),
+            EngineKind::Uci { command, .. } => command.rsplit('/').next().unwrap_or(command).to_string()
+        });
+        Ok(EngineSpec { name, kind, limits })
+    }
+
+    pub fn create(&self) -> Result<Box<dyn Player>, String> {
+        Ok(match &self.kind {
+            EngineKind::Internal { evaluators, evaluator, threads, hash, skill } => {
+                let mut engine = MinimaxEngine::with_evaluator(evaluators.create(*evaluator).expect("Checked when parsed"));
+                engine.set_threads(*threads);
+                engine.set_hash_size(*hash);
+                Box::new(InternalPlayer { engine, limits: self.limits, skill: *skill })
+            }
+            EngineKind::Uci { command, args, options } => Box::new(UciPlayer::start(command, args, options, self.limits)?)
+        })
+    }
+}
+
+pub struct InternalPlayer {
+    engine: MinimaxEngine<AnyEvaluator>,
+    limits: MoveLimits,
+    skill: u8
+}
+
+impl Player for InternalPlayer {
+    fn new_game(&mut self) -> Result<(), String> {
+        self.engine.clear_hash();
+        Ok(())
+    }
+
+    fn play(&mut self, game: &Game, clock: Option<&ClockReading>) -> Result<PlayerMove, String> {
+        let team = game.team_to_play();
+        let movetime = match (clock, self.limits.movetime) {
+            (Some(clock), movetime) => Some(movetime.map_or(time::allocate(clock, team), |movetime| movetime.min(time::allocate(clock, team)))),
+            (None, movetime) => movetime
+        };
+        let limits = SearchLimits {
+            depth: self.limits.depth.unwrap_or(UNBOUNDED_DEPTH),
+            movetime,
+            nodes: self.limits.nodes,
+            multipv: 1,
+            skill: self.skill
+        };
+        let result = self.engine.search(game.board(), &limits).ok_or("No legal moves to play")?;
+        Ok(PlayerMove { origin: result.best_move.origin, target: result.best_move.target, promotion: result.best_move.get_promotion(), score: result.score })
+    }
+}
+
+// An engine binary spoken to over UCI
+pub struct UciPlayer {
+    child: Child,
+    stdin: ChildStdin,
+    lines: Receiver<String>,
+    limits: MoveLimits,
+    // Last value sent for UCI_Chess960, following the games' start positions
+    chess960: bool
+}
+
+impl UciPlayer {
+    pub fn start(command: &str, args: &[String], options: &[(String, String)], limits: MoveLimits) -> Result<UciPlayer, String> {
+        let mut child = Command::new(command)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|error| format!("Could not start {}: {}", command, error))?;
+        let stdin = child.stdin.take().unwrap();
+        let stdout = child.stdout.take().unwrap();
+        // Read on a thread of its own so a silent engine can be timed out
+        let (sender, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        let mut player = UciPlayer { child, stdin, lines, limits, chess960: false };
+        player.send("uci")?;
+        player.wait_for("uciok", HANDSHAKE_TIMEOUT)?;
+        for (name, value) in options {
+            player.send(&format!("setoption name {} value {}", name, value))?;
+        }
+        player.send("isready")?;
+        player.wait_for("readyok", HANDSHAKE_TIMEOUT)?;
+        Ok(player)
+    }
+
+    fn send(&mut self, command: &str) -> Result<(), String> {
+        writeln!(self.stdin, "{}", command).and_then(|_| self.stdin.flush()).map_err(|_| "Engine exited".to_string())
+    }
+
+    fn receive(&self, timeout: Duration) -> Result<Option<String>, String> {
+        match self.lines.recv_timeout(timeout) {
+            Ok(line) => Ok(Some(line)),
+            Err(RecvTimeoutError::Timeout) => Ok(None),
+            Err(RecvTimeoutError::Disconnected) => Err("Engine exited".to_string())
+        }
+    }
+
+    fn wait_for(&self, expected: &str, timeout: Duration) -> Result<(), String> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let line = self.receive(deadline.saturating_duration_since(Instant::now()))?
+                .ok_or(format!("Engine did not answer {}", expected))?;
+            if line.trim() == expected {
+                return Ok(());
+            }
+        }
+    }
+
+    fn go_command(&self, clock: Option<&ClockReading>) -> String {
+        let mut command = "go".to_string();
+        if let Some(clock) = clock {
+            command += &format!(" wtime {} btime {} winc {} binc {}", clock.white_ms, clock.black_ms, clock.increment_ms, clock.increment_ms);
+            if let Some(moves) = clock.moves_to_go {
+                command += &format!(" movestogo {}", moves);
+            }
+        }
+        if let Some(depth) = self.limits.depth {
+            command += &format!(" depth {}", depth);
+        }
+        if let Some(nodes) = self.limits.nodes {
+            command += &format!(" nodes {}", nodes);
+        }
+        if let Some(movetime) = self.limits.movetime {
+            command += &format!(" movetime {}", movetime.as_millis());
+        }
+        command
+    }
+}
+
+impl Player for UciPlayer {
+    fn new_game(&mut self) -> Result<(), String> {
+        self.send("ucinewgame")?;
+        self.send("isready")?;
+        self.wait_for("readyok", HANDSHAKE_TIMEOUT)
+    }
+
+    fn play(&mut self, game: &Game, clock: Option<&ClockReading>) -> Result<PlayerMove, String> {
+        let start = if game.start_fen() == STANDARD_START { "startpos".to_string() } else { format!("fen {}", game.start_fen()) };
+        let chess960 = game.board().state.chess960;
+        if chess960 != self.chess960 {
+            self.send(&format!("setoption name UCI_Chess960 value {}", chess960))?;
+            self.chess960 = chess960;
+        }
+        let mut position = format!("position {}", start);
+        if !game.moves().is_empty() {
+            position += " moves";
+            for mv in game.moves() {
+                position += &format!(" {}", mv);
+            }
+        }
+        self.send(&position)?;
+        self.send(&self.go_command(clock))?;
+
+        // Searches bound only by depth or nodes may take as long as they need
+        let team = game.team_to_play();
+        let budget = clock.map(|clock| Duration::from_millis(if team == Teams::WHITE { clock.white_ms } else { clock.black_ms }))
+            .or(self.limits.movetime);
+        let mut deadline = budget.map(|budget| Instant::now() + budget + HANG_MARGIN);
+        let mut stopped = false;
+        let mut score = None;
+        loop {
+            let line = match deadline {
+                Some(deadline) => self.receive(deadline.saturating_duration_since(Instant::now()))?,
+                None => Some(self.lines.recv().map_err(|_| "Engine exited".to_string())?)
+            };
+            let Some(line) = line else {
+                if stopped {
+                    return Err("Engine stopped responding".to_string());
+                }
+                self.send("stop")?;
+                stopped = true;
+                deadline = Some(Instant::now() + HANG_MARGIN);
+                continue;
+            };
+            let mut tokens = line.split_whitespace();
+            match tokens.next() {
+                Some("info") => {
+                    let tokens: Vec<&str> = tokens.collect();
+                    // Only the best line's score counts under multi-PV
+                    let multipv = tokens.windows(2).find(|pair| pair[0] == "multipv").and_then(|pair| pair[1].parse::<u32>().ok());
+                    if multipv.is_none_or(|multipv| multipv == 1) {
+                        if let Some(parsed) = parse_score(&tokens) {
+                            score = Some(parsed);
+                        }
+                    }
+                }
+                Some("bestmove") => {
+                    let text = tokens.next().ok_or("Engine sent no best move")?;
+                    let mut mv = PlayerMove::parse(text).ok_or(format!("Engine sent an unreadable move {}", text))?;
+                    mv.score = score;
+                    return Ok(mv);
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+impl Drop for UciPlayer {
+    fn drop(&mut self) {
+        let _ = self.send("quit");
+        let deadline = Instant::now() + Duration::from_secs(1);
+        while Instant::now() < deadline {
+            if let Ok(Some(_)) = self.child.try_wait() {
+                return;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+// The score of an info line, such as "score cp 35" or "score mate -3", bounds included
+fn parse_score(tokens: &[&str]) -> Option<Score> {
+    let index = tokens.iter().position(|token| *token == "score")?;
+    let value = tokens.get(index + 2)?.parse().ok()?;
+    match *tokens.get(index + 1)? {
+        "cp" => Some(Score::Cp(value)),
+        "mate" => Some(Score::Mate(value)),
+        _ => None
+    }
+}
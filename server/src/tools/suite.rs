@@ -0,0 +1,166 @@
+use std::fs;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::board::CompletedMove;
+use crate::engine::{ChessEngine, SearchLimits};
+use crate::engine::evaluator::{EvaluatorKind, EvaluatorRegistry};
+use crate::engine::info::{Score, SearchInfo};
+use crate::engine::minimax::MinimaxEngine;
+use crate::engine::nnue::Network;
+use crate::engine::transposition::TranspositionTable;
+use crate::game::epd::{EpdRecord, parse_epd};
+use crate::game::san::to_san;
+use crate::tools::players::UNBOUNDED_DEPTH;
+
+const USAGE: &str = "Usage: suite <positions.epd> [--movetime ms] [--depth n] [--nodes n] [--evaluator kind] [--nnue file] \
+[--threads n] [--hash mb]";
+const DEFAULT_MOVETIME: Duration = Duration::from_secs(1);
+
+// What a position asks for: a best move, a move to avoid, or a mate in so many moves
+enum Goal {
+    Best(Vec<CompletedMove>),
+    Avoid(Vec<CompletedMove>),
+    Mate(i32)
+}
+
+impl Goal {
+    fn of(record: &EpdRecord) -> Result<Goal, &'static str> {
+        if let Some(moves) = record.operation("dm") {
+            return moves.first().and_then(|moves| moves.parse().ok()).filter(|moves| *moves > 0).map(Goal::Mate).ok_or("invalid dm");
+        }
+        let moves = |opcode| record.moves(opcode).ok_or("move the board cannot play");
+        match (record.operation("bm"), record.operation("am")) {
+            (Some(_), _) => Ok(Goal::Best(moves("bm")?)),
+            (None, Some(_)) => Ok(Goal::Avoid(moves("am")?)),
+            (None, None) => Err("no bm, am or dm")
+        }
+    }
+
+    fn is_met(&self, best_move: &CompletedMove, score: Score) -> bool {
+        let same = |mv: &&CompletedMove| mv.origin == best_move.origin && mv.target == best_move.target && mv.get_promotion() == best_move.get_promotion();
+        match self {
+            Goal::Best(moves) => moves.iter().any(|mv| same(&mv)),
+            Goal::Avoid(moves) => !moves.iter().any(|mv| same(&mv)),
+            Goal::Mate(moves) => matches!(score, Score::Mate(found) if found > 0 && found <= *moves)
+        }
+    }
+}
+
+// Usage: suite <positions.epd> [--movetime ms] [--depth n] [--nodes n] [--evaluator kind] [--nnue file] [--threads n] [--hash mb]
+// Searches every position of a test suite such as WAC or ECM and checks the move against its bm, am or dm
+// operation. A position counts as solved from the iteration the engine settled on a right answer, which gives
+// the time to solution. One second a position when no limit is given.
+pub fn run(args: &[String]) -> Result<(), String> {
+    let input = args.first().ok_or(USAGE)?;
+    let mut limits = SearchLimits::depth(UNBOUNDED_DEPTH);
+    let mut evaluator = None;
+    let mut network = None;
+    let mut threads = 1;
+    let mut hash = TranspositionTable::DEFAULT_SIZE_MB;
+
+    let mut args = args[1..].iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("Missing value for {}", arg));
+        let invalid = || format!("Invalid value for {}", arg);
+        match arg.as_str() {
+            "--movetime" => limits.movetime = Some(Duration::from_millis(value()?.parse().ok().filter(|ms| *ms > 0).ok_or_else(invalid)?)),
+            "--depth" => limits.depth = value()?.parse().ok().filter(|depth| *depth > 0).ok_or_else(invalid)?,
+            "--nodes" => limits.nodes = Some(value()?.parse().ok().filter(|nodes| *nodes > 0).ok_or_else(invalid)?),
+            "--evaluator" => evaluator = Some(value()?.parse::<EvaluatorKind>().map_err(|_| invalid())?),
+            "--nnue" => {
+                let path = value()?;
+                network = Some(Network::load(path).map_err(|error| format!("Could not load network {}: {}", path, error))?);
+            }
+            "--threads" => threads = value()?.parse().map_err(|_| invalid())?,
+            "--hash" => hash = value()?.parse().map_err(|_| invalid())?,
+            _ => return Err(format!("Unknown option {}\n{}", arg, USAGE))
+        }
+    }
+    if limits.depth == UNBOUNDED_DEPTH && limits.movetime.is_none() && limits.nodes.is_none() {
+        limits.movetime = Some(DEFAULT_MOVETIME);
+    }
+
+    let text = fs::read_to_string(input).map_err(|error| format!("Could not read {}: {}", input, error))?;
+    let records = parse_epd(&text);
+    if records.is_empty() {
+        return Err(format!("No positions found in {}", input));
+    }
+    let kind = evaluator.unwrap_or(if network.is_some() { EvaluatorKind::Nnue } else { EvaluatorKind::default() });
+    let evaluators = EvaluatorRegistry::new(kind, network);
+    let mut engine = MinimaxEngine::with_evaluator(evaluators.create(kind).ok_or("--evaluator nnue requires --nnue")?);
+    engine.set_threads(threads);
+    engine.set_hash_size(hash);
+    // Every iteration of the current search, to find when it settled on its answer
+    let iterations: Arc<Mutex<Vec<SearchInfo>>> = Arc::default();
+    let recorded = iterations.clone();
+    engine.set_info_callback(move |info| recorded.lock().unwrap().push(info.clone()));
+
+    let started = Instant::now();
+    let mut solved = 0;
+    let mut solution_time = Duration::ZERO;
+    let mut failures = Vec::new();
+    let mut skipped = Vec::new();
+    for (index, record) in records.iter().enumerate() {
+        let id = record.id().map_or(format!("#{}", index + 1), str::to_string);
+        let (Some(board), goal) = (record.board(), Goal::of(record)) else {
+            skipped.push(format!("{}: invalid position", id));
+            continue;
+        };
+        let goal = match goal {
+            Ok(goal) => goal,
+            Err(reason) => {
+                skipped.push(format!("{}: {}", id, reason));
+                continue;
+            }
+        };
+        engine.clear_hash();
+        iterations.lock().unwrap().clear();
+        let Some(result) = engine.search(&board, &limits) else {
+            skipped.push(format!("{}: no legal moves", id));
+            continue;
+        };
+        let iterations = iterations.lock().unwrap();
+        let score = result.score.unwrap_or(Score::Cp(0));
+        let san = to_san(&board, &result.best_move);
+        if goal.is_met(&result.best_move, score) {
+            // The first iteration of the streak of right answers that lasted to the end
+            let settled = iterations.iter().rposition(|info| !info.pv.first().is_some_and(|mv| goal.is_met(mv, info.score)))
+                .map_or(0, |wrong| wrong + 1);
+            let found = iterations.get(settled).map_or(Duration::ZERO, |info| Duration::from_millis(info.elapsed_ms));
+            let depth = iterations.get(settled).map_or(result.depth, |info| info.depth);
+            solved += 1;
+            solution_time += found;
+            println!("ok   {} {} ({}) in {} ms at depth {}", id, san, score, found.as_millis(), depth);
+        } else {
+            let expected = record.operation(record_kind(&goal)).unwrap_or_default().join(" ");
+            println!("FAIL {} {} ({}), expected {} {}", id, san, score, record_kind(&goal), expected);
+            failures.push(id);
+        }
+    }
+
+    let attempted = records.len() - skipped.len();
+    println!();
+    println!("Solved {} of {} in {:.1}s", solved, attempted, started.elapsed().as_secs_f64());
+    if solved > 0 {
+        println!("Average time to solution {} ms", (solution_time / solved).as_millis());
+    }
+    if !failures.is_empty() {
+        println!("Failed: {}", failures.join(", "));
+    }
+    if !skipped.is_empty() {
+        println!("Skipped {}:", skipped.len());
+        for reason in &skipped {
+            println!("  {}", reason);
+        }
+    }
+    Ok(())
+}
+
+fn record_kind(goal: &Goal) -> &'static str {
+    match goal {
+        Goal::Best(_) => "bm",
+        Goal::Avoid(_) => "am",
+        Goal::Mate(_) => "dm"
+    }
+}
@@ -0,0 +1,283 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+
+use crate::board::{Pieces, Team, Teams};
+use crate::board::board::ChessBoard;
+use crate::engine::endgame::evaluate_endgame;
+use crate::engine::evaluator::{Evaluator, HandCraftedEvaluator};
+use crate::engine::pawn_structure::{BACKWARD_PENALTY, CONNECTED_BONUS, DOUBLED_PENALTY, FREE_PATH_BONUS, ISOLATED_PENALTY, PASSED_BONUS_ENDGAME, PASSED_BONUS_MIDDLEGAME, trace_free_paths, trace_team_pawns};
+use crate::engine::PIECE_VALUES;
+use crate::engine::pst::{ENDGAME_TABLES, MAX_PHASE, MIDDLEGAME_TABLES, table_index};
+use crate::game::fen;
+use crate::math::iterate_bits;
+
+// Layout of the parameter vector. The king has no tunable value.
+const PIECE_VALUE_OFFSET: usize = 0;
+const MIDDLEGAME_TABLE_OFFSET: usize = PIECE_VALUE_OFFSET + 5;
+const ENDGAME_TABLE_OFFSET: usize = MIDDLEGAME_TABLE_OFFSET + 6 * 64;
+const DOUBLED_OFFSET: usize = ENDGAME_TABLE_OFFSET + 6 * 64;
+const ISOLATED_OFFSET: usize = DOUBLED_OFFSET + 2;
+const BACKWARD_OFFSET: usize = ISOLATED_OFFSET + 2;
+const CONNECTED_OFFSET: usize = BACKWARD_OFFSET + 2;
+const PASSED_MIDDLEGAME_OFFSET: usize = CONNECTED_OFFSET + 8;
+const PASSED_ENDGAME_OFFSET: usize = PASSED_MIDDLEGAME_OFFSET + 8;
+const FREE_PATH_OFFSET: usize = PASSED_ENDGAME_OFFSET + 8;
+const PARAMETER_COUNT: usize = FREE_PATH_OFFSET + 8;
+
+const DEFAULT_EPOCHS: usize = 1000;
+const LEARNING_RATE: f64 = 1.0;
+
+// A labelled position reduced to the linear form offset + sum(coefficient * parameter), from white's point of view
+struct TuningPosition {
+    coefficients: Vec<(u16, f32)>,
+    // Everything the tuner does not touch (mobility, king safety, rounding)
+    offset: f32,
+    result: f32
+}
+
+// Usage: tune <positions> [output.rs] [epochs]
+// Each line of the positions file holds a FEN followed by the game result, either as 1-0, 0-1 and 1/2-1/2
+// or as [1.0], [0.0] and [0.5], always from white's point of view. Quiet positions give the best results.
+pub fn run(args: &[String]) -> Result<(), String> {
+    let input = args.first().ok_or("Usage: tune <positions> [output.rs] [epochs]")?;
+    let output = args.get(1).map(String::as_str).unwrap_or("tuned.rs");
+    let epochs = match args.get(2) {
+        Some(epochs) => epochs.parse().map_err(|_| format!("Invalid epoch count {}", epochs))?,
+        None => DEFAULT_EPOCHS
+    };
+
+    let initial = initial_parameters();
+    let positions = load_positions(input, &initial)?;
+    if positions.is_empty() {
+        return Err(format!("No labelled positions found in {}", input));
+    }
+    println!("Loaded {} positions", positions.len());
+
+    let k = fit_scaling_constant(&positions, &initial);
+    println!("Scaling constant K = {:.4}, initial loss {:.6}", k, loss(&positions, &initial, k));
+
+    let mut parameters = initial;
+    optimise(&positions, &mut parameters, k, epochs);
+    let final_loss = loss(&positions, &parameters, k);
+    println!("Final loss {:.6}", final_loss);
+
+    write_rust_source(output, &parameters, positions.len(), final_loss)
+        .map_err(|error| format!("Could not write {}: {}", output, error))?;
+    println!("Wrote tuned parameters to {}", output);
+    Ok(())
+}
+
+fn initial_parameters() -> Vec<f64> {
+    let mut parameters = vec![0.0; PARAMETER_COUNT];
+    for piece in Pieces::PAWN..Pieces::KING {
+        parameters[PIECE_VALUE_OFFSET + piece] = PIECE_VALUES[piece] as f64;
+    }
+    for piece in Pieces::iter() {
+        for square in 0..64 {
+            parameters[MIDDLEGAME_TABLE_OFFSET + piece * 64 + square] = MIDDLEGAME_TABLES[piece][square] as f64;
+            parameters[ENDGAME_TABLE_OFFSET + piece * 64 + square] = ENDGAME_TABLES[piece][square] as f64;
+        }
+    }
+    for (offset, (middlegame, endgame)) in [(DOUBLED_OFFSET, DOUBLED_PENALTY), (ISOLATED_OFFSET, ISOLATED_PENALTY), (BACKWARD_OFFSET, BACKWARD_PENALTY)] {
+        parameters[offset] = middlegame as f64;
+        parameters[offset + 1] = endgame as f64;
+    }
+    for rank in 0..8 {
+        parameters[CONNECTED_OFFSET + rank] = CONNECTED_BONUS[rank] as f64;
+        parameters[PASSED_MIDDLEGAME_OFFSET + rank] = PASSED_BONUS_MIDDLEGAME[rank] as f64;
+        parameters[PASSED_ENDGAME_OFFSET + rank] = PASSED_BONUS_ENDGAME[rank] as f64;
+        parameters[FREE_PATH_OFFSET + rank] = FREE_PATH_BONUS[rank] as f64;
+    }
+    parameters
+}
+
+fn load_positions(path: &str, initial: &[f64]) -> Result<Vec<TuningPosition>, String> {
+    let file = File::open(path).map_err(|error| format!("Could not open {}: {}", path, error))?;
+    let mut evaluator = HandCraftedEvaluator::default();
+    let mut positions = Vec::new();
+    for line in BufReader::new(file).lines() {
+        let line = line.map_err(|error| error.to_string())?;
+        let Some((board, result)) = parse_labelled_position(&line) else { continue };
+        // The dedicated endgame scores replace the terms being tuned, and would otherwise end up in the offset
+        if evaluate_endgame(&board, Teams::WHITE).is_some() {
+            continue;
+        }
+        let coefficients = trace_position(&board);
+        let linear: f64 = coefficients.iter().map(|(index, coefficient)| *coefficient as f64 * initial[*index as usize]).sum();
+        let offset = evaluator.evaluate(&board, Teams::WHITE) as f64 - linear;
+        positions.push(TuningPosition { coefficients, offset: offset as f32, result });
+    }
+    Ok(positions)
+}
+
+pub fn parse_labelled_position(line: &str) -> Option<(ChessBoard, f32)> {
+    let result = if line.contains("1/2-1/2") || line.contains("[0.5]") {
+        0.5
+    } else if line.contains("1-0") || line.contains("[1.0]") {
+        1.0
+    } else if line.contains("0-1") || line.contains("[0.0]") {
+        0.0
+    } else {
+        return None;
+    };
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    if fields.len() < 4 {
+        return None;
+    }
+    // EPD records stop after the en passant field, so the move counters are optional
+    let counters = match (fields.get(4), fields.get(5)) {
+        (Some(halfmove), Some(fullmove)) if halfmove.parse::<u32>().is_ok() && fullmove.parse::<u32>().is_ok() => format!("{} {}", halfmove, fullmove),
+        _ => "0 1".to_string()
+    };
+    let fen = format!("{} {} {} {} {}", fields[0], fields[1], fields[2], fields[3], counters);
+    fen::new_board(&fen).map(|board| (board, result))
+}
+
+// Coefficient of every parameter in the tapered evaluation of the position
+fn trace_position(board: &ChessBoard) -> Vec<(u16, f32)> {
+    let mut dense = vec![0.0f64; PARAMETER_COUNT];
+    let middlegame_weight = board.psqt.phase() as f64 / MAX_PHASE as f64;
+    let endgame_weight = 1.0 - middlegame_weight;
+
+    for team in [Teams::WHITE, Teams::BLACK] {
+        let sign = if team == Teams::WHITE { 1.0 } else { -1.0 };
+        for piece in Pieces::iter() {
+            iterate_bits(board.bits.get_pieces(team, piece).0, |bit| {
+                let index = table_index(team, bit.trailing_zeros() as usize);
+                if piece != Pieces::KING {
+                    dense[PIECE_VALUE_OFFSET + piece] += sign;
+                }
+                dense[MIDDLEGAME_TABLE_OFFSET + piece * 64 + index] += sign * middlegame_weight;
+                dense[ENDGAME_TABLE_OFFSET + piece * 64 + index] += sign * endgame_weight;
+            });
+        }
+        trace_pawns(board, team, sign, middlegame_weight, &mut dense);
+    }
+
+    dense.iter()
+        .enumerate()
+        .filter(|(_, coefficient)| coefficient.abs() > 1e-9)
+        .map(|(index, coefficient)| (index as u16, *coefficient as f32))
+        .collect()
+}
+
+fn trace_pawns(board: &ChessBoard, team: Team, sign: f64, middlegame_weight: f64, dense: &mut [f64]) {
+    let endgame_weight = 1.0 - middlegame_weight;
+    let own = board.bits.get_pieces(team, Pieces::PAWN).0;
+    let enemy = board.bits.get_pieces(1 - team, Pieces::PAWN).0;
+    let trace = trace_team_pawns(team, own, enemy);
+    for (offset, count) in [(DOUBLED_OFFSET, trace.doubled), (ISOLATED_OFFSET, trace.isolated), (BACKWARD_OFFSET, trace.backward)] {
+        dense[offset] += sign * count as f64 * middlegame_weight;
+        dense[offset + 1] += sign * count as f64 * endgame_weight;
+    }
+    let free_paths = trace_free_paths(board, team, trace.passed_pawns);
+    for rank in 0..8 {
+        dense[CONNECTED_OFFSET + rank] += sign * trace.connected[rank] as f64;
+        dense[PASSED_MIDDLEGAME_OFFSET + rank] += sign * trace.passed[rank] as f64 * middlegame_weight;
+        dense[PASSED_ENDGAME_OFFSET + rank] += sign * trace.passed[rank] as f64 * endgame_weight;
+        dense[FREE_PATH_OFFSET + rank] += sign * free_paths[rank] as f64 * endgame_weight;
+    }
+}
+
+fn evaluate(position: &TuningPosition, parameters: &[f64]) -> f64 {
+    position.offset as f64 + position.coefficients.iter()
+        .map(|(index, coefficient)| *coefficient as f64 * parameters[*index as usize])
+        .sum::<f64>()
+}
+
+// Expected score of white for an evaluation in centipawns
+fn sigmoid(k: f64, evaluation: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-k * evaluation / 400.0))
+}
+
+fn loss(positions: &[TuningPosition], parameters: &[f64], k: f64) -> f64 {
+    positions.iter()
+        .map(|position| (position.result as f64 - sigmoid(k, evaluate(position, parameters))).powi(2))
+        .sum::<f64>() / positions.len() as f64
+}
+
+// The loss is unimodal in K, so a ternary search finds the constant that best maps current scores to results
+fn fit_scaling_constant(positions: &[TuningPosition], parameters: &[f64]) -> f64 {
+    let (mut low, mut high) = (0.0, 10.0);
+    for _ in 0..60 {
+        let first = low + (high - low) / 3.0;
+        let second = high - (high - low) / 3.0;
+        if loss(positions, parameters, first) < loss(positions, parameters, second) {
+            high = second;
+        } else {
+            low = first;
+        }
+    }
+    (low + high) / 2.0
+}
+
+// Full batch gradient descent with Adam on the mean squared error
+fn optimise(positions: &[TuningPosition], parameters: &mut [f64], k: f64, epochs: usize) {
+    let (beta1, beta2, epsilon) = (0.9, 0.999, 1e-8);
+    let mut momentum = vec![0.0; parameters.len()];
+    let mut velocity = vec![0.0; parameters.len()];
+    let scale = k * std::f64::consts::LN_10 / 400.0;
+
+    for epoch in 1..=epochs {
+        let mut gradient = vec![0.0; parameters.len()];
+        for position in positions {
+            let expected = sigmoid(k, evaluate(position, parameters));
+            let error = -2.0 * (position.result as f64 - expected) * expected * (1.0 - expected) * scale;
+            for (index, coefficient) in &position.coefficients {
+                gradient[*index as usize] += error * *coefficient as f64;
+            }
+        }
+        for index in 0..parameters.len() {
+            let gradient = gradient[index] / positions.len() as f64;
+            momentum[index] = beta1 * momentum[index] + (1.0 - beta1) * gradient;
+            velocity[index] = beta2 * velocity[index] + (1.0 - beta2) * gradient * gradient;
+            let momentum_hat = momentum[index] / (1.0 - beta1.powi(epoch as i32));
+            let velocity_hat = velocity[index] / (1.0 - beta2.powi(epoch as i32));
+            parameters[index] -= LEARNING_RATE * momentum_hat / (velocity_hat.sqrt() + epsilon);
+        }
+        if epoch % 100 == 0 || epoch == epochs {
+            println!("Epoch {}: loss {:.6}", epoch, loss(positions, parameters, k));
+        }
+    }
+}
+
+// Writes the constants under the same names they have in the engine, ready to be pasted over the old ones
+fn write_rust_source(path: &str, parameters: &[f64], position_count: usize, final_loss: f64) -> std::io::Result<()> {
+    let round = |index: usize| parameters[index].round() as i32;
+    let mut file = File::create(path)?;
+    writeln!(file, "// Tuned from {} positions, final loss {:.6}", position_count, final_loss)?;
+    writeln!(file)?;
+
+    let piece_values: Vec<String> = (Pieces::PAWN..Pieces::KING).map(|piece| round(PIECE_VALUE_OFFSET + piece).to_string()).collect();
+    writeln!(file, "// engine/mod.rs")?;
+    writeln!(file, "pub const PIECE_VALUES: [i32; 6] = [{}, {}];", piece_values.join(", "), PIECE_VALUES[Pieces::KING])?;
+    writeln!(file)?;
+
+    writeln!(file, "// engine/pst.rs")?;
+    for (name, offset) in [("MIDDLEGAME_TABLES", MIDDLEGAME_TABLE_OFFSET), ("ENDGAME_TABLES", ENDGAME_TABLE_OFFSET)] {
+        writeln!(file, "#[rustfmt::skip]")?;
+        writeln!(file, "pub const {}: [[i32; 64]; 6] = [", name)?;
+        for (piece, piece_name) in ["Pawn", "Bishop", "Knight", "Rook", "Queen", "King"].iter().enumerate() {
+            writeln!(file, "    // {}", piece_name)?;
+            writeln!(file, "    [")?;
+            for rank in 0..8 {
+                let row: Vec<String> = (0..8).map(|file| format!("{:4}", round(offset + piece * 64 + rank * 8 + file))).collect();
+                writeln!(file, "        {},", row.join(","))?;
+            }
+            writeln!(file, "    ],")?;
+        }
+        writeln!(file, "];")?;
+        writeln!(file)?;
+    }
+
+    let ranks = |offset: usize| (0..8).map(|rank| round(offset + rank).to_string()).collect::<Vec<String>>().join(", ");
+    writeln!(file, "// engine/pawn_structure.rs")?;
+    writeln!(file, "pub const DOUBLED_PENALTY: (i32, i32) = ({}, {});", round(DOUBLED_OFFSET), round(DOUBLED_OFFSET + 1))?;
+    writeln!(file, "pub const ISOLATED_PENALTY: (i32, i32) = ({}, {});", round(ISOLATED_OFFSET), round(ISOLATED_OFFSET + 1))?;
+    writeln!(file, "pub const BACKWARD_PENALTY: (i32, i32) = ({}, {});", round(BACKWARD_OFFSET), round(BACKWARD_OFFSET + 1))?;
+    writeln!(file, "pub const CONNECTED_BONUS: [i32; 8] = [{}];", ranks(CONNECTED_OFFSET))?;
+    writeln!(file, "pub const PASSED_BONUS_MIDDLEGAME: [i32; 8] = [{}];", ranks(PASSED_MIDDLEGAME_OFFSET))?;
+    writeln!(file, "pub const PASSED_BONUS_ENDGAME: [i32; 8] = [{}];", ranks(PASSED_ENDGAME_OFFSET))?;
+    writeln!(file, "pub const FREE_PATH_BONUS: [i32; 8] = [{}];", ranks(FREE_PATH_OFFSET))?;
+    Ok(())
+}
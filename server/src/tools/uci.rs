@@ -0,0 +1,179 @@
+use std::io::{self, BufRead};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::board::Teams;
+use crate::engine::{ChessEngine, SearchLimits, StopHandle, time};
+use crate::engine::evaluator::{AnyEvaluator, EvaluatorKind, EvaluatorRegistry};
+use crate::engine::minimax::MinimaxEngine;
+use crate::engine::nnue::Network;
+use crate::engine::transposition::TranspositionTable;
+use crate::game::clock::ClockReading;
+use crate::game::fen;
+use crate::game::play::Game;
+use crate::tools::players::{PlayerMove, UNBOUNDED_DEPTH};
+
+const STARTING_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+const SEARCH_STACK_SIZE: usize = 32 * 1024 * 1024;
+
+struct UciState {
+    engine: MinimaxEngine<AnyEvaluator>,
+    evaluators: EvaluatorRegistry,
+    game: Game,
+    skill: u8,
+    // UCI_Chess960: castling comes and goes as the king taking its own rook
+    chess960: bool,
+    // The running search, answering with its best move once done
+    search: Option<(StopHandle, JoinHandle<()>)>
+}
+
+// Usage: uci
+// Speaks the UCI protocol on standard input and output, so match runners and GUIs can play the engine. This
+// is how a build is entered as the external engine of a match against another one.
+pub fn run(_args: &[String]) -> Result<(), String> {
+    let mut engine = MinimaxEngine::with_evaluator(AnyEvaluator::default());
+    engine.set_info_callback(|info| println!("{}", info));
+    let mut state = UciState {
+        engine,
+        evaluators: EvaluatorRegistry::default(),
+        game: Game::new(fen::new_board(STARTING_FEN).unwrap()),
+        skill: SearchLimits::MAX_SKILL,
+        chess960: false,
+        search: None
+    };
+    for line in io::stdin().lock().lines() {
+        let line = line.map_err(|error| error.to_string())?;
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        match tokens.first().copied() {
+            Some("uci") => {
+                println!("id name Caissa");
+                println!("id author Caissa developers");
+                println!("option name Hash type spin default {} min 1 max 65536", TranspositionTable::DEFAULT_SIZE_MB);
+                println!("option name Threads type spin default 1 min 1 max 256");
+                println!("option name Evaluator type combo default hand_crafted var material var hand_crafted var nnue");
+                println!("option name EvalFile type string default <empty>");
+                println!("option name Skill Level type spin default {} min 0 max {}", SearchLimits::MAX_SKILL, SearchLimits::MAX_SKILL);
+                println!("option name UCI_Chess960 type check default false");
+                println!("uciok");
+            }
+            Some("isready") => println!("readyok"),
+            Some("setoption") => {
+                finish_search(&mut state);
+                if let Err(error) = set_option(&mut state, &tokens[1..]) {
+                    println!("info string {}", error);
+                }
+            }
+            Some("ucinewgame") => {
+                finish_search(&mut state);
+                state.engine.clear_hash();
+            }
+            Some("position") => {
+                finish_search(&mut state);
+                match parse_position(&tokens[1..], state.chess960) {
+                    Some(game) => state.game = game,
+                    None => println!("info string Invalid position")
+                }
+            }
+            Some("go") => {
+                finish_search(&mut state);
+                go(&mut state, &tokens[1..]);
+            }
+            Some("stop") => finish_search(&mut state),
+            Some("quit") => break,
+            _ => {}
+        }
+    }
+    finish_search(&mut state);
+    Ok(())
+}
+
+// Stops the running search, if any, and waits for its best move to be out
+fn finish_search(state: &mut UciState) {
+    if let Some((stop, search)) = state.search.take() {
+        stop.stop();
+        let _ = search.join();
+    }
+}
+
+fn set_option(state: &mut UciState, tokens: &[&str]) -> Result<(), String> {
+    // setoption name <name, possibly several words> value <value>
+    let value_at = tokens.iter().position(|token| *token == "value").unwrap_or(tokens.len());
+    let name = tokens.get(1..value_at).ok_or("Missing option name")?.join(" ");
+    let value = tokens.get(value_at + 1..).unwrap_or_default().join(" ");
+    let invalid = || format!("Invalid value {} for {}", value, name);
+    match name.to_lowercase().as_str() {
+        "hash" => state.engine.set_hash_size(value.parse().map_err(|_| invalid())?),
+        "threads" => state.engine.set_threads(value.parse().map_err(|_| invalid())?),
+        "skill level" => state.skill = value.parse().ok().filter(|skill| *skill <= SearchLimits::MAX_SKILL).ok_or_else(invalid)?,
+        "uci_chess960" => state.chess960 = value.parse().map_err(|_| invalid())?,
+        "evalfile" => {
+            let network = Network::load(&value).map_err(|error| format!("Could not load network {}: {}", value, error))?;
+            state.evaluators = EvaluatorRegistry::new(state.evaluators.default, Some(network));
+        }
+        "evaluator" => {
+            let kind: EvaluatorKind = value.parse().map_err(|_| invalid())?;
+            state.engine.set_evaluator(state.evaluators.create(kind).ok_or("The nnue evaluator needs an EvalFile")?);
+        }
+        _ => return Err(format!("Unknown option {}", name))
+    }
+    Ok(())
+}
+
+// position startpos|fen <fen> [moves <move>...]
+fn parse_position(tokens: &[&str], chess960: bool) -> Option<Game> {
+    let moves_at = tokens.iter().position(|token| *token == "moves").unwrap_or(tokens.len());
+    let fen = match *tokens.first()? {
+        "startpos" => STARTING_FEN.to_string(),
+        "fen" => tokens[1..moves_at].join(" "),
+        _ => return None
+    };
+    let mut board = fen::new_board(&fen)?;
+    board.state.chess960 |= chess960;
+    let mut game = Game::new(board);
+    for text in tokens.get(moves_at + 1..).unwrap_or_default() {
+        let mv = PlayerMove::parse(text)?;
+        game.play(mv.origin, mv.target, mv.promotion)?;
+    }
+    Some(game)
+}
+
+// go [wtime ms] [btime ms] [winc ms] [binc ms] [movestogo n] [depth n] [nodes n] [movetime ms] [infinite]
+fn go(state: &mut UciState, tokens: &[&str]) {
+    let value = |name: &str| tokens.iter().position(|token| *token == name).and_then(|at| tokens.get(at + 1)).and_then(|value| value.parse::<u64>().ok());
+    let team = state.game.team_to_play();
+    let (time, increment) = if team == Teams::WHITE { ("wtime", "winc") } else { ("btime", "binc") };
+    let mut movetime = value("movetime").map(Duration::from_millis);
+    if value(time).is_some() && !tokens.contains(&"infinite") {
+        let clock = ClockReading {
+            white_ms: value("wtime").unwrap_or(0),
+            black_ms: value("btime").unwrap_or(0),
+            increment_ms: value(increment).unwrap_or(0),
+            moves_to_go: value("movestogo").map(|moves| moves as u32)
+        };
+        let allocated = time::allocate(&clock, team);
+        movetime = Some(movetime.map_or(allocated, |movetime| movetime.min(allocated)));
+    }
+    let limits = SearchLimits {
+        depth: value("depth").map_or(UNBOUNDED_DEPTH, |depth| depth.clamp(1, UNBOUNDED_DEPTH as u64) as u8),
+        movetime,
+        nodes: value("nodes"),
+        multipv: 1,
+        skill: state.skill
+    };
+    let stop = StopHandle::default();
+    let mut engine = state.engine.fork();
+    engine.set_stop_handle(stop.clone());
+    let board = state.game.board().clone();
+    let search = thread::Builder::new()
+        .name("negamax".to_string())
+        .stack_size(SEARCH_STACK_SIZE)
+        .spawn(move || {
+            // GUIs wait for a bestmove even when there is no move to give
+            match engine.search(&board, &limits) {
+                Some(result) => println!("bestmove {}", result.best_move),
+                None => println!("bestmove 0000")
+            }
+        })
+        .expect("Could not spawn a search thread");
+    state.search = Some((stop, search));
+}
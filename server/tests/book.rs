@@ -0,0 +1,174 @@
+use caissa::board::board::ChessBoard;
+use caissa::book::BookSelection;
+use caissa::book::builder::{MoveStats, OpeningTree};
+use caissa::book::polyglot::{polyglot_key, PolyglotBook};
+use caissa::game::fen;
+use caissa::game::pgn::parse_pgn;
+use caissa::game::san::parse_san;
+
+const START: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+fn play(board: &mut ChessBoard, moves: &[&str]) {
+    for san in moves {
+        let mv = parse_san(board, san).unwrap_or_else(|| panic!("{} is not legal", san));
+        board.play_move(mv.origin, mv.target, mv.get_promotion(), true).unwrap();
+    }
+}
+
+// The keys given in the Polyglot format description, which every book in the wild is keyed by
+#[test]
+fn matches_the_published_polyglot_keys() {
+    let lines: [(&[&str], u64); 9] = [
+        (&[], 0x463b96181691fc9c),
+        (&["e4"], 0x823c9b50fd114196),
+        (&["e4", "d5"], 0x0756b94461c50fb0),
+        (&["e4", "d5", "e5"], 0x662fafb965db29d4),
+        // Black's pawn may be taken en passant, so the file of the square counts
+        (&["e4", "d5", "e5", "f5"], 0x22a48b5a8e47ff78),
+        (&["e4", "d5", "e5", "f5", "Ke2"], 0x652a607ca3f242c1),
+        (&["e4", "d5", "e5", "f5", "Ke2", "Kf7"], 0x00fdd303c946bdd9),
+        (&["a4", "b5", "h4", "b4", "c4"], 0x3c8123ea7b067637),
+        (&["a4", "b5", "h4", "b4", "c4", "bxc3", "Ra3"], 0x5c3f9b829b279560)
+    ];
+    for (moves, key) in lines {
+        let mut board = fen::new_board(START).unwrap();
+        play(&mut board, moves);
+        assert_eq!(polyglot_key(&board), key, "after {}", moves.join(" "));
+    }
+}
+
+// Entries as a .bin file lays them out, big endian: key, move, weight and learn. Moves written in octal
+// read as origin rank, origin file, target rank, target file, counted from zero.
+fn bin_file(entries: &[(u64, u16, u16)]) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    for (key, raw_move, weight) in entries {
+        bytes.extend(key.to_be_bytes());
+        bytes.extend(raw_move.to_be_bytes());
+        bytes.extend(weight.to_be_bytes());
+        bytes.extend(0u32.to_be_bytes());
+    }
+    bytes
+}
+
+#[test]
+fn reads_the_moves_of_a_position_from_a_bin_file() {
+    let start = fen::new_board(START).unwrap();
+    let mut italian = fen::new_board(START).unwrap();
+    play(&mut italian, &["e4", "e5", "Nf3", "Nc6", "Bc4", "Bc5"]);
+    let book = PolyglotBook::from_bytes(&bin_file(&[
+        // e2e4, d2d4, a never played g1f3 and an e2e5 the board cannot play
+        (polyglot_key(&start), 0o1434, 30),
+        (polyglot_key(&start), 0o1333, 10),
+        (polyglot_key(&start), 0o0625, 0),
+        (polyglot_key(&start), 0o1444, 50),
+        // Castling is written as the king taking its rook, e1h1
+        (polyglot_key(&italian), 0o0407, 20),
+        (polyglot_key(&italian), 0o1323, 5)
+    ])).unwrap();
+    assert_eq!(book.len(), 6);
+
+    let moves = |board: &ChessBoard| book.moves(board).iter().map(|(mv, weight)| (mv.to_string(), *weight)).collect::<Vec<_>>();
+    assert_eq!(moves(&start), [("e2e4".to_string(), 30), ("d2d4".to_string(), 10)]);
+    assert_eq!(moves(&italian), [("e1g1".to_string(), 20), ("d2d3".to_string(), 5)]);
+    assert_eq!(book.pick(&italian, BookSelection::BestWeight).unwrap().to_string(), "e1g1");
+    assert!(book.moves(&fen::new_board("4k3/8/8/8/8/8/8/4K3 w - - 0 1").unwrap()).is_empty());
+}
+
+const GAMES: &str = r#"[Event "Club"]
+[White "A"]
+[Black "B"]
+[Result "1-0"]
+
+1. e4 e5 2. Nf3 Nc6 3. Bc4 Bc5 4. O-O Nf6 1-0
+
+[Event "Club"]
+[Result "0-1"]
+
+1. e4 c5 2. Nf3 d6 0-1
+
+[Event "Club"]
+[Result "1/2-1/2"]
+
+1. d4 {a quiet game} d5 (1... Nf6 2. c4) 2. c4 1/2-1/2
+
+[Event "Club"]
+[Variant "Atomic"]
+[Result "1-0"]
+
+1. e4 e5 1-0
+"#;
+
+#[test]
+fn builds_a_book_from_games() {
+    let mut tree = OpeningTree::default();
+    for game in parse_pgn(GAMES) {
+        tree.add_game(&game, 6);
+    }
+    // The atomic game is left out
+    assert_eq!(tree.games(), 3);
+
+    let start = fen::new_board(START).unwrap();
+    let stats = &tree.position(&start).unwrap().moves;
+    assert_eq!(stats.len(), 2);
+    assert_eq!(stats[&0o1434], MoveStats { games: 2, white_wins: 1, draws: 0, black_wins: 1 });
+    assert_eq!(stats[&0o1333], MoveStats { games: 1, white_wins: 0, draws: 1, black_wins: 0 });
+
+    let path = std::env::temp_dir().join(format!("caissa-book-{}.bin", std::process::id()));
+    tree.to_polyglot(1).save(path.to_str().unwrap()).unwrap();
+    let book = PolyglotBook::load(path.to_str().unwrap());
+    let _ = std::fs::remove_file(&path);
+    let book = book.unwrap();
+
+    // Two points per win and one per draw for the side to move, a move that only lost keeps no weight
+    let moves = |board: &ChessBoard| {
+        let mut moves: Vec<_> = book.moves(board).iter().map(|(mv, weight)| (mv.to_string(), *weight)).collect();
+        moves.sort();
+        moves
+    };
+    assert_eq!(moves(&start), [("d2d4".to_string(), 1), ("e2e4".to_string(), 2)]);
+    let mut board = fen::new_board(START).unwrap();
+    play(&mut board, &["e4"]);
+    assert_eq!(moves(&board), [("c7c5".to_string(), 2)]);
+    play(&mut board, &["e5", "Nf3", "Nc6", "Bc4", "Bc5"]);
+    // Past the sixth half-move nothing was recorded
+    assert!(moves(&board).is_empty());
+    let mut board = fen::new_board(START).unwrap();
+    play(&mut board, &["d4"]);
+    // The variation is not part of the game
+    assert_eq!(moves(&board), [("d7d5".to_string(), 1)]);
+}
+
+#[test]
+fn records_castling_as_the_king_taking_its_rook() {
+    let mut tree = OpeningTree::default();
+    for game in parse_pgn(GAMES) {
+        tree.add_game(&game, 8);
+    }
+    let mut board = fen::new_board(START).unwrap();
+    play(&mut board, &["e4", "e5", "Nf3", "Nc6", "Bc4", "Bc5"]);
+    let moves = &tree.position(&board).unwrap().moves;
+    assert!(moves.contains_key(&0o0407) && !moves.contains_key(&0o0406));
+    let book = tree.to_polyglot(1);
+    assert_eq!(book.pick(&board, BookSelection::BestWeight).unwrap().to_string(), "e1g1");
+}
+
+#[test]
+fn keeps_the_statistics_in_the_native_format() {
+    let mut tree = OpeningTree::default();
+    for game in parse_pgn(GAMES) {
+        tree.add_game(&game, 6);
+    }
+    let path = std::env::temp_dir().join(format!("caissa-tree-{}.book", std::process::id()));
+    tree.save(path.to_str().unwrap()).unwrap();
+    let loaded = OpeningTree::load(path.to_str().unwrap());
+    let _ = std::fs::remove_file(&path);
+    let loaded = loaded.unwrap();
+
+    assert_eq!(loaded.games(), tree.games());
+    assert_eq!(loaded.positions(), tree.positions());
+    let mut board = fen::new_board(START).unwrap();
+    for san in ["e4", "e5", "Nf3", "Nc6", "Bc4"] {
+        assert_eq!(loaded.position(&board).unwrap().moves, tree.position(&board).unwrap().moves, "before {}", san);
+        play(&mut board, &[san]);
+    }
+}
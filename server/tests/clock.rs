@@ -0,0 +1,92 @@
+use std::time::{Duration, Instant};
+
+use caissa::board::Teams;
+use caissa::game::clock::{Clock, Delay, TimeControl};
+
+fn clock(control: &str, delay: Option<Delay>) -> Clock {
+    Clock::new(TimeControl { delay, ..control.parse().unwrap() })
+}
+
+fn seconds(seconds: u64) -> Duration {
+    Duration::from_secs(seconds)
+}
+
+#[test]
+fn adds_the_increment_after_the_move() {
+    let start = Instant::now();
+    let mut clock = clock("60+2", None);
+    clock.start(Teams::WHITE, start);
+    assert!(clock.press(start + seconds(10)));
+    assert_eq!(clock.remaining(Teams::WHITE, start + seconds(20)), seconds(52));
+    // Black's time runs from White's move
+    assert_eq!(clock.remaining(Teams::BLACK, start + seconds(20)), seconds(50));
+    assert_eq!(clock.reading(Teams::BLACK, start + seconds(20)).increment_ms, 2000);
+}
+
+#[test]
+fn a_simple_delay_is_not_charged() {
+    let start = Instant::now();
+    let mut clock = clock("60", Some(Delay::Simple { ms: 5000 }));
+    clock.start(Teams::WHITE, start);
+    assert_eq!(clock.remaining(Teams::WHITE, start + seconds(3)), seconds(60));
+    assert!(clock.press(start + seconds(3)));
+    assert_eq!(clock.remaining(Teams::WHITE, start + seconds(3)), seconds(60));
+    assert!(clock.press(start + seconds(11)));
+    assert_eq!(clock.remaining(Teams::BLACK, start + seconds(11)), seconds(57));
+}
+
+#[test]
+fn a_bronstein_delay_gives_back_the_time_used_up_to_the_delay() {
+    let start = Instant::now();
+    let mut clock = clock("60", Some(Delay::Bronstein { ms: 5000 }));
+    clock.start(Teams::WHITE, start);
+    // Charged while thinking, given back with the move
+    assert_eq!(clock.remaining(Teams::WHITE, start + seconds(3)), seconds(57));
+    assert!(clock.press(start + seconds(3)));
+    assert_eq!(clock.remaining(Teams::WHITE, start + seconds(3)), seconds(60));
+    assert!(clock.press(start + seconds(11)));
+    assert_eq!(clock.remaining(Teams::BLACK, start + seconds(11)), seconds(57));
+}
+
+#[test]
+fn rolls_over_to_the_next_period() {
+    let start = Instant::now();
+    let mut clock = clock("2/60:30", None);
+    clock.start(Teams::WHITE, start);
+    assert_eq!(clock.reading(Teams::WHITE, start).moves_to_go, Some(2));
+    assert!(clock.press(start + seconds(10)));
+    assert!(clock.press(start + seconds(20)));
+    // White's second move reaches the time control and brings the last period's 30 seconds
+    assert!(clock.press(start + seconds(30)));
+    assert_eq!(clock.remaining(Teams::WHITE, start + seconds(30)), seconds(70));
+    assert_eq!(clock.reading(Teams::WHITE, start + seconds(30)).moves_to_go, None);
+    assert_eq!(clock.reading(Teams::BLACK, start + seconds(30)).moves_to_go, Some(1));
+}
+
+#[test]
+fn a_last_period_with_a_move_count_repeats() {
+    let start = Instant::now();
+    let mut clock = clock("1/60", None);
+    clock.start(Teams::WHITE, start);
+    assert!(clock.press(start + seconds(10)));
+    assert_eq!(clock.remaining(Teams::WHITE, start + seconds(10)), seconds(110));
+    assert_eq!(clock.reading(Teams::WHITE, start + seconds(10)).moves_to_go, Some(1));
+}
+
+#[test]
+fn flags_once_the_time_is_up_under_each_delay() {
+    // Flag time from the start of the move, and the delays
+    for (delay, flag) in [(None, 60), (Some(Delay::Simple { ms: 5000 }), 65), (Some(Delay::Bronstein { ms: 5000 }), 60)] {
+        let start = Instant::now();
+        let mut clock = clock("60", delay);
+        clock.start(Teams::WHITE, start);
+        assert_eq!(clock.time_to_flag(Teams::WHITE, start), seconds(flag), "{:?}", delay);
+        assert_eq!(clock.time_to_flag(Teams::WHITE, start + seconds(2)), seconds(flag - 2), "{:?}", delay);
+        assert!(!clock.is_flagged(Teams::WHITE, start + seconds(flag) - Duration::from_millis(1)), "{:?}", delay);
+        assert!(clock.is_flagged(Teams::WHITE, start + seconds(flag)), "{:?}", delay);
+        assert!(!clock.is_flagged(Teams::BLACK, start + seconds(flag)), "{:?}", delay);
+        // A move made too late does not count
+        assert!(!clock.press(start + seconds(flag)), "{:?}", delay);
+        assert!(clock.is_flagged(Teams::WHITE, start + seconds(flag)), "{:?}", delay);
+    }
+}
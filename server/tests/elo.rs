@@ -0,0 +1,78 @@
+use caissa::game::elo::{elo_to_score, score_to_elo, MatchScore, Sprt, SprtStatus};
+
+fn close(actual: f64, expected: f64, tolerance: f64) -> bool {
+    (actual - expected).abs() < tolerance
+}
+
+fn results(wins: u32, draws: u32, losses: u32) -> MatchScore {
+    MatchScore { wins, draws, losses }
+}
+
+#[test]
+fn converts_between_scores_and_elo() {
+    assert!(close(score_to_elo(0.5), 0.0, 1e-9));
+    assert!(close(score_to_elo(0.75), 190.8485, 1e-4));
+    assert!(close(score_to_elo(0.25), -190.8485, 1e-4));
+    assert!(close(elo_to_score(100.0), 0.640065, 1e-6));
+    for elo in [-400.0, -35.5, 0.0, 12.0, 250.0] {
+        assert!(close(score_to_elo(elo_to_score(elo)), elo, 1e-9));
+    }
+}
+
+#[test]
+fn measures_the_elo_difference_and_its_margin() {
+    let score = results(60, 20, 20);
+    assert!(close(score.score(), 0.7, 1e-12));
+    assert!(close(score.draw_ratio(), 0.2, 1e-12));
+    let (elo, margin) = score.elo().unwrap();
+    assert!(close(elo, 147.1907, 1e-4), "{}", elo);
+    assert!(close(margin, 66.0134, 1e-4), "{}", margin);
+
+    // Equal results, the margin shrinking with the square root of the games played
+    let (elo, small) = results(100, 200, 100).elo().unwrap();
+    let (_, large) = results(25, 50, 25).elo().unwrap();
+    assert!(close(elo, 0.0, 1e-9));
+    assert!(close(large / small, 2.0, 0.01));
+
+    // Without a point lost or a point scored the difference is infinite
+    assert_eq!(results(0, 0, 0).elo(), None);
+    assert_eq!(results(5, 0, 0).elo(), None);
+    assert_eq!(results(0, 0, 5).elo(), None);
+}
+
+#[test]
+fn likelihood_of_superiority_leaves_draws_out() {
+    assert_eq!(results(0, 10, 0).los(), 0.5);
+    assert!(close(results(10, 0, 10).los(), 0.5, 1e-9));
+    assert!(close(results(10, 50, 10).los(), 0.5, 1e-9));
+    // Two standard deviations ahead
+    assert!(close(results(60, 7, 40).los(), 0.97725, 1e-4));
+    assert!(close(results(40, 7, 60).los(), 0.02275, 1e-4));
+}
+
+#[test]
+fn sprt_log_likelihood_ratio() {
+    let test = Sprt::default();
+    assert!(close(test.lower_bound(), -2.944439, 1e-6));
+    assert!(close(test.upper_bound(), 2.944439, 1e-6));
+    // Summed over the games as normal log densities around the two hypotheses' scores
+    for (wins, draws, losses, llr) in [
+        (1200, 2000, 1000, 4.684516),
+        (1100, 2000, 1000, 1.983011),
+        (1000, 2000, 1000, -0.828307),
+        (1300, 2600, 1150, 3.377596)
+    ] {
+        let actual = test.llr(&results(wins, draws, losses));
+        assert!(close(actual, llr, 1e-5), "{} - {} - {}: {}", wins, losses, draws, actual);
+    }
+    assert_eq!(test.llr(&results(0, 0, 0)), 0.0);
+    assert_eq!(test.llr(&results(0, 30, 0)), 0.0);
+}
+
+#[test]
+fn sprt_stops_once_a_hypothesis_is_accepted() {
+    let test = Sprt::default();
+    assert_eq!(test.status(&results(1200, 2000, 1000)), SprtStatus::AcceptH1);
+    assert_eq!(test.status(&results(1100, 2000, 1000)), SprtStatus::Continue);
+    assert_eq!(test.status(&results(900, 2000, 1000)), SprtStatus::AcceptH0);
+}
@@ -0,0 +1,61 @@
+use caissa::game::epd::{parse_epd, EpdRecord};
+
+const WAC_001: &str = r#"2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qg6; id "WAC.001";"#;
+
+fn moves(record: &EpdRecord, opcode: &str) -> Option<Vec<String>> {
+    record.moves(opcode).map(|moves| moves.iter().map(ToString::to_string).collect())
+}
+
+#[test]
+fn reads_best_moves_and_ids() {
+    let record = EpdRecord::parse(WAC_001).unwrap();
+    assert_eq!(record.fen, "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - 0 1");
+    assert_eq!(record.id(), Some("WAC.001"));
+    assert_eq!(moves(&record, "bm"), Some(vec!["g3g6".to_string()]));
+    assert_eq!(moves(&record, "am"), None);
+    assert_eq!(record.direct_mate(), None);
+}
+
+#[test]
+fn reads_avoid_moves_in_either_notation() {
+    let record = EpdRecord::parse("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - am e2e4 Nf3; id \"start\"").unwrap();
+    assert_eq!(moves(&record, "am"), Some(vec!["e2e4".to_string(), "g1f3".to_string()]));
+    // The last operation may lack its semicolon
+    assert_eq!(record.id(), Some("start"));
+
+    // A single move the position does not allow makes the whole operation unusable
+    let record = EpdRecord::parse("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - bm e4 Ke2;").unwrap();
+    assert_eq!(moves(&record, "bm"), None);
+}
+
+#[test]
+fn keeps_quoted_operands_whole() {
+    let record = EpdRecord::parse(r#"4k3/8/8/8/8/8/8/4K2R w K - c0 "castles; then mates"; id "a b";"#).unwrap();
+    assert_eq!(record.operation("c0"), Some(&["castles; then mates".to_string()][..]));
+    assert_eq!(record.id(), Some("a b"));
+}
+
+#[test]
+fn takes_the_move_counters_from_operations_or_fen_fields() {
+    let record = EpdRecord::parse("7k/8/6K1/8/8/8/8/R7 w - - hmvc 12; fmvn 40; dm 1;").unwrap();
+    assert_eq!(record.fen, "7k/8/6K1/8/8/8/8/R7 w - - 12 40");
+    assert_eq!(record.direct_mate(), Some(1));
+
+    let record = EpdRecord::parse("7k/8/6K1/8/8/8/8/R7 w - - 3 57").unwrap();
+    assert_eq!(record.fen, "7k/8/6K1/8/8/8/8/R7 w - - 3 57");
+    assert!(record.operations.is_empty());
+    assert!(record.board().is_some());
+}
+
+#[test]
+fn skips_lines_that_are_not_records() {
+    assert!(EpdRecord::parse("").is_none());
+    assert!(EpdRecord::parse("   ").is_none());
+    assert!(EpdRecord::parse("# WAC.001 to WAC.300").is_none());
+    // Without the castling and en passant fields
+    assert!(EpdRecord::parse("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w").is_none());
+
+    let text = format!("# Win at chess\n\n{}\nrnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq\n{}\n", WAC_001, WAC_001.replace("001", "002"));
+    let records = parse_epd(&text);
+    assert_eq!(records.iter().map(|record| record.id().unwrap()).collect::<Vec<_>>(), ["WAC.001", "WAC.002"]);
+}
@@ -0,0 +1,156 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use caissa::board::{get_opposite_team, Pieces, Team, Teams};
+use caissa::board::board::ChessBoard;
+use caissa::board::variant::Variant;
+use caissa::engine::nnue::{feature_index, Network, NnueAccumulator, INPUTS};
+use caissa::game::fen;
+use caissa::game::play::legal_moves;
+use caissa::math::iterate_bits;
+
+// Not a multiple of the 16 lanes the AVX2 path works in, so its scalar tail is covered as well
+const HIDDEN: usize = 40;
+// Quantisation and scale the network file format is defined with
+const QA: i32 = 255;
+const QB: i32 = 64;
+const SCALE: i32 = 400;
+
+const START: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+const KIWIPETE: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+const PROMOTIONS: &str = "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1";
+const EN_PASSANT: &str = "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3";
+
+struct Weights {
+    feature_weights: Vec<i16>,
+    feature_biases: Vec<i16>,
+    output_weights: Vec<i16>,
+    output_bias: i32
+}
+
+impl Weights {
+    // Large enough that accumulator values land both below zero and above the clipping ceiling
+    fn random() -> Self {
+        let mut rng = ChaCha8Rng::seed_from_u64(31);
+        Weights {
+            feature_weights: (0..INPUTS * HIDDEN).map(|_| rng.gen_range(-200..=200)).collect(),
+            feature_biases: (0..HIDDEN).map(|_| rng.gen_range(-300..=300)).collect(),
+            output_weights: (0..2 * HIDDEN).map(|_| rng.gen_range(-127..=127)).collect(),
+            output_bias: rng.gen_range(-10000..=10000)
+        }
+    }
+
+    fn network(&self) -> Arc<Network> {
+        let mut bytes = b"CNNU".to_vec();
+        bytes.extend((HIDDEN as u32).to_le_bytes());
+        for value in self.feature_weights.iter().chain(&self.feature_biases).chain(&self.output_weights) {
+            bytes.extend(value.to_le_bytes());
+        }
+        bytes.extend(self.output_bias.to_le_bytes());
+        Arc::new(Network::from_bytes(&bytes).unwrap())
+    }
+
+    // The network's output worked out from scratch, one hidden unit at a time
+    fn evaluate(&self, board: &ChessBoard, reference: Team) -> i32 {
+        let hidden = |perspective: Team| {
+            let mut values: Vec<i32> = self.feature_biases.iter().map(|bias| *bias as i32).collect();
+            for team in [Teams::WHITE, Teams::BLACK] {
+                for piece in Pieces::iter() {
+                    iterate_bits(board.bits.get_pieces(team, piece).0, |bit| {
+                        let index = feature_index(perspective, team, piece, bit.trailing_zeros() as usize);
+                        for (value, weight) in values.iter_mut().zip(&self.feature_weights[index * HIDDEN..]) {
+                            *value += *weight as i32;
+                        }
+                    });
+                }
+            }
+            values
+        };
+        let sum: i32 = hidden(reference).into_iter()
+            .chain(hidden(get_opposite_team(reference)))
+            .zip(&self.output_weights)
+            .map(|(value, weight)| value.clamp(0, QA) * *weight as i32)
+            .sum();
+        (sum + self.output_bias) * SCALE / (QA * QB)
+    }
+}
+
+fn fingerprint(accumulator: &NnueAccumulator) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    accumulator.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn assert_refreshed(board: &ChessBoard, network: &Arc<Network>, line: &[String]) {
+    let incremental = board.nnue.as_ref().unwrap();
+    let refreshed = NnueAccumulator::new(network.clone(), &board.bits);
+    assert_eq!(fingerprint(incremental), fingerprint(&refreshed), "after {}", line.join(" "));
+    for team in [Teams::WHITE, Teams::BLACK] {
+        assert_eq!(incremental.evaluate(team), refreshed.evaluate(team), "after {}", line.join(" "));
+    }
+}
+
+#[derive(Default)]
+struct Seen {
+    captures: u32,
+    castles: u32,
+    en_passants: u32,
+    promotions: u32
+}
+
+// Plays every line to the given depth, checking the accumulator against a full refresh after each move and undo
+fn walk(board: &mut ChessBoard, network: &Arc<Network>, depth: u8, line: &mut Vec<String>, seen: &mut Seen) {
+    if depth == 0 {
+        return;
+    }
+    for mv in legal_moves(board) {
+        let state = board.state;
+        let Some(played) = board.play_move(mv.origin, mv.target, mv.get_promotion(), true) else { continue };
+        seen.captures += played.is_capture() as u32;
+        seen.castles += played.is_castling() as u32;
+        seen.en_passants += played.is_en_passant() as u32;
+        seen.promotions += played.is_promotion() as u32;
+        line.push(format!("{}-{}", played.origin, played.target));
+        assert_refreshed(board, network, line);
+        walk(board, network, depth - 1, line, seen);
+        board.undo_move(&played);
+        board.state = state;
+        assert_refreshed(board, network, line);
+        line.pop();
+    }
+}
+
+#[test]
+fn incremental_updates_match_a_full_refresh() {
+    let network = Weights::random().network();
+    let mut seen = Seen::default();
+    for (fen, variant, depth) in [
+        (KIWIPETE, Variant::Standard, 3),
+        (PROMOTIONS, Variant::Standard, 3),
+        (EN_PASSANT, Variant::Standard, 2),
+        // Captures blow up every piece around the target square
+        (KIWIPETE, Variant::Atomic, 2)
+    ] {
+        let mut board = fen::new_variant_board(fen, variant).unwrap();
+        board.nnue = Some(NnueAccumulator::new(network.clone(), &board.bits));
+        walk(&mut board, &network, depth, &mut Vec::new(), &mut seen);
+    }
+    assert!(seen.captures > 0 && seen.castles > 0 && seen.en_passants > 0 && seen.promotions > 0);
+}
+
+#[test]
+fn evaluation_matches_the_scalar_reference() {
+    let weights = Weights::random();
+    let network = weights.network();
+    for fen in [START, KIWIPETE, PROMOTIONS, EN_PASSANT, "8/8/4k3/8/8/3K4/8/8 w - - 0 1"] {
+        let board = fen::new_board(fen).unwrap();
+        let accumulator = NnueAccumulator::new(network.clone(), &board.bits);
+        for team in [Teams::WHITE, Teams::BLACK] {
+            assert_eq!(accumulator.evaluate(team), weights.evaluate(&board, team), "{} for team {}", fen, team);
+        }
+    }
+}
@@ -0,0 +1,93 @@
+use caissa::board::variant::Variant;
+use caissa::game::{chess960, fen};
+use caissa::game::play::perft;
+
+// Leaf counts by depth
+type Counts = &'static [(u8, u64)];
+
+const START: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+const KIWIPETE: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+
+fn check(variant: Variant, positions: &[(&str, Counts)]) {
+    for (fen, counts) in positions {
+        let mut board = fen::new_variant_board(fen, variant).unwrap_or_else(|| panic!("Invalid FEN {}", fen));
+        for (depth, expected) in *counts {
+            assert_eq!(perft(&mut board, *depth), *expected, "{} at depth {} of {}", variant.name(), depth, fen);
+        }
+    }
+}
+
+#[test]
+fn standard() {
+    check(Variant::Standard, &[
+        (START, &[(1, 20), (2, 400), (3, 8902), (4, 197281)]),
+        (KIWIPETE, &[(1, 48), (2, 2039), (3, 97862), (4, 4085603)]),
+        ("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", &[(1, 14), (2, 191), (3, 2812), (4, 43238), (5, 674624)]),
+        // Promotions, captures among them, for both sides
+        ("r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1", &[(1, 6), (2, 264), (3, 9467), (4, 422333)]),
+        ("rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8", &[(1, 44), (2, 1486), (3, 62379)])
+    ]);
+}
+
+#[test]
+fn chess960() {
+    check(Variant::Standard, &[
+        ("bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9", &[(1, 21), (2, 528), (3, 12189)]),
+        ("2nnrbkr/p1qppppp/8/1ppb4/6PP/3PP3/PPP2P2/BQNNRBKR w HEhe - 1 9", &[(1, 21), (2, 807), (3, 18002)]),
+        ("b1q1rrkb/pppppppp/3nn3/8/P7/1PPP4/4PPPP/BQNNRKRB w GE - 1 9", &[(1, 20), (2, 479), (3, 10471), (4, 273318)]),
+        ("qbbnnrkr/2pp2pp/p7/1p2pp2/8/P3PP2/1PPP1KPP/QBBNNR1R w hf - 0 9", &[(1, 22), (2, 593), (3, 13440), (4, 382958)]),
+        // Both sides can castle at once, the king staying on g1 and only the rook moving
+        ("qnbnr1kr/ppp1b1pp/4p3/3p1p2/8/2NPP3/PPP1BPPP/QNB1R1KR w HEhe - 1 9", &[(1, 29), (2, 899), (3, 26578), (4, 824055)])
+    ]);
+}
+
+#[test]
+fn chess960_start_positions() {
+    for (index, counts) in [
+        (0, &[(1, 20), (2, 400), (3, 9006), (4, 201143)]),
+        (518, &[(1, 20), (2, 400), (3, 8902), (4, 197281)]),
+        // The knight on f1 clears the way for castling on the third ply
+        (100, &[(1, 20), (2, 400), (3, 9026), (4, 201178), (5, 4958510)]),
+        (959, &[(1, 20), (2, 400), (3, 9006), (4, 201143)])
+    ] as [(u16, Counts); 4] {
+        let fen = chess960::start_fen(index).unwrap();
+        check(Variant::Standard, &[(&fen, counts)]);
+    }
+}
+
+#[test]
+fn king_of_the_hill() {
+    check(Variant::KingOfTheHill, &[
+        (START, &[(1, 20), (2, 400), (3, 8902)]),
+        ("4k3/8/8/8/8/3K4/8/8 w - - 0 1", &[(1, 8), (2, 30), (3, 240), (4, 1458)])
+    ]);
+}
+
+#[test]
+fn three_check() {
+    check(Variant::ThreeCheck, &[
+        ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 3+3 0 1", &[(1, 20), (2, 400), (3, 8902)]),
+        ("4k3/8/8/8/8/8/8/R3K3 w - - 1+3 0 1", &[(1, 15), (2, 65), (3, 1185), (4, 6519)]),
+        (KIWIPETE, &[(1, 48), (2, 2039), (3, 97862)])
+    ]);
+}
+
+#[test]
+fn atomic() {
+    check(Variant::Atomic, &[
+        (START, &[(1, 20), (2, 400), (3, 8902), (4, 197326)]),
+        (KIWIPETE, &[(1, 48), (2, 1939), (3, 88298)]),
+        // Kings side by side cannot take each other or be checked
+        ("8/8/8/8/8/8/3kK3/8 w - - 0 1", &[(1, 7), (2, 52)]),
+        ("8/2k5/8/3pP3/8/8/8/4K3 w - d6 0 1", &[(1, 7), (2, 48)])
+    ]);
+}
+
+#[test]
+fn horde() {
+    check(Variant::Horde, &[
+        (Variant::Horde.start_fen(), &[(1, 8), (2, 128), (3, 1274), (4, 23310)]),
+        // Pawns on the first rank may step two squares
+        ("4k3/8/8/8/8/8/8/PPPPPPPP w - - 0 1", &[(1, 16), (2, 80), (3, 1240)])
+    ]);
+}
@@ -0,0 +1,75 @@
+use caissa::board::{Pieces, Teams};
+use caissa::board::variant::Variant;
+use caissa::game::fen;
+use caissa::game::pgn::GameResult;
+use caissa::game::play::{EndReason, Game, Outcome};
+
+fn game(fen: &str, variant: Variant) -> Game {
+    Game::new(fen::new_variant_board(fen, variant).unwrap())
+}
+
+fn play(game: &mut Game, moves: &[(u8, u8)]) {
+    for (origin, target) in moves {
+        assert!(game.play(*origin, *target, None).is_some(), "{}-{} is not legal in {}", origin, target, game.fen());
+    }
+}
+
+#[test]
+fn bare_kings_draw_only_where_kings_cannot_win() {
+    let kings = "4k3/8/8/8/8/3K4/8/8 w - - 0 1";
+    assert_eq!(game(kings, Variant::Standard).outcome(), Some(Outcome::draw(EndReason::InsufficientMaterial)));
+    assert_eq!(game(kings, Variant::ThreeCheck).outcome(), Some(Outcome::draw(EndReason::InsufficientMaterial)));
+    assert_eq!(game(kings, Variant::KingOfTheHill).outcome(), None);
+}
+
+#[test]
+fn a_lone_minor_piece_can_still_win_three_check() {
+    let knight = "4k3/8/8/8/8/8/8/4KN2 w - - 0 1";
+    assert_eq!(game(knight, Variant::Standard).outcome(), Some(Outcome::draw(EndReason::InsufficientMaterial)));
+    let mut three_check = game(knight, Variant::ThreeCheck);
+    assert_eq!(three_check.outcome(), None);
+    three_check.flag(Teams::BLACK);
+    assert_eq!(three_check.outcome().map(|outcome| outcome.result), Some(GameResult::WhiteWins));
+}
+
+#[test]
+fn a_flagging_king_of_the_hill_side_loses_to_a_lone_king() {
+    let mut hill = game("4k3/8/8/8/8/3K4/8/7R w - - 0 1", Variant::KingOfTheHill);
+    hill.flag(Teams::WHITE);
+    assert_eq!(hill.outcome(), Some(Outcome::win(Teams::BLACK, EndReason::Timeout)));
+}
+
+#[test]
+fn three_check_repetitions_count_the_checks_given() {
+    // The start comes back three times, the first time before the rook's check
+    let moves = [(7, 4), (60, 59), (4, 7), (59, 60), (0, 8), (60, 61), (8, 0), (61, 60)];
+    let mut standard = game("4k3/8/8/8/8/8/8/K6R w - - 0 1", Variant::Standard);
+    play(&mut standard, &moves);
+    assert_eq!(standard.outcome(), Some(Outcome::draw(EndReason::Repetition)));
+    let mut three_check = game("4k3/8/8/8/8/8/8/K6R w - - 0 1", Variant::ThreeCheck);
+    play(&mut three_check, &moves);
+    assert_eq!(three_check.outcome(), None);
+}
+
+#[test]
+fn pawns_promote_to_the_piece_asked_for() {
+    let mut game = game("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1", Variant::Standard);
+    assert_eq!(game.legal_moves().iter().filter(|mv| mv.is_promotion()).count(), 4);
+    // The last rank is only reached by naming a piece
+    assert!(game.play(49, 57, None).is_none());
+    let mv = game.play(49, 57, Some(Pieces::KNIGHT)).unwrap();
+    assert_eq!(mv.to_string(), "b7b8n");
+    assert_eq!(game.fen(), "1N2k3/8/8/8/8/8/8/4K3 b - - 0 1");
+}
+
+#[test]
+fn kings_do_not_castle_across_squares_pawns_attack() {
+    let castles = |fen: &str| {
+        let game = game(fen, Variant::Standard);
+        let moves = game.legal_moves();
+        [6, 2].map(|target| moves.iter().any(|mv| mv.origin == 4 && mv.target == target && mv.is_castling()))
+    };
+    // The pawn on g2 covers the empty f1, the one on b2 the empty c1
+    assert_eq!(castles("4k3/8/8/8/8/8/6p1/R3K2R w KQ - 0 1"), [false, true]);
+    assert_eq!(castles("4k3/8/8/8/8/8/1p6/R3K2R w KQ - 0 1"), [true, false]);
+}
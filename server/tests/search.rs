@@ -0,0 +1,35 @@
+use caissa::engine::{ChessEngine, SearchLimits};
+use caissa::engine::minimax::MinimaxEngine;
+use caissa::game::fen;
+
+const FOOLS_MATE: &str = "rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3";
+const STALEMATE: &str = "7k/5Q2/6K1/8/8/8/8/8 b - - 0 1";
+// Walking the king first and promoting at the horizon must not look better than promoting now
+const PROMOTION: &str = "8/4P3/8/8/8/8/k5K1/8 w - - 0 1";
+
+#[test]
+fn finds_no_move_without_legal_moves() {
+    let mut engine: MinimaxEngine = MinimaxEngine::new();
+    for position in [FOOLS_MATE, STALEMATE] {
+        assert!(engine.search(&fen::new_board(position).unwrap(), &SearchLimits::depth(3)).is_none(), "{}", position);
+    }
+}
+
+#[test]
+fn leaves_the_score_out_when_stopped_inside_the_first_iteration() {
+    let mut engine: MinimaxEngine = MinimaxEngine::new();
+    let board = fen::new_board("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").unwrap();
+    let result = engine.search(&board, &SearchLimits { nodes: Some(2), ..SearchLimits::depth(5) }).unwrap();
+    assert_eq!((result.score, result.depth), (None, 0));
+    assert!(result.lines.is_empty());
+    assert!(board.generate_moves(board.state.team_to_play).iter().any(|mv| mv.to_string() == result.best_move.to_string()));
+}
+
+#[test]
+fn promotes_a_winning_pawn_at_once() {
+    let mut engine: MinimaxEngine = MinimaxEngine::new();
+    for depth in 1..=6 {
+        let result = engine.search(&fen::new_board(PROMOTION).unwrap(), &SearchLimits::depth(depth)).unwrap();
+        assert_eq!(result.best_move.to_string(), "e7e8q", "at depth {}", depth);
+    }
+}
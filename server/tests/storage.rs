@@ -0,0 +1,134 @@
+use std::path::PathBuf;
+
+use caissa::engine::evaluator::EvaluatorKind;
+use caissa::engine::info::{Analysis, Score, SearchLine};
+use caissa::game::fen;
+use caissa::game::pgn::GameResult;
+use caissa::storage::{GameQuery, GameRecord, GameSummary, Storage};
+
+const START: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+// A database file of its own for each test, removed when the test is done
+struct TempDatabase(PathBuf);
+
+impl TempDatabase {
+    fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("caissa-{}-{}.sqlite", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        TempDatabase(path)
+    }
+
+    fn open(&self) -> Storage {
+        Storage::open(self.0.to_str().unwrap()).unwrap()
+    }
+}
+
+impl Drop for TempDatabase {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+fn record(white: &str, black: &str, result: GameResult, ended_at: u64) -> GameRecord {
+    GameRecord {
+        summary: GameSummary {
+            id: 0,
+            white: white.to_string(),
+            black: black.to_string(),
+            result,
+            reason: "checkmate".to_string(),
+            time_control: Some("300+3".to_string()),
+            white_ms: Some(1000),
+            black_ms: None,
+            started_at: ended_at - 60,
+            ended_at,
+            start_fen: START.to_string(),
+            moves: 4
+        },
+        pgn: format!("[White \"{}\"]\n[Black \"{}\"]\n\n1. f3 e5 2. g4 Qh4# {}\n", white, black, result.as_str())
+    }
+}
+
+fn analysis(depth: u8, lines: usize) -> Analysis {
+    Analysis {
+        depth,
+        nodes: 1000,
+        time_ms: 10,
+        lines: (0..lines).map(|line| SearchLine { score: Score::Cp(50 - line as i32), pv: Vec::new() }).collect()
+    }
+}
+
+fn ids(games: &[GameSummary]) -> Vec<i64> {
+    games.iter().map(|game| game.id).collect()
+}
+
+#[test]
+fn saves_and_reads_back_games() {
+    let database = TempDatabase::new("games");
+    let storage = database.open();
+    let saved = record("Alice", "Bob", GameResult::BlackWins, 1_700_000_000);
+    let id = storage.save_game(&saved).unwrap();
+
+    let game = storage.game(id).unwrap().unwrap();
+    assert_eq!(game.summary.id, id);
+    assert_eq!((game.summary.white.as_str(), game.summary.black.as_str()), ("Alice", "Bob"));
+    assert_eq!(game.summary.result, GameResult::BlackWins);
+    assert_eq!((game.summary.white_ms, game.summary.black_ms), (Some(1000), None));
+    assert_eq!(game.summary.time_control.as_deref(), Some("300+3"));
+    assert_eq!(game.pgn, saved.pgn);
+    assert!(storage.game(id + 1).unwrap().is_none());
+
+    // Still there once the database is opened again
+    drop(storage);
+    assert!(database.open().game(id).unwrap().is_some());
+}
+
+#[test]
+fn filters_games() {
+    let database = TempDatabase::new("search");
+    let storage = database.open();
+    let first = storage.save_game(&record("Alice", "Bob", GameResult::WhiteWins, 100)).unwrap();
+    let second = storage.save_game(&record("Carol", "alice", GameResult::Draw, 200)).unwrap();
+    let third = storage.save_game(&record("Bob", "Carol", GameResult::WhiteWins, 300)).unwrap();
+    let search = |query: GameQuery| ids(&storage.search_games(&query).unwrap());
+
+    assert_eq!(search(GameQuery::default()), vec![third, second, first]);
+    assert_eq!(search(GameQuery { player: Some("ALICE".to_string()), ..GameQuery::default() }), vec![second, first]);
+    assert_eq!(search(GameQuery { result: Some("1-0".to_string()), ..GameQuery::default() }), vec![third, first]);
+    assert_eq!(search(GameQuery { since: Some(150), until: Some(300), ..GameQuery::default() }), vec![third, second]);
+    assert_eq!(search(GameQuery { until: Some(199), ..GameQuery::default() }), vec![first]);
+    assert_eq!(search(GameQuery { limit: Some(1), offset: Some(1), ..GameQuery::default() }), vec![second]);
+    assert_eq!(search(GameQuery { player: Some("Bob".to_string()), result: Some("1/2-1/2".to_string()), ..GameQuery::default() }), Vec::<i64>::new());
+}
+
+#[test]
+fn keeps_the_deeper_analysis() {
+    let database = TempDatabase::new("deeper");
+    let storage = database.open();
+    let board = fen::new_board(START).unwrap();
+    let cached_depth = |depth: u8| storage.cached_analysis(&board, EvaluatorKind::HandCrafted, depth, 3).unwrap().map(|analysis| analysis.depth);
+
+    storage.store_analysis(&board, EvaluatorKind::HandCrafted, 3, &analysis(8, 3)).unwrap();
+    storage.store_analysis(&board, EvaluatorKind::HandCrafted, 3, &analysis(5, 3)).unwrap();
+    assert_eq!(cached_depth(1), Some(8));
+    assert_eq!(cached_depth(9), None);
+
+    storage.store_analysis(&board, EvaluatorKind::HandCrafted, 3, &analysis(10, 3)).unwrap();
+    assert_eq!(cached_depth(9), Some(10));
+    // Another evaluator's analyses are kept apart
+    assert!(storage.cached_analysis(&board, EvaluatorKind::Material, 1, 3).unwrap().is_none());
+}
+
+#[test]
+fn cuts_cached_lines_down_to_the_count_asked_for() {
+    let database = TempDatabase::new("lines");
+    let storage = database.open();
+    let board = fen::new_board(START).unwrap();
+    storage.store_analysis(&board, EvaluatorKind::HandCrafted, 5, &analysis(6, 5)).unwrap();
+
+    let cached = storage.cached_analysis(&board, EvaluatorKind::HandCrafted, 6, 2).unwrap().unwrap();
+    assert_eq!(cached.lines.len(), 2);
+    assert_eq!(cached.lines[1].score, Score::Cp(49));
+    // Fewer lines than asked for cannot answer the request
+    assert!(storage.cached_analysis(&board, EvaluatorKind::HandCrafted, 6, 6).unwrap().is_none());
+}
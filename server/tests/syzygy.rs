@@ -0,0 +1,94 @@
+#![cfg(feature = "syzygy")]
+
+use caissa::board::Pieces;
+use caissa::engine::syzygy::{Tablebase, Wdl};
+use caissa::game::fen;
+
+// Known 3- and 4-piece positions with their outcome for the side to move
+const POSITIONS: [(&str, Wdl); 14] = [
+    ("4k3/8/8/8/8/8/8/3QK3 w - - 0 1", Wdl::Win),
+    ("4k3/8/8/8/8/8/8/3QK3 b - - 0 1", Wdl::Loss),
+    ("4k3/8/8/8/8/8/8/R3K3 w - - 0 1", Wdl::Win),
+    ("4k3/8/8/8/8/8/8/R3K3 b - - 0 1", Wdl::Loss),
+    // The same with the colours swapped
+    ("3qk3/8/8/8/8/8/8/4K3 b - - 0 1", Wdl::Win),
+    ("3qk3/8/8/8/8/8/8/4K3 w - - 0 1", Wdl::Loss),
+    // The king takes the undefended queen
+    ("8/8/8/8/8/8/1q6/K6k w - - 0 1", Wdl::Draw),
+    // A king on the sixth rank in front of its pawn wins whoever moves
+    ("4k3/8/4K3/4P3/8/8/8/8 w - - 0 1", Wdl::Win),
+    ("4k3/8/4K3/4P3/8/8/8/8 b - - 0 1", Wdl::Loss),
+    // The defending king holds the corner against a rook pawn
+    ("k7/8/K7/P7/8/8/8/8 w - - 0 1", Wdl::Draw),
+    ("k7/8/K7/P7/8/8/8/8 b - - 0 1", Wdl::Draw),
+    // Two knights cannot force mate, but can give one
+    ("4k3/8/8/8/8/8/8/1N2K1N1 w - - 0 1", Wdl::Draw),
+    ("7k/5K2/8/6N1/5N2/8/8/8 w - - 0 1", Wdl::Win),
+    ("4k3/8/8/8/8/8/8/2B1K3 w - - 0 1", Wdl::Draw)
+];
+// Ra8 is the only mate
+const MATE_IN_ONE: &str = "7k/8/6K1/8/8/8/8/R7 w - - 0 1";
+
+// Small tables written by examples/syzygy_fixtures.rs, every test fails without them
+fn tablebase() -> Tablebase {
+    Tablebase::open(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/syzygy")).expect("No Syzygy tables in tests/syzygy")
+}
+
+#[test]
+fn probes_wdl() {
+    let tablebase = tablebase();
+    for (position, wdl) in POSITIONS {
+        assert_eq!(tablebase.probe_wdl(&fen::new_board(position).unwrap()), Some(wdl), "{}", position);
+    }
+}
+
+#[test]
+fn probes_dtz() {
+    let tablebase = tablebase();
+    for (position, wdl) in POSITIONS {
+        let dtz = tablebase.probe_dtz(&fen::new_board(position).unwrap()).unwrap_or_else(|| panic!("No DTZ for {}", position));
+        assert_eq!(dtz.signum(), (wdl as i32).signum(), "{} has DTZ {}", position, dtz);
+    }
+    assert_eq!(tablebase.probe_dtz(&fen::new_board(MATE_IN_ONE).unwrap()), Some(1));
+}
+
+#[test]
+fn picks_the_mating_move() {
+    let tablebase = tablebase();
+    let (mv, dtz) = tablebase.best_move(&fen::new_board(MATE_IN_ONE).unwrap()).unwrap();
+    assert_eq!((mv.to_string().as_str(), dtz), ("a1a8", 1));
+}
+
+// Following the best moves, the distance to zeroing drops by one every ply until a capture or a pawn move,
+// and the game ends in mate
+#[test]
+fn plays_the_win_out() {
+    let tablebase = tablebase();
+    for position in ["8/8/8/3k4/8/8/8/R3K3 w - - 0 1", "8/8/8/8/3k4/8/8/KQ6 b - - 0 1", "4k3/8/4K3/4P3/8/8/8/8 w - - 0 1"] {
+        let mut board = fen::new_board(position).unwrap();
+        let mut dtz = tablebase.probe_dtz(&board).unwrap();
+        let mut plies = 0;
+        loop {
+            assert!(plies < 100, "{} is not mated after {} plies", position, plies);
+            let (mv, _) = tablebase.best_move(&board).unwrap();
+            let pawn = board.bits.get_pieces(board.state.team_to_play, Pieces::PAWN).0 & (1 << mv.origin) != 0;
+            board.play_move(mv.origin, mv.target, mv.get_promotion(), true).unwrap();
+            plies += 1;
+            if board.generate_moves(board.state.team_to_play).is_empty() {
+                break;
+            }
+            let next = tablebase.probe_dtz(&board).unwrap();
+            if !pawn && !mv.is_capture() {
+                assert_eq!(next, -dtz + dtz.signum(), "{} after {}", position, mv);
+            }
+            dtz = next;
+        }
+        assert_eq!(dtz, 1, "{} is mated early", position);
+        assert!(board.is_in_check(board.state.team_to_play), "{} ends in stalemate", position);
+    }
+}
+
+#[test]
+fn leaves_positions_with_castling_rights_alone() {
+    assert_eq!(tablebase().probe_wdl(&fen::new_board("4k3/8/8/8/8/8/8/R3K3 w Q - 0 1").unwrap()), None);
+}
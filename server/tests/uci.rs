@@ -0,0 +1,73 @@
+use std::io::{BufRead, BufReader, Lines, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+
+// Castling is the only mate, the king on g1 taking g2 from its opponent
+const CASTLING_MATES: &str = "8/8/8/8/4ppp1/4pkp1/8/2N1K2R w K - 0 1";
+// The king already stands on its castled square, so castling only moves the rook, the one way it reaches f1
+const ROOK_ONLY_CASTLING_MATES: &str = "4rkr1/4p1p1/8/8/8/1B6/6PP/6KR w H - 0 1";
+
+// The engine binary run as `server uci`
+struct Engine {
+    child: Child,
+    stdin: ChildStdin,
+    lines: Lines<BufReader<ChildStdout>>
+}
+
+impl Engine {
+    fn start(chess960: bool) -> Engine {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_server"))
+            .arg("uci")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .expect("Could not start the engine");
+        let stdin = child.stdin.take().unwrap();
+        let lines = BufReader::new(child.stdout.take().unwrap()).lines();
+        let mut engine = Engine { child, stdin, lines };
+        engine.send("uci");
+        engine.wait_for("uciok");
+        engine.send(&format!("setoption name UCI_Chess960 value {}", chess960));
+        engine
+    }
+
+    fn send(&mut self, command: &str) {
+        writeln!(self.stdin, "{}", command).unwrap();
+    }
+
+    fn wait_for(&mut self, prefix: &str) -> String {
+        self.lines.by_ref()
+            .map(|line| line.expect("The engine exited"))
+            .find(|line| line.starts_with(prefix))
+            .unwrap_or_else(|| panic!("The engine never answered {}", prefix))
+    }
+
+    fn best_move(&mut self, position: &str) -> String {
+        self.send(&format!("position {}", position));
+        self.send("go depth 3");
+        self.wait_for("bestmove ")["bestmove ".len()..].to_string()
+    }
+}
+
+impl Drop for Engine {
+    fn drop(&mut self) {
+        let _ = writeln!(self.stdin, "quit");
+        let _ = self.child.wait();
+    }
+}
+
+#[test]
+fn castles_as_the_king_taking_its_rook_in_chess960() {
+    let mut engine = Engine::start(true);
+    assert_eq!(engine.best_move(&format!("fen {}", CASTLING_MATES)), "e1h1");
+    assert_eq!(engine.best_move(&format!("fen {}", ROOK_ONLY_CASTLING_MATES)), "g1h1");
+    // Read back, the castling leaves black mated with no move to answer
+    assert_eq!(engine.best_move(&format!("fen {} moves e1h1", CASTLING_MATES)), "0000");
+    assert_eq!(engine.best_move(&format!("fen {} moves g1h1", ROOK_ONLY_CASTLING_MATES)), "0000");
+}
+
+#[test]
+fn castles_as_the_king_stepping_two_squares_otherwise() {
+    let mut engine = Engine::start(false);
+    assert_eq!(engine.best_move(&format!("fen {}", CASTLING_MATES)), "e1g1");
+    assert_eq!(engine.best_move(&format!("fen {} moves e1g1", CASTLING_MATES)), "0000");
+}